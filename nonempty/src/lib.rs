@@ -165,6 +165,14 @@ impl<T> NonEmptyWithCursor<T> {
   /// ```
   ///
   /// ```
+  /// use nonempty::NonEmptyWithCursor;
+  /// let mut ne = NonEmptyWithCursor::new_with_rest(1, vec![2, 3]);
+  /// ne.set_cursor(2);
+  /// assert_eq!(ne.remove(2).unwrap(), 3);
+  /// assert_eq!(ne.get_cursor(), 0); // Cursor wrapped around to the first element
+  /// ```
+  ///
+  /// ```
   /// use nonempty::{NonEmptyWithCursor, Error};
   /// let mut ne = NonEmptyWithCursor::new(1);
   /// assert_eq!(ne.remove(0), Err(Error::RemoveLastElement))
@@ -173,10 +181,34 @@ impl<T> NonEmptyWithCursor<T> {
     let r = self.data.remove(index)?;
     if index < self.cursor {
       self.cursor -= 1;
+    } else if self.cursor >= self.data.len() {
+      self.cursor = 0;
     }
     Ok(r)
   }
 
+  /// Insert an element at an index, shifting everything after it to the right. The cursor is
+  /// adjusted so that it points at the same element.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use nonempty::{NonEmptyWithCursor, Error};
+  /// let mut ne = NonEmptyWithCursor::new_with_rest(1, vec![3]);
+  /// ne.set_cursor(1);
+  /// assert_eq!(ne.insert(1, 2), Ok(()));
+  /// assert_eq!(ne.get_cursor(), 2); // Cursor adjusted right
+  /// assert_eq!(ne.get_current(), &3);
+  /// assert_eq!(ne.insert(4, 5), Err(Error::OutOfBounds { index: 4, length: 3 }));
+  /// ```
+  pub fn insert(&mut self, index: usize, t: T) -> Result<(), Error> {
+    self.data.insert(index, t)?;
+    if index <= self.cursor {
+      self.cursor += 1;
+    }
+    Ok(())
+  }
+
   /// See Vec::sort_by_key. Note that the cursor is NOT affected.
   pub fn sort_by_key<B, F>(&mut self, f: F)
   where
@@ -341,6 +373,29 @@ impl<T> NonEmpty<T> {
     }
   }
 
+  /// Insert an element at an index, shifting everything after it to the right.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use nonempty::{NonEmpty, Error};
+  ///
+  /// let mut ne = NonEmpty::new_with_rest(1, vec![3]);
+  /// assert_eq!(ne.insert(1, 2), Ok(()));
+  /// assert_eq!(ne, NonEmpty::new_with_rest(1, vec![2, 3]));
+  /// // Inserting at the end is like pushing:
+  /// assert_eq!(ne.insert(3, 4), Ok(()));
+  /// // But inserting past the end is an error:
+  /// assert_eq!(ne.insert(5, 6), Err(Error::OutOfBounds { index: 5, length: 4 }));
+  /// ```
+  pub fn insert(&mut self, idx: usize, t: T) -> Result<(), Error> {
+    if idx > self.len() {
+      return Err(Error::OutOfBounds { index: idx, length: self.len() });
+    }
+    self.0.insert(idx, t);
+    Ok(())
+  }
+
   /// See Vec::sort_by_key.
   ///
   /// # Examples
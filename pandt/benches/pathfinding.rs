@@ -0,0 +1,59 @@
+#[macro_use]
+extern crate criterion;
+extern crate pandt;
+
+use criterion::Criterion;
+
+use pandt::grid::TerrainMap;
+use pandt::types::{u32cm, Point3, Terrain, TileSystem, Volume, AABB};
+
+/// A 200x200 square of open terrain, centered on the origin.
+fn open_map() -> Terrain {
+  let mut map = Terrain::new();
+  for x in -100..100 {
+    for y in -100..100 {
+      map.insert(Point3::new(x * 100, y * 100, 0));
+    }
+  }
+  map
+}
+
+/// The same square, with a wall on every fourth column that has a gap at alternating ends, so paths
+/// have to snake back and forth.
+fn maze_map() -> Terrain {
+  let mut map = open_map();
+  for x in (-100..100).filter(|x| x % 4 == 0) {
+    let gap = if x % 8 == 0 { 99 } else { -100 };
+    for y in (-100..100).filter(|y| *y != gap) {
+      map.remove(&Point3::new(x * 100, y * 100, 0));
+    }
+  }
+  map
+}
+
+fn medium_creature() -> Volume {
+  Volume::AABB(AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100) })
+}
+
+fn bench_get_all_accessible(c: &mut Criterion, name: &str, terrain: Terrain, speed: u32) {
+  let ts = TileSystem::Realistic;
+  let start = Point3::new(100, 0, 0);
+  c.bench_function(name, move |b| {
+    b.iter(|| {
+      ts.get_all_accessible(start, TerrainMap::plain(&terrain), medium_creature(), u32cm(speed))
+    })
+  });
+}
+
+fn get_all_accessible_open(c: &mut Criterion) {
+  bench_get_all_accessible(c, "get_all_accessible open 200x200, 10m", open_map(), 1000);
+  bench_get_all_accessible(c, "get_all_accessible open 200x200, 30m", open_map(), 3000);
+}
+
+fn get_all_accessible_maze(c: &mut Criterion) {
+  bench_get_all_accessible(c, "get_all_accessible maze 200x200, 10m", maze_map(), 1000);
+  bench_get_all_accessible(c, "get_all_accessible maze 200x200, 30m", maze_map(), 3000);
+}
+
+criterion_group!(benches, get_all_accessible_open, get_all_accessible_maze);
+criterion_main!(benches);
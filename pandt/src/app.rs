@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 use std::path::Path;
 
+use crate::dice::{Distribution, SeededRoller};
 use crate::types::*;
 
 // random misplaced notes
@@ -40,8 +41,19 @@ impl App {
     let snapshots = VecDeque::with_capacity(SNAPSHOTS);
     App { current_game: g, snapshots: snapshots }
   }
+  /// Perform a command, rolling any dice with a freshly-seeded `SeededRoller`.
   pub fn perform_command(
     &mut self, cmd: GameCommand, saved_game_path: &Path, module_path: Option<&Path>
+  ) -> Result<(&Game, Vec<GameLog>), GameError> {
+    let seed = SeededRoller::from_entropy().seed();
+    self.perform_command_with_seed(cmd, seed, saved_game_path, module_path)
+  }
+
+  /// Perform a command, rolling any dice with a `SeededRoller` created from the given seed. If any
+  /// dice were rolled, the seed is recorded as a `GameLog::DiceSeed` at the start of the resulting
+  /// logs, so that the command can be performed again with identical results.
+  pub fn perform_command_with_seed(
+    &mut self, cmd: GameCommand, seed: u64, saved_game_path: &Path, module_path: Option<&Path>
   ) -> Result<(&Game, Vec<GameLog>), GameError> {
     match cmd {
       GameCommand::Rollback(ref snapshot_idx, ref log_idx) => {
@@ -52,8 +64,14 @@ impl App {
         Ok((&self.current_game, vec![log]))
       }
       _ => {
-        let (game, logs) =
-          self.current_game.perform_command(cmd.clone(), saved_game_path, module_path)?.done();
+        let mut rng = SeededRoller::new(seed);
+        let (game, mut logs) = self
+          .current_game
+          .perform_command(cmd.clone(), saved_game_path, module_path, &mut rng)?
+          .done();
+        if rng.rolls() > 0 {
+          logs.insert(0, GameLog::DiceSeed(seed));
+        }
 
         if self.snapshots.is_empty()
           || self.snapshots.back().unwrap().1.len() + logs.len() > LOGS_PER_SNAP
@@ -113,6 +131,12 @@ impl App {
     self.current_game.get_movement_options(scene, creature_id)
   }
 
+  pub fn get_visible_tiles(
+    &self, scene: SceneID, creature_id: CreatureID
+  ) -> Result<Vec<Point3>, GameError> {
+    self.current_game.get_visible_tiles(scene, creature_id)
+  }
+
   pub fn get_combat_movement_options(&self) -> Result<Vec<Point3>, GameError> {
     Ok(self.current_game.get_combat()?.current_movement_options()?)
   }
@@ -123,9 +147,15 @@ impl App {
     self.current_game.get_target_options(scene, cid, abid)
   }
 
+  pub fn get_multi_part_target_options(
+    &self, scene: SceneID, cid: CreatureID, abid: AbilityID, decided: &[DecidedTarget]
+  ) -> Result<PotentialTargets, GameError> {
+    self.current_game.get_multi_part_target_options(scene, cid, abid, decided)
+  }
+
   pub fn preview_volume_targets(
     &self, sid: SceneID, actor_id: CreatureID, ability_id: AbilityID, pt: Point3
-  ) -> Result<(Vec<CreatureID>, Vec<Point3>), GameError> {
+  ) -> Result<(Vec<CreatureID>, Vec<Point3>, Option<Distribution>), GameError> {
     let scene = self.current_game.get_scene(sid)?;
     self.current_game.preview_volume_targets(scene, actor_id, ability_id, pt)
   }
@@ -194,7 +224,11 @@ mod test {
     // 1
     perf(
       &mut app,
-      GameCommand::StartCombat(t_scene_id(), vec![cid_ranger(), cid_rogue(), cid_cleric()]),
+      GameCommand::StartCombat(
+        t_scene_id(),
+        vec![cid_ranger(), cid_rogue(), cid_cleric()],
+        InitiativeMode::Individual,
+      ),
     ).unwrap();
     // 2
     perf(&mut app, GameCommand::StopCombat).unwrap();
@@ -207,6 +241,52 @@ mod test {
     assert_eq!(scene.get_pos(cid_ranger()).unwrap(), Point3::new(0, 0, 0));
   }
 
+  /// Performing a command with the same seed produces the same rolls, and the seed is logged.
+  #[test]
+  fn seeded_commands_are_reproducible() {
+    let mut app = t_app();
+    app.current_game.creatures.mutate(&cid_ranger(), |c| c.initiative = Dice::expr(1, 20));
+    let cids = vec![cid_rogue(), cid_ranger(), cid_cleric()];
+    let cmd = GameCommand::StartCombat(t_scene_id(), cids, InitiativeMode::Individual);
+    let path = PathBuf::from("");
+    let mut app2 = app.clone();
+    let logs = app.perform_command_with_seed(cmd.clone(), 42, &path, None).unwrap().1;
+    let logs2 = app2.perform_command_with_seed(cmd, 42, &path, None).unwrap().1;
+    assert_eq!(logs[0], GameLog::DiceSeed(42));
+    assert_eq!(logs, logs2);
+  }
+
+  /// Replaying an area ability with the same seed rolls the same dice for each creature it hits,
+  /// no matter how the creatures happen to be hashed.
+  #[test]
+  fn seeded_area_abilities_are_reproducible() {
+    let mut app = t_app();
+    app.current_game.abilities.mutate(&abid_fireball(), |ab| {
+      if let Action::Creature { ref mut effect, .. } = ab.action {
+        *effect = CreatureEffect::Damage(Dice::expr(1, 20));
+      }
+    });
+    let target = DecidedTarget::Point(Point3::new(0, 0, 0));
+    let cmd = GameCommand::ActCreature(t_scene_id(), cid_cleric(), abid_fireball(), target);
+    let path = PathBuf::from("");
+    let logs = app.clone().perform_command_with_seed(cmd.clone(), 42, &path, None).unwrap().1;
+    assert_eq!(logs[0], GameLog::DiceSeed(42));
+    for _ in 0..10 {
+      let replayed = app.clone().perform_command_with_seed(cmd.clone(), 42, &path, None).unwrap().1;
+      assert_eq!(replayed, logs);
+    }
+  }
+
+  /// Commands which don't roll any dice don't record a seed.
+  #[test]
+  fn seed_only_logged_when_rolling() {
+    let mut app = t_app();
+    let cmd = GameCommand::SetCreaturePos(t_scene_id(), cid_ranger(), Point3::new(1, 1, 1));
+    let logs = app.perform_command_with_seed(cmd, 42, &PathBuf::from(""), None).unwrap().1;
+    let expected = GameLog::SetCreaturePos(t_scene_id(), cid_ranger(), Point3::new(1, 1, 1));
+    assert_eq!(logs, vec![expected]);
+  }
+
   ///
   #[test]
   fn rollback_through_rollback() {
@@ -3,8 +3,10 @@
 use nonempty;
 use num::{Saturating, Zero};
 
+use crate::dice::DiceRoller;
 use crate::types::*;
 
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 
 /// This is set to 1.5 so that it's greater than sqrt(2) -- meaning that creatures can attack
@@ -12,6 +14,9 @@ use std::marker::PhantomData;
 pub const MELEE_RANGE: u32units::Length =
   u32units::Length { dimension: PhantomData, units: PhantomData, value: 150 };
 
+/// How many reactions (opportunity attacks and readied actions) each creature gets per round.
+pub const REACTIONS_PER_ROUND: u8 = 1;
+
 impl<'game> DynamicCombat<'game> {
   pub fn remove_from_combat(&self, cid: CreatureID) -> Result<Option<Combat>, GameError> {
     self.combat.remove_from_combat(cid)
@@ -25,51 +30,224 @@ impl<'game> DynamicCombat<'game> {
       }
       CombatLog::EndTurn(ref cid) => {
         assert_eq!(*cid, new.current_creature_id());
-        new.creatures.next_circular();
-        new.movement_used = Zero::zero();
+        let members = new.turn_members(self.game)?;
+        new.skip_to_end_of_turn(self.game)?;
+        new.resuming.retain(|c| !members.contains(c));
+        new.next_creature();
+        new.start_turn(self.game)?;
       }
       CombatLog::RerollInitiative(ref combatants) => {
         if new.creatures.get_cursor() != 0 {
           bail!(GameError::MustRerollAtStartOfRound);
         }
-        new.creatures = sort_combatants(combatants.clone())?;
+        new.creatures = sort_combatants(&new.initiative_mode, combatants.clone())?;
+        new.delayed.clear();
+        new.resuming.clear();
       }
       CombatLog::ChangeCreatureInitiative(cid, new_init) => {
-        let cursor = new.creatures.get_cursor();
+        let current = new.current_creature_id();
         let update_init = |&(c, i)| if c == cid { (c, new_init) } else { (c, i) };
         let creatures_with_inits =
-          sort_combatants(new.creatures.iter().map(update_init).collect())?;
+          sort_combatants(&new.initiative_mode, new.creatures.iter().map(update_init).collect())?;
         new.creatures = creatures_with_inits;
-        new.creatures.set_cursor(cursor);
+        new.set_current_creature(current)?;
+      }
+      CombatLog::SetInitiativeMode(ref mode) => {
+        let current = new.current_creature_id();
+        new.creatures = sort_combatants(mode, new.creatures.iter().cloned().collect())?;
+        new.initiative_mode = mode.clone();
+        new.set_current_creature(current)?;
       }
       CombatLog::ForceNextTurn => {
-        new.movement_used = Zero::zero();
-        new.creatures.next_circular();
+        let members = new.turn_members(self.game)?;
+        new.skip_to_end_of_turn(self.game)?;
+        new.resuming.retain(|c| !members.contains(c));
+        new.next_creature();
+        new.start_turn(self.game)?;
       }
       CombatLog::ForcePrevTurn => {
-        new.movement_used = Zero::zero();
+        let (start, _) = new.turn_slot(self.game, new.creatures.get_cursor())?;
+        new.creatures.set_cursor(start);
+        if new.creatures.get_cursor() == 0 && new.round > 1 {
+          new.round -= 1;
+        }
         new.creatures.prev_circular();
+        let (start, _) = new.turn_slot(self.game, new.creatures.get_cursor())?;
+        new.creatures.set_cursor(start);
+        new.start_turn(self.game)?;
+      }
+      CombatLog::QueueInterrupt(ref interrupt) => {
+        new.pending_interrupts.push(interrupt.clone());
+      }
+      CombatLog::ResolveInterrupt => {
+        if new.pending_interrupts.is_empty() {
+          bail!(GameError::NoInterruptPending);
+        }
+        new.pending_interrupts.remove(0);
+      }
+      CombatLog::UseReaction(cid) => {
+        if new.reactions_left(cid) == 0 {
+          bail!(GameError::NoReactionLeft(cid));
+        }
+        *new.reactions_used.entry(cid).or_insert(0) += 1;
+      }
+      CombatLog::ReadyAction(cid, ref readied) => {
+        new.readied_actions.insert(cid, readied.clone());
+      }
+      CombatLog::TriggerReadiedAction(cid) => {
+        let readied = new
+          .readied_actions
+          .remove(&cid)
+          .ok_or_else(|| GameError::BuggyProgram("No readied action to trigger".to_string()))?;
+        new.pending_interrupts.push(Interrupt {
+          creature_id: cid,
+          ability_id: readied.ability_id,
+          target: readied.target,
+        });
+      }
+      CombatLog::VolumeConditionsApplied(cid, ref condition_ids) => {
+        let applied = new.volume_conditions_applied.entry(cid).or_insert_with(HashSet::new);
+        applied.extend(condition_ids.iter().cloned());
+      }
+      CombatLog::UseAction(slot) => {
+        if !new.has_action_left(slot) {
+          bail!(GameError::NoActionLeft(new.current_creature_id(), slot));
+        }
+        *new.actions_used.entry(slot).or_insert(0) += 1;
+      }
+      CombatLog::SetActionBudget(budget) => {
+        new.action_budget = budget;
+      }
+      CombatLog::DelayTurn(cid) => {
+        assert_eq!(cid, new.current_creature_id());
+        let cursor = new.creatures.get_cursor();
+        let was_last = cursor == new.creatures.len() - 1;
+        match new.creatures.remove(cursor) {
+          Ok(_) => {}
+          Err(nonempty::Error::RemoveLastElement) => bail!(GameError::CombatMustHaveCreatures),
+          Err(nonempty::Error::OutOfBounds { .. }) => {
+            bail!(GameError::BuggyProgram("Cursor out of bounds in DelayTurn".to_string()))
+          }
+        }
+        new.delayed.push(cid);
+        new.resuming.retain(|c| *c != cid);
+        if was_last {
+          new.round += 1;
+        }
+        new.start_turn(self.game)?;
+      }
+      CombatLog::ResumeTurn(cid) => {
+        let idx =
+          new.delayed.iter().position(|c| *c == cid).ok_or(GameError::CreatureNotDelayed(cid))?;
+        new.delayed.remove(idx);
+        let init = new.creatures.get_current().1;
+        // Creatures that resume after the same turn go in the order they resumed in.
+        let (_, end) = new.turn_slot(self.game, new.creatures.get_cursor())?;
+        let mut pos = end + 1;
+        while new.creatures.get(pos).map_or(false, |&(c, _)| new.resuming.contains(&c)) {
+          pos += 1;
+        }
+        new.creatures.insert(pos, (cid, init)).map_err(|_| {
+          GameError::BuggyProgram("Couldn't insert a resuming creature".to_string())
+        })?;
+        new.resuming.push(cid);
+      }
+      CombatLog::SwitchTurnMember(cid) => {
+        let (start, end) = new.turn_slot(self.game, new.creatures.get_cursor())?;
+        let idx = (start..=end)
+          .find(|&idx| new.creatures.get(idx).map(|&(c, _)| c) == Some(cid))
+          .ok_or(GameError::CreatureNotInTurn(cid))?;
+        let current = new.current_creature_id();
+        new.group_movement_used.insert(current, new.movement_used);
+        new.group_actions_used.insert(current, new.actions_used.clone());
+        new.movement_used = new.group_movement_used.remove(&cid).unwrap_or_else(Zero::zero);
+        new.actions_used = new.group_actions_used.remove(&cid).unwrap_or_default();
+        new.creatures.set_cursor(idx);
       }
     }
     Ok(new)
   }
 
   pub fn next_turn(&self) -> Result<ChangedCombat<'game>, GameError> {
+    if !self.combat.pending_interrupts.is_empty() {
+      bail!(GameError::InterruptsPending);
+    }
     let change = self.change_with(CombatLog::EndTurn(self.current_creature()?.id()))?;
     Ok(change)
   }
 
+  /// Take the current creature out of the order until it resumes its turn. This is only allowed
+  /// before it has moved or acted.
+  pub fn delay_turn(&self) -> Result<ChangedCombat<'game>, GameError> {
+    if !self.combat.pending_interrupts.is_empty() {
+      bail!(GameError::InterruptsPending);
+    }
+    let cid = self.current_creature()?.id();
+    if self.combat.has_acted() {
+      bail!(GameError::CannotDelayAfterActing(cid));
+    }
+    if self.combat.turn_members(self.game)?.len() > 1 {
+      bail!(GameError::CannotDelayGroupTurn(cid));
+    }
+    self.change_with(CombatLog::DelayTurn(cid))
+  }
+
+  /// Hand the current group turn to another creature taking part in it.
+  pub fn switch_turn_member(&self, cid: CreatureID) -> Result<ChangedCombat<'game>, GameError> {
+    if !self.combat.pending_interrupts.is_empty() {
+      bail!(GameError::InterruptsPending);
+    }
+    self.change_with(CombatLog::SwitchTurnMember(cid))
+  }
+
   pub fn current_movement_options(&self) -> Result<Vec<Point3>, GameError> {
     let current = self.current_creature()?;
     let current_speed = current.speed().saturating_sub(self.combat.movement_used);
     Ok(self.game.tile_system.get_all_accessible(
       self.current_pos()?,
-      &self.scene.terrain,
+      self.scene.terrain_map(),
       Volume::AABB(current.creature.size),
       current_speed,
     ))
   }
 
+  /// Find the first step of a path at which a creature moving along it leaves the melee reach of a
+  /// creature with an opportunity attack that regards it as an enemy. Returns the index of the last
+  /// point of the path before that step, along with the threatening creature's reaction.
+  pub fn opportunity_attack(
+    &self, mover: CreatureID, path: &[Point3]
+  ) -> Result<Option<(usize, Interrupt)>, GameError> {
+    let tile_system = self.game.tile_system;
+    let size = self.game.get_creature(mover)?.creature.size;
+    let target = DecidedTarget::Creature(mover);
+    let mut threats = vec![];
+    for cid in self.combat.creature_ids() {
+      let creature = self.game.get_creature(cid)?;
+      if cid == mover || !self.combat.can_react(cid) || !creature.can_act() {
+        continue;
+      }
+      if self.game.creature_relationship(cid, mover)? != Relationship::Enemy {
+        continue;
+      }
+      if let Some(ability_id) = self.game.get_class(creature.creature.class)?.opportunity_attack {
+        threats.push((cid, ability_id, self.scene.creature_tiles(self.game, cid)?));
+      }
+    }
+    for (idx, step) in path.windows(2).enumerate() {
+      let before = tile_system.footprint(step[0], size);
+      let after = tile_system.footprint(step[1], size);
+      for &(creature_id, ability_id, ref tiles) in &threats {
+        if tile_system.footprints_within_distance(tiles, &before, MELEE_RANGE)
+          && !tile_system.footprints_within_distance(tiles, &after, MELEE_RANGE)
+        {
+          let interrupt = Interrupt { creature_id, ability_id, target: target.clone() };
+          return Ok(Some((idx, interrupt)));
+        }
+      }
+    }
+    Ok(None)
+  }
+
   pub fn get_movement(&'game self) -> Result<CombatMove<'game>, GameError> {
     let current = self.current_creature()?;
     if current.can_move() {
@@ -82,9 +260,30 @@ impl<'game> DynamicCombat<'game> {
     }
   }
 
-  pub fn reroll_initiative(&self) -> Result<ChangedCombat<'game>, GameError> {
+  /// Queue up the interrupts of every creature whose readied action is set off by a trigger.
+  pub fn trigger_readied_actions(
+    &self, trigger: ReadyTrigger
+  ) -> Result<ChangedCombat<'game>, GameError> {
+    let mut triggered: Vec<CreatureID> = self
+      .combat
+      .readied_actions
+      .iter()
+      .filter(|&(cid, readied)| readied.trigger == trigger && self.combat.can_react(*cid))
+      .map(|(cid, _)| *cid)
+      .collect();
+    triggered.sort();
+    let mut change = self.change();
+    for cid in triggered {
+      change = change.apply(&CombatLog::TriggerReadiedAction(cid))?;
+    }
+    Ok(change)
+  }
+
+  pub fn reroll_initiative(
+    &self, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedCombat<'game>, GameError> {
     let cids = self.combat.creature_ids();
-    let combatants = Combat::roll_initiative(self.game, cids)?;
+    let combatants = Combat::roll_initiative(self.game, &self.combat.initiative_mode, cids, rng)?;
     self.change_with(CombatLog::RerollInitiative(combatants))
   }
 
@@ -107,45 +306,293 @@ impl<'game> DynamicCombat<'game> {
 }
 
 fn sort_combatants(
-  mut combatants: Vec<(CreatureID, i16)>
+  mode: &InitiativeMode, mut combatants: Vec<(CreatureID, i16)>
 ) -> Result<nonempty::NonEmptyWithCursor<(CreatureID, i16)>, GameError> {
   combatants.sort_by_key(|&(_, i)| -i);
+  if let InitiativeMode::Sides(ref sides) = *mode {
+    combatants = alternate_sides(sides, combatants);
+  }
   nonempty::NonEmptyWithCursor::from_vec(combatants)
     .ok_or_else(|| GameError::CombatMustHaveCreatures.into())
 }
 
+/// Rearrange combatants that are already sorted by initiative so that turns alternate between
+/// sides. Sides go in the order of their best initiative.
+fn alternate_sides(
+  sides: &[Vec<CreatureID>], combatants: Vec<(CreatureID, i16)>
+) -> Vec<(CreatureID, i16)> {
+  let side_of = |cid| sides.iter().position(|side| side.contains(&cid)).unwrap_or(sides.len());
+  let mut queues: Vec<(usize, Vec<(CreatureID, i16)>)> = vec![];
+  for combatant in combatants {
+    let side = side_of(combatant.0);
+    match queues.iter().position(|&(s, _)| s == side) {
+      Some(idx) => queues[idx].1.push(combatant),
+      None => queues.push((side, vec![combatant])),
+    }
+  }
+  let longest = queues.iter().map(|&(_, ref queue)| queue.len()).max().unwrap_or(0);
+  let mut alternated = vec![];
+  for turn in 0..longest {
+    alternated.extend(queues.iter().filter_map(|&(_, ref queue)| queue.get(turn)));
+  }
+  alternated
+}
+
+/// The initiative group that a creature belongs to, if it shares its roll with other creatures.
+#[derive(PartialEq)]
+enum InitiativeGroup {
+  Class(ClassID),
+  Listed(usize),
+}
+
+fn initiative_group(
+  game: &Game, mode: &InitiativeMode, cid: CreatureID
+) -> Result<Option<InitiativeGroup>, GameError> {
+  Ok(match *mode {
+    InitiativeMode::GroupByClass => {
+      Some(InitiativeGroup::Class(game.get_creature(cid)?.creature.class))
+    }
+    InitiativeMode::Groups(ref groups) => {
+      groups.iter().position(|group| group.contains(&cid)).map(InitiativeGroup::Listed)
+    }
+    InitiativeMode::Individual | InitiativeMode::Sides(_) => None,
+  })
+}
+
 impl Combat {
   pub fn new(scene: SceneID, combatants: Vec<(CreatureID, i16)>) -> Result<Combat, GameError> {
     Ok(Combat {
       scene: scene,
       movement_used: Zero::zero(),
-      creatures: sort_combatants(combatants)?,
+      creatures: sort_combatants(&InitiativeMode::Individual, combatants)?,
+      round: 1,
+      pending_interrupts: vec![],
+      reactions_used: HashMap::new(),
+      readied_actions: HashMap::new(),
+      volume_conditions_applied: HashMap::new(),
+      action_budget: Default::default(),
+      actions_used: HashMap::new(),
+      group_movement_used: HashMap::new(),
+      group_actions_used: HashMap::new(),
+      delayed: vec![],
+      resuming: vec![],
+      initiative_mode: InitiativeMode::Individual,
     })
   }
 
-  pub fn creature_ids(&self) -> Vec<CreatureID> { self.creatures.iter().map(|&(c, _)| c).collect() }
+  /// Get the IDs of all the creatures in the combat, including the ones that have delayed their
+  /// turns.
+  pub fn creature_ids(&self) -> Vec<CreatureID> {
+    let mut cids: Vec<CreatureID> = self.creatures.iter().map(|&(c, _)| c).collect();
+    cids.extend(&self.delayed);
+    cids
+  }
 
+  /// Roll initiative for some creatures. Creatures in the same group share the roll of the first
+  /// one of them, and are kept together so that ties with other creatures don't split them up.
   pub fn roll_initiative(
-    game: &Game, cids: Vec<CreatureID>
+    game: &Game, mode: &InitiativeMode, cids: Vec<CreatureID>, rng: &mut dyn DiceRoller
   ) -> Result<Vec<(CreatureID, i16)>, GameError> {
-    cids
-      .iter()
-      .map(|cid| {
-        let creature = game.get_creature(*cid)?;
-        Ok((*cid, creature.creature.initiative.roll().1 as i16))
-      })
-      .collect::<Result<Vec<(CreatureID, i16)>, GameError>>()
+    let mut rolled: Vec<(Option<InitiativeGroup>, Vec<(CreatureID, i16)>)> = vec![];
+    for cid in cids {
+      let group = initiative_group(game, mode, cid)?;
+      let shared = match group {
+        Some(_) => rolled.iter().position(|&(ref g, _)| *g == group),
+        None => None,
+      };
+      if let Some(idx) = shared {
+        let init = rolled[idx].1[0].1;
+        rolled[idx].1.push((cid, init));
+        continue;
+      }
+      let creature = game.get_creature(cid)?;
+      rolled.push((group, vec![(cid, creature.creature.initiative.roll(rng).1 as i16)]));
+    }
+    Ok(rolled.into_iter().flat_map(|(_, members)| members).collect())
+  }
+
+  /// Find the initiative that a creature joining the combat should share with the members of its
+  /// group who are already fighting, if there are any.
+  pub fn group_initiative(&self, game: &Game, cid: CreatureID) -> Result<Option<i16>, GameError> {
+    let group = initiative_group(game, &self.initiative_mode, cid)?;
+    if group.is_none() {
+      return Ok(None);
+    }
+    for &(c, init) in self.creatures.iter() {
+      if initiative_group(game, &self.initiative_mode, c)? == group {
+        return Ok(Some(init));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Put a creature joining the combat into the order. It goes right after the other members of
+  /// its group, or wherever its side's turns alternate with the others' in `Sides` mode. Otherwise
+  /// it comes last.
+  pub fn add_creature(
+    &mut self, game: &Game, cid: CreatureID, init: i16
+  ) -> Result<(), GameError> {
+    if let InitiativeMode::Sides(_) = self.initiative_mode {
+      let current = self.current_creature_id();
+      let mut combatants: Vec<(CreatureID, i16)> = self.creatures.iter().cloned().collect();
+      combatants.push((cid, init));
+      self.creatures = sort_combatants(&self.initiative_mode, combatants)?;
+      return self.set_current_creature(current);
+    }
+    let group = initiative_group(game, &self.initiative_mode, cid)?;
+    let mut last_member = None;
+    if group.is_some() {
+      for (idx, &(c, _)) in self.creatures.iter().enumerate() {
+        if initiative_group(game, &self.initiative_mode, c)? == group {
+          last_member = Some(idx);
+        }
+      }
+    }
+    match last_member {
+      Some(idx) => self
+        .creatures
+        .insert(idx + 1, (cid, init))
+        .map_err(|_| GameError::BuggyProgram("Couldn't insert after a group member".to_string()))?,
+      None => self.creatures.push((cid, init)),
+    }
+    Ok(())
   }
 
   pub fn current_creature_id(&self) -> CreatureID { self.creatures.get_current().0 }
 
+  /// Whether the current creature is the first one of the round.
+  pub fn is_start_of_round(&self) -> bool { self.creatures.get_cursor() == 0 }
+
+  /// Check whether the current creature has moved or acted yet this turn.
+  pub fn has_acted(&self) -> bool { !self.movement_used.is_zero() || !self.actions_used.is_empty() }
+
+  /// Point the cursor at a creature, wherever it is in the order.
+  fn set_current_creature(&mut self, cid: CreatureID) -> Result<(), GameError> {
+    let idx = self
+      .creatures
+      .iter()
+      .position(|&(c, _)| c == cid)
+      .ok_or_else(|| GameError::CreatureNotFound(cid.to_string()))?;
+    self.creatures.set_cursor(idx);
+    Ok(())
+  }
+
+  /// Find the positions in the order of the first and last creatures that take their turn along
+  /// with the one at `idx`. In the grouped initiative modes, a run of creatures from the same group
+  /// takes a single turn together. A creature that resumed a delayed turn takes it on its own.
+  fn turn_slot(&self, game: &Game, idx: usize) -> Result<(usize, usize), GameError> {
+    let group_at = |idx: usize| -> Result<Option<InitiativeGroup>, GameError> {
+      match self.creatures.get(idx) {
+        Some(&(cid, _)) if !self.resuming.contains(&cid) => {
+          initiative_group(game, &self.initiative_mode, cid)
+        }
+        _ => Ok(None),
+      }
+    };
+    let group = group_at(idx)?;
+    if group.is_none() {
+      return Ok((idx, idx));
+    }
+    let (mut start, mut end) = (idx, idx);
+    while start > 0 && group_at(start - 1)? == group {
+      start -= 1;
+    }
+    while group_at(end + 1)? == group {
+      end += 1;
+    }
+    Ok((start, end))
+  }
+
+  /// Get the creatures taking the current turn, in order. This is just the current creature,
+  /// unless it's sharing a group turn.
+  pub fn turn_members(&self, game: &Game) -> Result<Vec<CreatureID>, GameError> {
+    let (start, end) = self.turn_slot(game, self.creatures.get_cursor())?;
+    Ok((start..=end).filter_map(|idx| self.creatures.get(idx)).map(|&(c, _)| c).collect())
+  }
+
+  /// Point the cursor at the last creature taking the current turn, so that moving on to the next
+  /// creature skips the rest of the group.
+  fn skip_to_end_of_turn(&mut self, game: &Game) -> Result<(), GameError> {
+    let (_, end) = self.turn_slot(game, self.creatures.get_cursor())?;
+    self.creatures.set_cursor(end);
+    Ok(())
+  }
+
+  /// Forget everything that's tracked for the creatures taking the current turn.
+  fn clear_turn(&mut self) {
+    self.movement_used = Zero::zero();
+    self.pending_interrupts.clear();
+    self.volume_conditions_applied.clear();
+    self.actions_used.clear();
+    self.group_movement_used.clear();
+    self.group_actions_used.clear();
+  }
+
+  /// Reset everything that's tracked per turn for the creatures taking the new current turn.
+  fn start_turn(&mut self, game: &Game) -> Result<(), GameError> {
+    self.clear_turn();
+    for cid in self.turn_members(game)? {
+      self.reactions_used.remove(&cid);
+      self.readied_actions.remove(&cid);
+    }
+    Ok(())
+  }
+
+  /// Move on to the next creature in the order, starting a new round if we've gone around.
+  fn next_creature(&mut self) {
+    self.creatures.next_circular();
+    if self.is_start_of_round() {
+      self.round += 1;
+    }
+  }
+
   pub fn contains_creature(&self, cid: CreatureID) -> bool {
-    self.creatures.iter().any(|&(c, _)| c == cid)
+    self.creatures.iter().any(|&(c, _)| c == cid) || self.delayed.contains(&cid)
+  }
+
+  pub fn reactions_left(&self, cid: CreatureID) -> u8 {
+    REACTIONS_PER_ROUND.saturating_sub(self.reactions_used.get(&cid).cloned().unwrap_or(0))
+  }
+
+  /// How many actions of each kind the current creature has left this turn, or `None` if there's
+  /// no action budget and they can act as often as they like.
+  pub fn actions_left(&self) -> Option<ActionBudget> {
+    let used = |slot| self.actions_used.get(&slot).cloned().unwrap_or(0);
+    self.action_budget.map(|budget| ActionBudget {
+      standard: budget.standard.saturating_sub(used(ActionSlot::Standard)),
+      minor: budget.minor.saturating_sub(used(ActionSlot::Minor)),
+    })
+  }
+
+  /// Check whether the current creature can still use an ability that takes up the given kind of
+  /// action.
+  pub fn has_action_left(&self, slot: ActionSlot) -> bool {
+    let left = match self.actions_left() {
+      Some(left) => left,
+      None => return true,
+    };
+    match slot {
+      ActionSlot::Standard => left.standard > 0,
+      ActionSlot::Minor => left.minor > 0,
+      ActionSlot::Free => true,
+    }
+  }
+
+  /// Check whether a creature could react to something, counting the interrupts it already has
+  /// queued against its reactions.
+  pub fn can_react(&self, cid: CreatureID) -> bool {
+    let queued = self.pending_interrupts.iter().filter(|i| i.creature_id == cid).count();
+    usize::from(self.reactions_left(cid)) > queued
   }
 
   /// the Option<Combat> will be None if you're removing the last creature from a combat.
   pub fn remove_from_combat(&self, cid: CreatureID) -> Result<Option<Combat>, GameError> {
     let mut combat = self.clone();
+    combat.resuming.retain(|c| *c != cid);
+    if let Some(idx) = combat.delayed.iter().position(|c| *c == cid) {
+      combat.delayed.remove(idx);
+      return Ok(Some(combat));
+    }
     let idx = combat
       .creatures
       .iter()
@@ -173,8 +620,11 @@ impl<'game> CombatMove<'game> {
   pub fn movement_left(&self) -> u32units::Length { self.movement_left }
 
   /// Take a series of 1-square "steps". Diagonals are allowed, but consume an accurate amount of
-  /// movement.
+  /// movement. The creature can't move on until any reactions to its movement are resolved.
   pub fn move_current(&self, pt: Point3) -> Result<crate::game::ChangedGame, GameError> {
+    if !self.combat.combat.pending_interrupts.is_empty() {
+      bail!(GameError::InterruptsPending);
+    }
     let (change, distance) = self.combat.game.path_creature_distance(
       self.combat.scene.id,
       self.combat.combat.current_creature_id(),
@@ -219,10 +669,7 @@ pub mod test {
   /// Create a Test combat. Combat order is rogue, ranger, then cleric.
   pub fn t_combat() -> Game {
     let game = t_game();
-    t_perform(
-      &game,
-      GameCommand::StartCombat(t_scene_id(), vec![cid_rogue(), cid_ranger(), cid_cleric()]),
-    )
+    t_start_combat(&game, vec![cid_rogue(), cid_ranger(), cid_cleric()])
   }
 
   pub fn t_act(
@@ -270,8 +717,11 @@ pub mod test {
       name: "MultiEffect".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      propagation: VolumePropagation::Unobstructed,
+      cooldown: 0,
+      slot: ActionSlot::Standard,
       action: Action::Creature {
-        target: CreatureTarget::Melee,
+        target: CreatureTarget::Melee { filter: TargetFilter::Any },
 
         effect: CreatureEffect::MultiEffect(vec![
           CreatureEffect::Damage(Dice::flat(3)),
@@ -346,13 +796,13 @@ pub mod test {
   #[test]
   fn move_honors_path() {
     let mut game = t_combat();
-    let terrain = vec![
+    let terrain = hashset!{
       Point3::new(0, 0, 0),
       Point3::new(0, 100, 0),
       Point3::new(100, 100, 0),
       Point3::new(200, 100, 0),
       Point3::new(200, 0, 0),
-    ];
+    };
     game.scenes.mutate(&t_scene_id(), move |s| s.terrain = terrain);
 
     let next_game = game
@@ -365,4 +815,511 @@ pub mod test {
       .game;
     assert_eq!(next_game.get_combat().unwrap().combat.movement_used, u32cm(400));
   }
+
+  /// Leaving the reach of a creature with an opportunity attack stops movement and queues its
+  /// reaction, but only once per turn.
+  #[test]
+  fn leaving_reach_triggers_opportunity_attack() {
+    let mut game = t_combat();
+    game.classes.mutate(&classid_ranger(), |c| c.opportunity_attack = Some(abid_punch()));
+    let game = t_perform(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0)));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_rogue()).unwrap(), Point3::new(100, 0, 0));
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.movement_used, u32cm(100));
+    assert_eq!(
+      combat.pending_interrupts,
+      vec![
+        Interrupt {
+          creature_id: cid_ranger(),
+          ability_id: abid_punch(),
+          target: DecidedTarget::Creature(cid_rogue()),
+        },
+      ]
+    );
+
+    // The rogue can't keep moving or act until the reaction is resolved.
+    match perf(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0))) {
+      Err(GameError::InterruptsPending) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    match t_act(&game, abid_punch(), DecidedTarget::Creature(cid_ranger())) {
+      Err(GameError::InterruptsPending) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+
+    let game = t_perform(&game, GameCommand::DeclineInterrupt);
+    let game = t_perform(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0)));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_rogue()).unwrap(), Point3::new(500, 0, 0));
+    assert!(game.get_combat().unwrap().combat.pending_interrupts.is_empty());
+  }
+
+  /// A dash that provokes an opportunity attack forfeits the strike after it, rather than striking
+  /// before the reaction is resolved.
+  #[test]
+  fn dashing_out_of_reach_forfeits_the_rest_of_the_action() {
+    let mut game = t_combat();
+    game.classes.mutate(&classid_ranger(), |c| c.opportunity_attack = Some(abid_punch()));
+    let dash = t_give_rogue_dash(&mut game);
+    let game = t_perform(
+      &game,
+      GameCommand::SetCreaturePos(t_scene_id(), cid_cleric(), Point3::new(500, 0, 0)),
+    );
+    let target = DecidedTarget::Multi(vec![
+      DecidedTarget::Point(Point3::new(400, 0, 0)),
+      DecidedTarget::Creature(cid_cleric()),
+    ]);
+    let game = t_game_act(&game, dash, target);
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_rogue()).unwrap(), Point3::new(100, 0, 0));
+    assert_eq!(game.get_creature(cid_cleric()).unwrap().creature.cur_health, HP(10));
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.pending_interrupts.len(), 1);
+    assert_eq!(combat.pending_interrupts[0].creature_id, cid_ranger());
+  }
+
+  #[test]
+  fn allies_dont_provoke_opportunity_attacks() {
+    let mut game = t_combat();
+    game.classes.mutate(&classid_ranger(), |c| c.opportunity_attack = Some(abid_punch()));
+    let faction_id = FactionID(uuid_0());
+    let name = "Party".to_string();
+    let faction = Faction { id: faction_id, name, relationships: HashMap::new() };
+    let game = game.change().apply(&GameLog::CreateFaction(faction)).unwrap().game;
+    let game = t_perform(&game, GameCommand::SetCreatureFaction(cid_rogue(), Some(faction_id)));
+    let game = t_perform(&game, GameCommand::SetCreatureFaction(cid_ranger(), Some(faction_id)));
+    let game = t_perform(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0)));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_rogue()).unwrap(), Point3::new(500, 0, 0));
+    assert!(game.get_combat().unwrap().combat.pending_interrupts.is_empty());
+  }
+
+  /// Turns can't end until pending interrupts are resolved, and reacting uses up the reacting
+  /// creature's reaction for the round.
+  #[test]
+  fn react_to_opportunity_attack() {
+    let mut game = t_combat();
+    game.classes.mutate(&classid_ranger(), |c| c.opportunity_attack = Some(abid_punch()));
+    let hp = |game: &Game| game.get_creature(cid_rogue()).unwrap().creature.cur_health;
+    let game = t_perform(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0)));
+    match perf(&game, GameCommand::Done) {
+      Err(GameError::InterruptsPending) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+
+    let reacted = t_perform(&game, GameCommand::ReactToInterrupt);
+    assert_eq!(hp(&reacted), hp(&game) - HP(3));
+    let combat = reacted.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.pending_interrupts, vec![]);
+    assert_eq!(combat.reactions_left(cid_ranger()), 0);
+
+    // The ranger has no reactions left, so the rogue can get away this time.
+    let game = t_perform(&reacted, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0)));
+    assert_eq!(
+      game.get_scene(t_scene_id()).unwrap().get_pos(cid_rogue()).unwrap(),
+      Point3::new(500, 0, 0)
+    );
+    t_perform(&game, GameCommand::Done);
+  }
+
+  /// A queued opportunity attack can still be reacted to after the mover has tried to move on,
+  /// since the attempt is rejected without changing anything.
+  #[test]
+  fn react_to_opportunity_attack_after_trying_to_move_on() {
+    let mut game = t_combat();
+    game.classes.mutate(&classid_ranger(), |c| c.opportunity_attack = Some(abid_punch()));
+    let hp = |game: &Game| game.get_creature(cid_rogue()).unwrap().creature.cur_health;
+    let game = t_perform(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0)));
+    match perf(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0))) {
+      Err(GameError::InterruptsPending) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+
+    let reacted = t_perform(&game, GameCommand::ReactToInterrupt);
+    assert_eq!(hp(&reacted), hp(&game) - HP(3));
+    let scene = reacted.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_rogue()).unwrap(), Point3::new(100, 0, 0));
+    let combat = reacted.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.pending_interrupts, vec![]);
+    assert_eq!(combat.reactions_left(cid_ranger()), 0);
+  }
+
+  #[test]
+  fn readied_action_triggers_when_creature_moves() {
+    let game = t_combat();
+    let ready = GameCommand::ReadyAction {
+      ability_id: abid_punch(),
+      target: DecidedTarget::Creature(cid_ranger()),
+      trigger: ReadyTrigger::CreatureMoves(cid_ranger()),
+    };
+    let game = t_perform(&game, ready);
+    let game = t_perform(&game, GameCommand::Done);
+    let game = t_perform(&game, GameCommand::PathCurrentCombatCreature(Point3::new(100, 0, 0)));
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert!(combat.readied_actions.is_empty());
+    assert_eq!(
+      combat.pending_interrupts,
+      vec![
+        Interrupt {
+          creature_id: cid_rogue(),
+          ability_id: abid_punch(),
+          target: DecidedTarget::Creature(cid_ranger()),
+        },
+      ]
+    );
+
+    let game = t_perform(&game, GameCommand::DeclineInterrupt);
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.pending_interrupts, vec![]);
+    assert_eq!(combat.reactions_left(cid_rogue()), 1);
+    t_perform(&game, GameCommand::Done);
+  }
+
+  #[test]
+  fn abilities_cool_down_between_uses() {
+    let mut game = t_combat();
+    game.abilities.mutate(&abid_punch(), |ab| ab.cooldown = 2);
+    let punch = DecidedTarget::Creature(cid_ranger());
+    let mut game = t_game_act(&game, abid_punch(), punch.clone());
+    assert_eq!(game.get_creature(cid_rogue()).unwrap().ability_cooldown(abid_punch()), 2);
+    for _ in 0..3 {
+      game = t_perform(&game, GameCommand::Done);
+    }
+    match t_act(&game, abid_punch(), punch.clone()) {
+      Err(GameError::AbilityOnCooldown(cid, abid, 1)) => {
+        assert_eq!((cid, abid), (cid_rogue(), abid_punch()))
+      }
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    for _ in 0..3 {
+      game = t_perform(&game, GameCommand::Done);
+    }
+    t_game_act(&game, abid_punch(), punch);
+  }
+
+  /// Cooling down doesn't grant an ability: one granted by a condition goes away with the
+  /// condition, and its cooldown is dropped once it runs out.
+  #[test]
+  fn cooldowns_dont_grant_abilities() {
+    let mut game = t_combat();
+    game.abilities.mutate(&abid_heal(), |ab| ab.cooldown = 1);
+    let condition_id = ConditionID::gen();
+    let condition = Condition::ActivateAbility(abid_heal());
+    let apply = CreatureLog::ApplyCondition(condition_id, Duration::Interminate, condition);
+    let game = game.change().apply(&GameLog::CreatureLog(cid_rogue(), apply)).unwrap().game;
+    let game = t_game_act(&game, abid_heal(), DecidedTarget::Creature(cid_rogue()));
+    assert_eq!(game.get_creature(cid_rogue()).unwrap().ability_cooldown(abid_heal()), 1);
+
+    let remove = CreatureLog::RemoveCondition(condition_id);
+    let mut game = game.change().apply(&GameLog::CreatureLog(cid_rogue(), remove)).unwrap().game;
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    assert!(!rogue.has_ability(abid_heal()));
+    assert_eq!(rogue.ability_cooldown(abid_heal()), 0);
+
+    for _ in 0..3 {
+      game = t_perform(&game, GameCommand::Done);
+    }
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    assert!(!rogue.has_ability(abid_heal()));
+    assert!(rogue.creature.cooldowns.is_empty());
+  }
+
+  /// A volume condition hits each creature once per turn, whether it starts or ends its turn
+  /// inside, and counts down once per round.
+  #[test]
+  fn volume_conditions_hit_once_per_turn_and_expire() {
+    let game = t_combat();
+    let thorns = Condition::RecurringEffect(Box::new(CreatureEffect::Damage(Dice::flat(1))));
+    let mut game = t_perform(
+      &game,
+      GameCommand::AddSceneVolumeCondition {
+        scene_id: t_scene_id(),
+        point: Point3::new(0, 0, 0),
+        volume: Volume::Sphere(u32cm(200)),
+        condition: thorns,
+        duration: Duration::Rounds(1),
+      },
+    );
+    let health = |game: &Game| -> Vec<HP> {
+      let cids = [cid_rogue(), cid_ranger(), cid_cleric()];
+      cids.iter().map(|cid| game.get_creature(*cid).unwrap().creature.cur_health).collect()
+    };
+    // The condition runs out at the start of the second round, so it doesn't hit anyone after that.
+    let mut expected_health = vec![
+      vec![HP(9), HP(9), HP(10)],
+      vec![HP(9), HP(9), HP(9)],
+      vec![HP(9), HP(9), HP(9)],
+      vec![HP(9), HP(9), HP(9)],
+      vec![HP(9), HP(9), HP(9)],
+      vec![HP(9), HP(9), HP(9)],
+    ].into_iter();
+    for _ in 0..5 {
+      game = t_perform(&game, GameCommand::Done);
+      assert_eq!(health(&game), expected_health.next().unwrap());
+    }
+    assert_eq!(game.get_scene(t_scene_id()).unwrap().volume_conditions.len(), 1);
+    game = t_perform(&game, GameCommand::Done);
+    assert_eq!(health(&game), expected_health.next().unwrap());
+    assert!(game.get_scene(t_scene_id()).unwrap().volume_conditions.is_empty());
+  }
+
+  /// A hazard hits each creature standing in it at the start of the creature's turns.
+  #[test]
+  fn hazards_hit_at_the_start_of_turns() {
+    let game = t_combat();
+    let fire = TerrainCell::Hazard(CreatureEffect::Damage(Dice::flat(2)));
+    let terrain_cells = hashmap!{Point3::new(0, 0, 0) => fire};
+    let cmd = GameCommand::EditSceneTerrainCells { scene_id: t_scene_id(), terrain_cells };
+    let mut game = t_perform(&game, cmd);
+    let health = |game: &Game| -> Vec<HP> {
+      let cids = [cid_rogue(), cid_ranger(), cid_cleric()];
+      cids.iter().map(|cid| game.get_creature(*cid).unwrap().creature.cur_health).collect()
+    };
+    let expected_health =
+      vec![vec![HP(10), HP(8), HP(10)], vec![HP(10), HP(8), HP(8)], vec![HP(8), HP(8), HP(8)]];
+    for expected in expected_health {
+      game = t_perform(&game, GameCommand::Done);
+      assert_eq!(health(&game), expected);
+    }
+  }
+
+  /// Forcing the turn along skips start-of-turn events, but the creatures whose turn it becomes
+  /// still get their reactions back.
+  #[test]
+  fn forced_turns_restore_reactions() {
+    let mut game = t_combat();
+    game.classes.mutate(&classid_ranger(), |c| c.opportunity_attack = Some(abid_punch()));
+    let game = t_perform(&game, GameCommand::PathCurrentCombatCreature(Point3::new(500, 0, 0)));
+    let game = t_perform(&game, GameCommand::ReactToInterrupt);
+    let reactions_left = |game: &Game| {
+      game.get_combat().unwrap().combat.reactions_left(cid_ranger())
+    };
+    assert_eq!(reactions_left(&game), 0);
+    let game = t_perform(&game, GameCommand::ForceNextTurn);
+    assert_eq!(game.get_combat().unwrap().combat.current_creature_id(), cid_ranger());
+    assert_eq!(reactions_left(&game), 1);
+  }
+
+  #[test]
+  fn combat_counts_rounds() {
+    let mut game = t_combat();
+    assert_eq!(game.get_combat().unwrap().combat.round, 1);
+    for _ in 0..3 {
+      game = t_perform(&game, GameCommand::Done);
+    }
+    assert_eq!(game.get_combat().unwrap().combat.round, 2);
+    game = t_perform(&game, GameCommand::ForcePrevTurn);
+    assert_eq!(game.get_combat().unwrap().combat.round, 1);
+    game = t_perform(&game, GameCommand::ForceNextTurn);
+    assert_eq!(game.get_combat().unwrap().combat.round, 2);
+  }
+
+  /// Conditions count down once per round, no matter whose turn it is, and start-of-round effects
+  /// go off for everyone.
+  #[test]
+  fn conditions_tick_at_start_of_round() {
+    let game = t_combat();
+    let effect = Box::new(CreatureEffect::Damage(Dice::flat(1)));
+    let condition = Condition::RecurringEffectAt(TickTiming::StartOfRound, effect);
+    let apply = CreatureLog::ApplyCondition(ConditionID::gen(), Duration::Rounds(1), condition);
+    let game = game.change().apply(&GameLog::CreatureLog(cid_cleric(), apply)).unwrap().game;
+    let mut game = t_perform(&game, GameCommand::Done);
+    let cleric_health = |game: &Game| game.get_creature(cid_cleric()).unwrap().creature.cur_health;
+    assert_eq!(cleric_health(&game), HP(10));
+    for _ in 0..2 {
+      game = t_perform(&game, GameCommand::Done);
+    }
+    assert_eq!(cleric_health(&game), HP(9));
+    for _ in 0..3 {
+      game = t_perform(&game, GameCommand::Done);
+    }
+    assert_eq!(cleric_health(&game), HP(9));
+    assert!(game.get_creature(cid_cleric()).unwrap().creature.conditions.is_empty());
+  }
+
+  #[test]
+  fn actions_per_turn_are_unlimited_by_default() {
+    let mut game = t_combat();
+    let punch = DecidedTarget::Creature(cid_ranger());
+    for _ in 0..3 {
+      game = t_game_act(&game, abid_punch(), punch.clone());
+    }
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(1));
+    assert_eq!(game.get_combat().unwrap().combat.actions_left(), None);
+  }
+
+  #[test]
+  fn actions_per_turn_are_limited() {
+    let game = t_combat();
+    let budget = ActionBudget { standard: 1, minor: 1 };
+    let game = t_perform(&game, GameCommand::SetActionBudget(Some(budget)));
+    let punch = DecidedTarget::Creature(cid_ranger());
+    let game = t_game_act(&game, abid_punch(), punch.clone());
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.actions_left(), Some(ActionBudget { standard: 0, minor: 1 }));
+    match t_act(&game, abid_punch(), punch.clone()) {
+      Err(GameError::NoActionLeft(cid, ActionSlot::Standard)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    let budget = ActionBudget { standard: 2, minor: 0 };
+    let game = t_perform(&game, GameCommand::SetActionBudget(Some(budget)));
+    let game = t_game_act(&game, abid_punch(), punch);
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.actions_left(), Some(ActionBudget { standard: 0, minor: 0 }));
+    let game = t_perform(&game, GameCommand::Done);
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.actions_left(), Some(ActionBudget { standard: 2, minor: 0 }));
+  }
+
+  #[test]
+  fn free_actions_are_unlimited() {
+    let budget = ActionBudget { standard: 1, minor: 1 };
+    let mut game = t_perform(&t_combat(), GameCommand::SetActionBudget(Some(budget)));
+    game.abilities.mutate(&abid_punch(), |ab| ab.slot = ActionSlot::Free);
+    let punch = DecidedTarget::Creature(cid_ranger());
+    for _ in 0..3 {
+      game = t_game_act(&game, abid_punch(), punch.clone());
+    }
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(1));
+  }
+
+  fn t_order(game: &Game) -> Vec<CreatureID> {
+    game.get_combat().unwrap().combat.creatures.iter().map(|&(c, _)| c).collect()
+  }
+
+  /// A creature that delays drops out of the order, and comes back in right after the creature
+  /// whose turn it is when it resumes.
+  #[test]
+  fn delay_and_resume_turn() {
+    let game = t_combat();
+    let game = t_perform(&game, GameCommand::DelayTurn);
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.current_creature_id(), cid_ranger());
+    assert_eq!(combat.delayed, vec![cid_rogue()]);
+    assert!(combat.contains_creature(cid_rogue()));
+    assert_eq!(t_order(&game), vec![cid_ranger(), cid_cleric()]);
+
+    let game = t_perform(&game, GameCommand::ResumeTurn(cid_rogue()));
+    assert_eq!(t_order(&game), vec![cid_ranger(), cid_rogue(), cid_cleric()]);
+    assert_eq!(game.get_combat().unwrap().combat.current_creature_id(), cid_ranger());
+    let game = t_perform(&game, GameCommand::Done);
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.current_creature_id(), cid_rogue());
+    assert!(combat.delayed.is_empty());
+    match perf(&game, GameCommand::ResumeTurn(cid_rogue())) {
+      Err(GameError::CreatureNotDelayed(cid)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn cannot_delay_after_acting() {
+    let game = t_combat();
+    let game = t_game_act(&game, abid_punch(), DecidedTarget::Creature(cid_ranger()));
+    match perf(&game, GameCommand::DelayTurn) {
+      Err(GameError::CannotDelayAfterActing(cid)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  /// Changing initiative re-sorts the order, but it's still the same creature's turn.
+  #[test]
+  fn change_initiative_keeps_current_creature() {
+    let game = t_combat();
+    let game = t_perform(&game, GameCommand::Done);
+    let game = t_perform(&game, GameCommand::ChangeCreatureInitiative(cid_cleric(), 100));
+    assert_eq!(t_order(&game), vec![cid_cleric(), cid_rogue(), cid_ranger()]);
+    assert_eq!(game.get_combat().unwrap().combat.current_creature_id(), cid_ranger());
+  }
+
+  /// Grouped creatures all go on the roll of the first one in the group.
+  #[test]
+  fn grouped_creatures_share_initiative() {
+    let game = t_game();
+    let mode = InitiativeMode::Groups(vec![vec![cid_cleric(), cid_rogue()]]);
+    let cids = vec![cid_cleric(), cid_ranger(), cid_rogue()];
+    let game = t_perform(&game, GameCommand::StartCombat(t_scene_id(), cids, mode));
+    let combat = game.get_combat().unwrap().combat.clone();
+    let inits: Vec<(CreatureID, i16)> = combat.creatures.iter().cloned().collect();
+    assert_eq!(inits, vec![(cid_ranger(), 10), (cid_cleric(), 0), (cid_rogue(), 0)]);
+  }
+
+  #[test]
+  fn turns_alternate_between_sides() {
+    let game = t_game();
+    let mode = InitiativeMode::Sides(vec![vec![cid_rogue(), cid_ranger()], vec![cid_cleric()]]);
+    let cids = vec![cid_rogue(), cid_ranger(), cid_cleric()];
+    let game = t_perform(&game, GameCommand::StartCombat(t_scene_id(), cids, mode));
+    assert_eq!(t_order(&game), vec![cid_rogue(), cid_cleric(), cid_ranger()]);
+    assert_eq!(game.get_combat().unwrap().combat.current_creature_id(), cid_rogue());
+    let game = t_perform(&game, GameCommand::RerollCombatInitiative);
+    assert_eq!(t_order(&game), vec![cid_rogue(), cid_cleric(), cid_ranger()]);
+  }
+
+  /// The members of a group take a single turn, but each of them gets its own actions.
+  #[test]
+  fn grouped_creatures_share_a_turn() {
+    let mut game = t_game();
+    game.classes.mutate(&classid_cleric(), |c| c.abilities.push(abid_punch()));
+    let mode = InitiativeMode::Groups(vec![vec![cid_cleric(), cid_rogue()]]);
+    let cids = vec![cid_cleric(), cid_ranger(), cid_rogue()];
+    let game = t_perform(&game, GameCommand::StartCombat(t_scene_id(), cids, mode));
+    let budget = ActionBudget { standard: 1, minor: 1 };
+    let game = t_perform(&game, GameCommand::SetActionBudget(Some(budget)));
+    let game = t_perform(&game, GameCommand::Done);
+    let turn_members = |game: &Game| game.get_combat().unwrap().combat.turn_members(game).unwrap();
+    assert_eq!(turn_members(&game), vec![cid_cleric(), cid_rogue()]);
+    match perf(&game, GameCommand::DelayTurn) {
+      Err(GameError::CannotDelayGroupTurn(cid)) => assert_eq!(cid, cid_cleric()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+
+    let punch = DecidedTarget::Creature(cid_ranger());
+    let game = t_game_act(&game, abid_punch(), punch.clone());
+    let game = t_perform(&game, GameCommand::SwitchTurnMember(cid_rogue()));
+    assert_eq!(game.get_combat().unwrap().combat.current_creature_id(), cid_rogue());
+    let game = t_game_act(&game, abid_punch(), punch.clone());
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(4));
+    let game = t_perform(&game, GameCommand::SwitchTurnMember(cid_cleric()));
+    match t_act(&game, abid_punch(), punch.clone()) {
+      Err(GameError::NoActionLeft(cid, ActionSlot::Standard)) => assert_eq!(cid, cid_cleric()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    match perf(&game, GameCommand::SwitchTurnMember(cid_ranger())) {
+      Err(GameError::CreatureNotInTurn(cid)) => assert_eq!(cid, cid_ranger()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+
+    let game = t_perform(&game, GameCommand::Done);
+    let combat = game.get_combat().unwrap().combat.clone();
+    assert_eq!(combat.current_creature_id(), cid_ranger());
+    assert_eq!(combat.round, 2);
+    assert_eq!(turn_members(&game), vec![cid_ranger()]);
+  }
+
+  /// A creature joining the fight goes on its group's initiative, right after the rest of it.
+  #[test]
+  fn added_creatures_join_their_group() {
+    let game = t_game();
+    let mode = InitiativeMode::Groups(vec![vec![cid_cleric(), cid_rogue()]]);
+    let cids = vec![cid_cleric(), cid_ranger()];
+    let game = t_perform(&game, GameCommand::StartCombat(t_scene_id(), cids, mode));
+    let game = t_perform(&game, GameCommand::AddCreatureToCombat(cid_rogue()));
+    let combat = game.get_combat().unwrap().combat.clone();
+    let inits: Vec<(CreatureID, i16)> = combat.creatures.iter().cloned().collect();
+    assert_eq!(inits, vec![(cid_ranger(), 10), (cid_cleric(), 0), (cid_rogue(), 0)]);
+  }
+
+  #[test]
+  fn added_creatures_alternate_between_sides() {
+    let game = t_game();
+    let mode = InitiativeMode::Sides(vec![vec![cid_rogue(), cid_ranger()], vec![cid_cleric()]]);
+    let cids = vec![cid_rogue(), cid_ranger()];
+    let game = t_perform(&game, GameCommand::StartCombat(t_scene_id(), cids, mode));
+    let game = t_perform(&game, GameCommand::Done);
+    let game = t_perform(&game, GameCommand::AddCreatureToCombat(cid_cleric()));
+    assert_eq!(t_order(&game), vec![cid_rogue(), cid_cleric(), cid_ranger()]);
+    assert_eq!(game.get_combat().unwrap().combat.current_creature_id(), cid_ranger());
+  }
 }
@@ -4,6 +4,7 @@ use std::cmp;
 use num::Saturating;
 
 use indexed::*;
+use crate::dice::DiceRoller;
 use crate::types::*;
 
 /// `STANDARD_CREATURE_SPEED` is carefully chosen to allow for circular-looking movement options.
@@ -52,12 +53,21 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     // an update?
     // We could at least reduce the number of times we calculate it by passing conditions to
     // can_act, can_move, and speed.
-    let mut conditions: Vec<AppliedCondition> =
-      self.creature.conditions.values().cloned().collect();
+    let mut conditions = self.intrinsic_conditions();
+    conditions.extend(self.volume_conditions().into_iter().map(|(_, v)| v));
+    conditions
+  }
+
+  /// Get the conditions a creature carries around with it: its own and its class's, but not the
+  /// volume conditions it happens to be standing in.
+  fn intrinsic_conditions(&self) -> Vec<AppliedCondition> {
+    // These are in a consistent order, since that's the order their effects are rolled in.
+    let mut own: Vec<(&ConditionID, &AppliedCondition)> = self.creature.conditions.iter().collect();
+    own.sort_by_key(|&(id, _)| *id);
+    let mut conditions: Vec<AppliedCondition> = own.into_iter().map(|(_, c)| c.clone()).collect();
     let applied_class_conditions =
       self.class.conditions.iter().map(|c| c.apply(Duration::Interminate));
     conditions.extend(applied_class_conditions);
-    conditions.extend(self.volume_conditions().into_iter().map(|(_, v)| v));
     conditions
   }
 
@@ -82,20 +92,25 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     conditions
   }
 
-  pub fn tick(&self) -> Result<ChangedCreature, GameError> {
+  /// Set off the recurring effects of a creature's conditions that go off at the given point in
+  /// the turn order. Everything else that happens with the passage of time happens at the start of
+  /// each round: conditions and cooldowns count down, and the creature regenerates energy.
+  pub fn tick(
+    &self, timing: TickTiming, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedCreature, GameError> {
     let mut changes = self.creature.change();
-    for condition in self.all_conditions() {
-      if let AppliedCondition { condition: Condition::RecurringEffect(ref eff), ref remaining } =
-        condition
-      {
-        if match *remaining {
-          Duration::Rounds(0) => false,
-          Duration::Interminate | Duration::Rounds(_) => true,
-        } {
-          changes = changes.merge(changes.creature(self.game)?.apply_effect(eff)?);
+    // Volume conditions are set off by the game at the start and end of turns, since they depend on
+    // where the creature is standing.
+    for condition in self.intrinsic_conditions() {
+      if let Some((cond_timing, eff)) = condition.condition.recurring_effect() {
+        if cond_timing == timing && condition.remaining != Duration::Rounds(0) {
+          changes = changes.merge(changes.creature(self.game)?.apply_effect(eff, rng)?);
         }
       }
     }
+    if timing != TickTiming::StartOfRound {
+      return Ok(changes);
+    }
 
     // We clone and collect the condition IDs so that the iterator doesn't keep a borrow on
     // `changes`, which we need to mutate.
@@ -109,6 +124,36 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
         },
       }
     }
+
+    if self.class.energy_regen > Energy(0) {
+      let regen = changes.creature(self.game)?.generate_energy(self.class.energy_regen);
+      for log in &regen {
+        changes = changes.apply(log)?;
+      }
+    }
+
+    for abid in changes.creature.cooldowns.keys().cloned().collect::<Vec<AbilityID>>() {
+      changes = changes.apply(&CreatureLog::DecrementCooldown(abid))?;
+    }
+    Ok(changes)
+  }
+
+  /// Restore a creature's resources, depending on how long it rests.
+  pub fn rest(&self, kind: RestKind) -> Result<ChangedCreature, GameError> {
+    let mut logs = self.generate_energy(self.creature.max_energy);
+    if kind == RestKind::Long {
+      let missing = self.creature.max_health - self.creature.cur_health;
+      if missing > HP(0) {
+        logs.push(CreatureLog::Heal(missing, vec![]));
+      }
+    }
+    for abid in self.creature.cooldowns.keys() {
+      logs.push(CreatureLog::StartCooldown(*abid, 0));
+    }
+    let mut changes = self.creature.change();
+    for log in &logs {
+      changes = changes.apply(log)?;
+    }
     Ok(changes)
   }
 
@@ -121,9 +166,9 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     }
   }
 
-  fn damage(&self, expr: &Dice) -> Vec<CreatureLog> {
-    let (rolls, amt) = expr.roll();
-    let amt = HP(amt as u8);
+  fn damage(&self, expr: &Dice, rng: &mut dyn DiceRoller) -> Vec<CreatureLog> {
+    let (rolls, amt) = expr.roll(rng);
+    let amt = roll_to_hp(amt);
     if amt >= self.creature.cur_health {
       vec![
         CreatureLog::Damage(self.creature.cur_health, rolls),
@@ -134,29 +179,36 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     }
   }
 
-  fn heal(&self, expr: &Dice) -> Vec<CreatureLog> {
-    let (dice, amt) = expr.roll();
-    let amt = HP(amt as u8);
+  fn heal(&self, expr: &Dice, rng: &mut dyn DiceRoller) -> Vec<CreatureLog> {
+    let (dice, amt) = expr.roll(rng);
+    let amt = roll_to_hp(amt);
     let missing = self.creature.max_health - self.creature.cur_health;
     vec![CreatureLog::Heal(cmp::min(missing, amt), dice)]
   }
 
-  fn eff2log(&self, effect: &CreatureEffect) -> Vec<CreatureLog> {
+  fn eff2log(&self, effect: &CreatureEffect, rng: &mut dyn DiceRoller) -> Vec<CreatureLog> {
     match *effect {
-      CreatureEffect::Damage(ref expr) => self.damage(expr),
-      CreatureEffect::Heal(ref expr) => self.heal(expr),
+      CreatureEffect::Damage(ref expr) => self.damage(expr, rng),
+      CreatureEffect::Heal(ref expr) => self.heal(expr, rng),
       CreatureEffect::GenerateEnergy(amt) => self.generate_energy(amt),
       CreatureEffect::MultiEffect(ref effects) => {
-        effects.iter().flat_map(|x| self.eff2log(x)).collect()
+        effects.iter().flat_map(|x| self.eff2log(x, rng)).collect()
       }
       CreatureEffect::ApplyCondition(ref duration, ref condition) => {
         vec![Self::apply_condition_log(*duration, condition.clone())]
       }
+      // Forced movement changes the scene, not the creature. See `ChangedGame::apply_action`.
+      CreatureEffect::Push(..)
+      | CreatureEffect::Pull(..)
+      | CreatureEffect::Knockback { .. }
+      | CreatureEffect::Teleport(..) => vec![],
     }
   }
 
-  pub fn apply_effect(&self, effect: &CreatureEffect) -> Result<ChangedCreature, GameError> {
-    let ops = Self::eff2log(self, effect);
+  pub fn apply_effect(
+    &self, effect: &CreatureEffect, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedCreature, GameError> {
+    let ops = Self::eff2log(self, effect, rng);
     let mut changes = self.creature.change();
     for op in &ops {
       changes = changes.apply(op)?;
@@ -181,12 +233,22 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     for ab in &self.creature.abilities {
       abs.insert(*ab);
     }
+    // Cooldowns only apply to abilities the creature has, so that an ability granted by a
+    // condition goes away with the condition even if it was cooling down.
+    for (abid, cooldown) in &self.creature.cooldowns {
+      abs.mutate(abid, |ab| ab.cooldown = *cooldown);
+    }
     abs
   }
 
   pub fn has_ability(&self, ability: AbilityID) -> bool {
     self.ability_statuses().iter().any(|ac| ac.ability_id == ability)
   }
+
+  /// How many more rounds the creature has to wait before it can use an ability again.
+  pub fn ability_cooldown(&self, ability: AbilityID) -> u8 {
+    self.ability_statuses().get(&ability).map_or(0, |ab| ab.cooldown)
+  }
 }
 
 impl Creature {
@@ -210,6 +272,8 @@ impl Creature {
       initiative: spec.initiative.clone(),
       size: spec.size,
       inventory: HashMap::new(),
+      faction: None,
+      cooldowns: HashMap::new(),
     }
   }
 
@@ -245,6 +309,23 @@ impl Creature {
       CreatureLog::RemoveCondition(ref id) => {
         new.conditions.remove(id).ok_or_else(|| GameError::ConditionNotFound(*id))?;
       }
+      CreatureLog::StartCooldown(abid, 0) => {
+        new.cooldowns.remove(&abid);
+      }
+      CreatureLog::StartCooldown(abid, cooldown) => {
+        new.cooldowns.insert(abid, cooldown);
+      }
+      CreatureLog::DecrementCooldown(abid) => match new.cooldowns.get(&abid).cloned() {
+        Some(1) => {
+          new.cooldowns.remove(&abid);
+        }
+        Some(cooldown) if cooldown > 1 => {
+          new.cooldowns.insert(abid, cooldown - 1);
+        }
+        _ => bail!(GameError::BuggyProgram(
+          "Tried to decrease the cooldown of an ability that isn't cooling down".to_string()
+        )),
+      },
     }
     Ok(new)
   }
@@ -274,13 +355,15 @@ impl Creature {
       .ok_or_else(|| GameError::AttributeNotFound(self.id, attr.clone()).into())
   }
 
-  pub fn attribute_check(&self, check: &AttributeCheck) -> Result<(u8, bool), GameError> {
+  pub fn attribute_check(
+    &self, check: &AttributeCheck, rng: &mut dyn DiceRoller
+  ) -> Result<(u8, bool), GameError> {
     let my_skill = self.get_attribute_score(&check.attr)?;
     if check.reliable && check.target <= my_skill {
       Ok((100, true))
     } else {
       let dice = Dice::expr(1, 100);
-      let roll = dice.roll().1 as u8; // panic: 1d100 better fit into a u8!
+      let roll = dice.roll(rng).1 as u8; // panic: 1d100 better fit into a u8!
       let success = roll >= my_skill.difficulty(check.target);
       Ok((roll, success))
     }
@@ -317,6 +400,10 @@ impl ChangedCreature {
   pub fn done(self) -> (Creature, Vec<CreatureLog>) { (self.creature, self.logs) }
 }
 
+/// Convert the result of a roll to an amount of HP. Negative results count as zero, and results
+/// too large to represent are capped.
+fn roll_to_hp(amt: i32) -> HP { HP(cmp::min(cmp::max(amt, 0), i32::from(u8::max_value())) as u8) }
+
 fn conditions_able(conditions: &[AppliedCondition]) -> bool {
   !conditions.iter().any(|&AppliedCondition { ref condition, .. }| {
     condition == &Condition::Incapacitated || condition == &Condition::Dead
@@ -326,11 +413,17 @@ fn conditions_able(conditions: &[AppliedCondition]) -> bool {
 #[cfg(test)]
 pub mod test {
   use crate::creature::*;
+  use crate::dice::test::*;
   use crate::types::test::*;
   use crate::game::test::*;
 
   use std::iter::FromIterator;
 
+  fn t_start_round(game: &Game, creature: &Creature) -> Creature {
+    let creature = game.dyn_creature(creature).unwrap();
+    creature.tick(TickTiming::StartOfRound, &mut t_roller()).unwrap().creature
+  }
+
   #[test]
   fn test_tick_and_expire_condition_remaining() {
     let mut game = t_game();
@@ -342,7 +435,7 @@ pub mod test {
       ]);
     });
     assert_eq!(
-      game.get_creature(cid_rogue()).unwrap().tick().unwrap().creature.conditions,
+      t_start_round(&game, game.get_creature(cid_rogue()).unwrap().creature).conditions,
       HashMap::from_iter(vec![
         (ConditionID(uuid_1()), app_cond(Condition::Incapacitated, Duration::Rounds(4))),
         (ConditionID(uuid_2()), app_cond(Condition::Incapacitated, Duration::Interminate)),
@@ -350,8 +443,8 @@ pub mod test {
     );
   }
 
-  /// A RecurringEffect with duration of "2" will tick exactly twice at the beginning of the
-  /// creature's next two turns.
+  /// A RecurringEffect with duration of "2" will tick exactly twice: at the end of the creature's
+  /// turn in the round it's applied, and at the end of its turn in the next round.
   #[test]
   fn test_recurring_effect_ticks_duration_times() {
     let mut game = t_game();
@@ -366,16 +459,45 @@ pub mod test {
         ),
       ]);
     });
-    let c = game.get_creature(cid_rogue()).unwrap().tick().unwrap().creature;
+    let round = |c: &Creature| {
+      let c = game.dyn_creature(c).unwrap().tick(TickTiming::EndOfTurn, &mut t_roller()).unwrap();
+      t_start_round(&game, &c.creature)
+    };
+    let c = round(game.get_creature(cid_rogue()).unwrap().creature);
     assert_eq!(c.cur_health, HP(9));
-    let c = game.dyn_creature(&c).unwrap().tick().unwrap().creature;
+    let c = round(&c);
     assert_eq!(c.cur_health, HP(8));
-    let c = game.dyn_creature(&c).unwrap().tick().unwrap().creature;
+    let c = round(&c);
     assert_eq!(c.cur_health, HP(8));
   }
 
-  /// If a condition has a duration of N, it will remain on the creature until the N+1'th tick
-  /// on that creature.
+  /// Recurring effects only go off at the point in the turn order they declare.
+  #[test]
+  fn recurring_effects_go_off_at_their_timing() {
+    let mut game = t_game();
+    let effect = Box::new(CreatureEffect::Damage(Dice::flat(1)));
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.conditions = HashMap::from_iter(vec![
+        (
+          ConditionID(uuid_0()),
+          app_cond(
+            Condition::RecurringEffectAt(TickTiming::StartOfTurn, effect),
+            Duration::Interminate,
+          ),
+        ),
+      ]);
+    });
+    let c = game.get_creature(cid_rogue()).unwrap();
+    let c = c.tick(TickTiming::EndOfTurn, &mut t_roller()).unwrap().creature;
+    assert_eq!(c.cur_health, HP(10));
+    assert_eq!(t_start_round(&game, &c).cur_health, HP(10));
+    let c = game.get_creature(cid_rogue()).unwrap();
+    let c = c.tick(TickTiming::StartOfTurn, &mut t_roller()).unwrap().creature;
+    assert_eq!(c.cur_health, HP(9));
+  }
+
+  /// If a condition has a duration of N, it will remain on the creature until the N+1'th round
+  /// starts.
   #[test]
   fn test_condition_duration() {
     let mut game = t_game();
@@ -384,14 +506,35 @@ pub mod test {
         (ConditionID(uuid_0()), app_cond(Condition::Incapacitated, Duration::Rounds(1))),
       ]);
     });
-    let c = game.get_creature(cid_rogue()).unwrap().tick().unwrap().creature;
+    let c = t_start_round(&game, game.get_creature(cid_rogue()).unwrap().creature);
     assert_eq!(
       c.conditions,
       HashMap::from_iter(vec![
         (ConditionID(uuid_0()), app_cond(Condition::Incapacitated, Duration::Rounds(0))),
       ])
     );
-    let c = game.dyn_creature(&c).unwrap().tick().unwrap().creature;
+    let c = t_start_round(&game, &c);
     assert_eq!(c.conditions, HashMap::new());
   }
+
+  #[test]
+  fn tick_regenerates_class_energy() {
+    let mut game = t_game();
+    game.classes.mutate(&classid_rogue(), |c| c.energy_regen = Energy(3));
+    game.creatures.mutate(&cid_rogue(), |c| c.cur_energy = Energy(5));
+    let c = t_start_round(&game, game.get_creature(cid_rogue()).unwrap().creature);
+    assert_eq!(c.cur_energy, Energy(8));
+    let c = t_start_round(&game, &c);
+    assert_eq!(c.cur_energy, Energy(10));
+  }
+
+  #[test]
+  fn negative_damage_does_nothing() {
+    let game = t_game();
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    let effect = CreatureEffect::Damage(Dice::expr(1, 4).minus(Dice::expr(1, 6)));
+    let changed = rogue.apply_effect(&effect, &mut FixedRoller::new(vec![1, 6])).unwrap();
+    assert_eq!(changed.creature.cur_health, rogue.creature.cur_health);
+    assert_eq!(changed.logs, vec![CreatureLog::Damage(HP(0), vec![1, -6])]);
+  }
 }
@@ -0,0 +1,1089 @@
+//! Rolling dice, and the sources of randomness used to do it.
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::{self, Rng, SeedableRng, XorShiftRng};
+use serde::de;
+use serde::ser::{self, SerializeStruct};
+
+use crate::types::{CreatureEffect, Dice, GameError, Keep};
+
+/// The most times a single exploding die can explode. This keeps `d1!`-style pathological
+/// expressions finite.
+pub const MAX_EXPLOSIONS: u8 = 20;
+
+/// The most steps that calculating a distribution may take. Dice like `255d255!` have
+/// distributions that would take far longer than this to work out, so they don't get one.
+pub const MAX_DISTRIBUTION_WORK: u64 = 5_000_000;
+
+/// A source of randomness. Every random number used by the game simulation comes from a
+/// `DiceRoller`, so that tests can be deterministic and so that a command can be re-run with
+/// exactly the same results.
+pub trait DiceRoller {
+  /// Roll a single die with `size` sides, returning a number from 1 to `size` inclusive.
+  fn roll_die(&mut self, size: u32) -> i32;
+}
+
+/// The default `DiceRoller`: a PRNG which is created from a known seed.
+///
+/// The `App` creates a new `SeededRoller` for every command it processes and records the seed in
+/// the resulting logs (see `GameLog::DiceSeed`), so the rolls can be reproduced later.
+pub struct SeededRoller {
+  seed: u64,
+  rolls: usize,
+  rng: XorShiftRng,
+}
+
+impl SeededRoller {
+  pub fn new(seed: u64) -> SeededRoller {
+    let (low, high) = (seed as u32, (seed >> 32) as u32);
+    // XorShiftRng panics when given an all-zero seed, so the last two words are mixed with
+    // constants to ensure that can never happen.
+    let rng = XorShiftRng::from_seed([low, high, low ^ 0x9E37_79B9, high ^ 0x7F4A_7C15]);
+    SeededRoller { seed, rolls: 0, rng }
+  }
+
+  /// Create a roller with a random seed.
+  pub fn from_entropy() -> SeededRoller { SeededRoller::new(rand::random()) }
+
+  pub fn seed(&self) -> u64 { self.seed }
+
+  /// The number of dice that have been rolled with this roller.
+  pub fn rolls(&self) -> usize { self.rolls }
+}
+
+impl DiceRoller for SeededRoller {
+  fn roll_die(&mut self, size: u32) -> i32 {
+    self.rolls += 1;
+    self.rng.gen_range(1, size as i32 + 1)
+  }
+}
+
+impl Dice {
+  /// Roll the dice, returning a vector containing all of the individual die rolls, and then the
+  /// final result.
+  pub fn roll(&self, rng: &mut dyn DiceRoller) -> (Vec<i16>, i32) {
+    match *self {
+      Dice::Expr { num, size } => {
+        let mut intermediate = vec![];
+        let mut result = 0i32;
+        for _ in 0..num {
+          let val = rng.roll_die(u32::from(size));
+          result += val;
+          intermediate.push(val as i16);
+        }
+        (intermediate, result)
+      }
+      Dice::Flat(val) => (vec![i16::from(val)], i32::from(val)),
+      Dice::Plus(ref l, ref r) => {
+        let (mut intermediate, left_result) = l.roll(rng);
+        let (right_intermediate, right_result) = r.roll(rng);
+        intermediate.extend(right_intermediate);
+        (intermediate, left_result + right_result)
+      }
+      Dice::Minus(ref l, ref r) => {
+        let (mut intermediate, left_result) = l.roll(rng);
+        let (right_intermediate, right_result) = r.roll(rng);
+        intermediate.extend(right_intermediate.into_iter().map(|v| -v));
+        (intermediate, left_result - right_result)
+      }
+      Dice::Roll { num, size, explode, reroll_below, keep } => {
+        let dice: Vec<i32> =
+          (0..num).map(|_| roll_modified_die(size, explode, reroll_below, rng)).collect();
+        let kept = kept_dice(&dice, keep);
+        let intermediate = kept.iter().map(|v| *v as i16).collect();
+        (intermediate, kept.iter().sum())
+      }
+      Dice::BestOf(count, ref dice) => {
+        if count == 0 {
+          panic!("Sorry, can't roll best of 0.")
+        }
+        let (mut best_rolls, mut best_result) = dice.roll(rng);
+        for _ in 1..count {
+          let (rolls, result) = dice.roll(rng);
+          if result > best_result {
+            best_rolls = rolls;
+            best_result = result;
+          }
+        }
+        (best_rolls, best_result)
+      }
+    }
+  }
+
+  /// Calculate the exact probability distribution of the results of rolling these dice, or `None`
+  /// if that would take more than `MAX_DISTRIBUTION_WORK` steps.
+  pub fn distribution(&self) -> Option<Distribution> {
+    self.distribution_within(&mut WorkBudget(MAX_DISTRIBUTION_WORK)).ok()
+  }
+
+  fn distribution_within(&self, budget: &mut WorkBudget) -> Result<Distribution, TooComplicated> {
+    Ok(match *self {
+      Dice::Expr { num, size } => Distribution::uniform(size).repeat(num, budget)?,
+      Dice::Roll { num, size, explode, reroll_below, keep } => {
+        let die = modified_die_distribution(size, explode, reroll_below, budget)?;
+        match keep {
+          None => die.repeat(num, budget)?,
+          Some(keep) => kept_distribution(&die, num, keep, budget)?,
+        }
+      }
+      Dice::Flat(val) => Distribution::constant(i32::from(val)),
+      Dice::Plus(ref l, ref r) => {
+        l.distribution_within(budget)?.add(&r.distribution_within(budget)?, budget)?
+      }
+      Dice::Minus(ref l, ref r) => {
+        l.distribution_within(budget)?.add(&r.distribution_within(budget)?.negate(), budget)?
+      }
+      Dice::BestOf(count, ref dice) => dice.distribution_within(budget)?.best_of(count),
+    })
+  }
+}
+
+/// Calculating a distribution would take more than `MAX_DISTRIBUTION_WORK` steps.
+#[derive(Debug)]
+struct TooComplicated;
+
+/// How many steps are left before calculating a distribution gives up.
+struct WorkBudget(u64);
+
+impl WorkBudget {
+  fn spend(&mut self, steps: usize) -> Result<(), TooComplicated> {
+    self.0 = self.0.checked_sub(steps as u64).ok_or(TooComplicated)?;
+    Ok(())
+  }
+}
+
+/// The probability of each possible result of rolling some `Dice`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+  min: i32,
+  /// The probability of each result from `min` up.
+  probabilities: Vec<f64>,
+}
+
+impl Distribution {
+  fn constant(val: i32) -> Distribution { Distribution { min: val, probabilities: vec![1.0] } }
+
+  fn uniform(size: u8) -> Distribution {
+    Distribution { min: 1, probabilities: vec![1.0 / f64::from(size); size as usize] }
+  }
+
+  /// Build a distribution out of (result, probability) pairs, combining duplicate results.
+  fn from_results<I: IntoIterator<Item = (i32, f64)>>(results: I) -> Distribution {
+    let mut by_result = BTreeMap::new();
+    for (result, probability) in results {
+      if probability > 0.0 {
+        *by_result.entry(result).or_insert(0.0) += probability;
+      }
+    }
+    let (min, max) = match (by_result.keys().next(), by_result.keys().next_back()) {
+      (Some(&min), Some(&max)) => (min, max),
+      _ => return Distribution::constant(0),
+    };
+    let probabilities =
+      (min..max + 1).map(|result| by_result.get(&result).cloned().unwrap_or(0.0)).collect();
+    Distribution { min, probabilities }
+  }
+
+  pub fn min(&self) -> i32 { self.min }
+
+  pub fn max(&self) -> i32 { self.min + self.probabilities.len() as i32 - 1 }
+
+  /// Iterate over every possible result along with its probability, from lowest to highest.
+  pub fn iter<'a>(&'a self) -> impl Iterator<Item = (i32, f64)> + 'a {
+    self.probabilities.iter().enumerate().map(move |(idx, p)| (self.min + idx as i32, *p))
+  }
+
+  /// The probability of rolling exactly `result`.
+  pub fn probability(&self, result: i32) -> f64 {
+    if result < self.min {
+      return 0.0;
+    }
+    self.probabilities.get((result - self.min) as usize).cloned().unwrap_or(0.0)
+  }
+
+  /// The probability of rolling `result` or higher.
+  pub fn at_least(&self, result: i32) -> f64 {
+    let skip = cmp::max(result - self.min, 0) as usize;
+    self.probabilities.iter().skip(skip).sum()
+  }
+
+  pub fn mean(&self) -> f64 { self.iter().map(|(result, p)| f64::from(result) * p).sum() }
+
+  pub fn variance(&self) -> f64 {
+    let mean = self.mean();
+    self.iter().map(|(result, p)| (f64::from(result) - mean).powi(2) * p).sum()
+  }
+
+  /// The distribution of the sum of a result from this distribution and one from `other`.
+  fn add(
+    &self, other: &Distribution, budget: &mut WorkBudget
+  ) -> Result<Distribution, TooComplicated> {
+    let (left, right) = (&self.probabilities, &other.probabilities);
+    budget.spend(left.len() * right.len())?;
+    let mut probabilities = vec![0.0; (left.len() + right.len()).saturating_sub(1)];
+    for (l, lp) in left.iter().enumerate().filter(|&(_, lp)| *lp > 0.0) {
+      for (r, rp) in right.iter().enumerate() {
+        probabilities[l + r] += lp * rp;
+      }
+    }
+    Ok(Distribution { min: self.min + other.min, probabilities })
+  }
+
+  /// The distribution of the sum of `count` independent results from this distribution.
+  fn repeat(&self, count: u8, budget: &mut WorkBudget) -> Result<Distribution, TooComplicated> {
+    let mut total = Distribution::constant(0);
+    for _ in 0..count {
+      total = total.add(self, budget)?;
+    }
+    Ok(total)
+  }
+
+  /// Count any negative results as zero, as happens when a roll is used for damage or healing.
+  fn non_negative(&self) -> Distribution {
+    Distribution::from_results(self.iter().map(|(result, p)| (cmp::max(result, 0), p)))
+  }
+
+  fn negate(&self) -> Distribution {
+    let mut probabilities = self.probabilities.clone();
+    probabilities.reverse();
+    Distribution { min: -self.max(), probabilities }
+  }
+
+  /// The distribution of the best of `count` independent results from this distribution.
+  fn best_of(&self, count: u8) -> Distribution {
+    let count = i32::from(count);
+    let mut below = 0.0f64;
+    let results: Vec<(i32, f64)> = self
+      .iter()
+      .map(|(result, p)| {
+        let at_most = below + p;
+        let probability = at_most.powi(count) - below.powi(count);
+        below = at_most;
+        (result, probability)
+      })
+      .collect();
+    Distribution::from_results(results)
+  }
+}
+
+/// Distributions serialize with their summary statistics, so clients don't need to calculate them.
+impl ser::Serialize for Distribution {
+  fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut str = serializer.serialize_struct("Distribution", 6)?;
+    let mut at_least: Vec<f64> = self
+      .probabilities
+      .iter()
+      .rev()
+      .scan(0.0, |above, p| {
+        *above += p;
+        Some(*above)
+      })
+      .collect();
+    at_least.reverse();
+    str.serialize_field("min", &self.min())?;
+    str.serialize_field("max", &self.max())?;
+    str.serialize_field("mean", &self.mean())?;
+    str.serialize_field("variance", &self.variance())?;
+    str.serialize_field("probabilities", &self.probabilities)?;
+    str.serialize_field("at_least", &at_least)?;
+    str.end()
+  }
+}
+
+impl CreatureEffect {
+  /// The distribution of the damage this effect immediately deals to each creature it affects, if
+  /// it deals any. This doesn't take into account the health of the creatures being damaged.
+  ///
+  /// This is also `None` if the damage is too complicated to calculate the distribution of, as
+  /// with `Dice::distribution`.
+  pub fn damage_distribution(&self) -> Option<Distribution> {
+    self.damage_distribution_within(&mut WorkBudget(MAX_DISTRIBUTION_WORK)).ok().and_then(|d| d)
+  }
+
+  fn damage_distribution_within(
+    &self, budget: &mut WorkBudget
+  ) -> Result<Option<Distribution>, TooComplicated> {
+    match *self {
+      CreatureEffect::Damage(ref dice) => {
+        Ok(Some(dice.distribution_within(budget)?.non_negative()))
+      }
+      CreatureEffect::MultiEffect(ref effects) => {
+        let mut total: Option<Distribution> = None;
+        for effect in effects {
+          if let Some(dist) = effect.damage_distribution_within(budget)? {
+            total = Some(match total {
+              Some(total) => total.add(&dist, budget)?,
+              None => dist,
+            });
+          }
+        }
+        Ok(total)
+      }
+      _ => Ok(None),
+    }
+  }
+}
+
+/// The distribution of a single die of a `Dice::Roll`, mirroring `roll_modified_die`.
+fn modified_die_distribution(
+  size: u8, explode: bool, reroll_below: Option<u8>, budget: &mut WorkBudget
+) -> Result<Distribution, TooComplicated> {
+  let uniform = Distribution::uniform(size);
+  let first = match reroll_below {
+    None => uniform.clone(),
+    Some(threshold) => {
+      let side = 1.0 / f64::from(size);
+      let reroll_chance = f64::from(threshold - 1) * side;
+      Distribution::from_results((1..i32::from(size) + 1).map(|face| {
+        let kept = if face >= i32::from(threshold) { side } else { 0.0 };
+        (face, kept + reroll_chance * side)
+      }))
+    }
+  };
+  if !explode {
+    return Ok(first);
+  }
+  // Work backwards from the last die that's allowed to be rolled, which can't explode.
+  let mut extra = uniform.clone();
+  for _ in 1..MAX_EXPLOSIONS {
+    extra = explode_once(&uniform, &extra, size, budget)?;
+  }
+  explode_once(&first, &extra, size, budget)
+}
+
+/// Given the distribution of a die's face and of whatever gets added when it explodes, calculate
+/// the distribution of its total.
+fn explode_once(
+  face: &Distribution, extra: &Distribution, size: u8, budget: &mut WorkBudget
+) -> Result<Distribution, TooComplicated> {
+  budget.spend(face.probabilities.len() + extra.probabilities.len())?;
+  let size = i32::from(size);
+  let mut results = vec![];
+  for (result, p) in face.iter() {
+    if result == size {
+      results.extend(extra.iter().map(|(extra_result, ep)| (size + extra_result, p * ep)));
+    } else {
+      results.push((result, p));
+    }
+  }
+  Ok(Distribution::from_results(results))
+}
+
+/// The distribution of the total of the highest or lowest few of `num` dice which each have the
+/// distribution `die`.
+///
+/// This considers each face in order from best to worst, tracking how many dice have been
+/// assigned a face so far and the total of the dice that are kept.
+fn kept_distribution(
+  die: &Distribution, num: u8, keep: Keep, budget: &mut WorkBudget
+) -> Result<Distribution, TooComplicated> {
+  let (count, highest) = match keep {
+    Keep::Highest(count) => (count as usize, true),
+    Keep::Lowest(count) => (count as usize, false),
+  };
+  let num = num as usize;
+  let mut faces: Vec<(i32, f64)> = die.iter().filter(|&(_, p)| p > 0.0).collect();
+  if highest {
+    faces.reverse();
+  }
+  let mut states: Vec<BTreeMap<i32, f64>> = vec![BTreeMap::new(); num + 1];
+  states[0].insert(0, 1.0);
+  for (face, p) in faces {
+    let mut next: Vec<BTreeMap<i32, f64>> = vec![BTreeMap::new(); num + 1];
+    for (assigned, totals) in states.iter().enumerate() {
+      let remaining = num - assigned;
+      let still_kept = count.saturating_sub(assigned);
+      budget.spend(totals.len() * (remaining + 1))?;
+      for (&total, &weight) in totals {
+        // The weight of exactly `showing` of the remaining dice showing this face is
+        // weight * C(remaining, showing) * p^showing, which we build up incrementally.
+        let mut weight = weight;
+        for showing in 0..remaining + 1 {
+          if showing > 0 {
+            weight *= p * (remaining - showing + 1) as f64 / showing as f64;
+          }
+          let kept = cmp::min(showing, still_kept) as i32;
+          *next[assigned + showing].entry(total + face * kept).or_insert(0.0) += weight;
+        }
+      }
+    }
+    states = next;
+  }
+  Ok(Distribution::from_results(states.pop().expect("There's always a state for all dice")))
+}
+
+/// Roll a single die of a `Dice::Roll`, applying its reroll and explode modifiers.
+fn roll_modified_die(
+  size: u8, explode: bool, reroll_below: Option<u8>, rng: &mut dyn DiceRoller
+) -> i32 {
+  let size = u32::from(size);
+  let mut face = rng.roll_die(size);
+  if let Some(threshold) = reroll_below {
+    if face < i32::from(threshold) {
+      face = rng.roll_die(size);
+    }
+  }
+  let mut total = face;
+  if explode {
+    let mut explosions = 0;
+    while face == size as i32 && explosions < MAX_EXPLOSIONS {
+      face = rng.roll_die(size);
+      total += face;
+      explosions += 1;
+    }
+  }
+  total
+}
+
+/// Figure out which dice count toward the result, preserving the order they were rolled in.
+fn kept_dice(dice: &[i32], keep: Option<Keep>) -> Vec<i32> {
+  let (count, highest) = match keep {
+    None => return dice.to_vec(),
+    Some(Keep::Highest(count)) => (count, true),
+    Some(Keep::Lowest(count)) => (count, false),
+  };
+  let mut ranked: Vec<usize> = (0..dice.len()).collect();
+  ranked.sort_by_key(|&idx| dice[idx]);
+  if highest {
+    ranked.reverse();
+  }
+  ranked.truncate(count as usize);
+  ranked.sort();
+  ranked.into_iter().map(|idx| dice[idx]).collect()
+}
+
+/// Dice are displayed in the same syntax that `Dice::from_str` parses, and parsing the displayed
+/// form of any `Dice` yields a `Dice` that displays identically.
+impl fmt::Display for Dice {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Dice::Expr { num, size } => write!(f, "{}d{}", num, size),
+      Dice::Roll { num, size, explode, reroll_below, keep } => {
+        write!(f, "{}d{}", num, size)?;
+        if explode {
+          write!(f, "!")?;
+        }
+        if let Some(threshold) = reroll_below {
+          write!(f, "r<{}", threshold)?;
+        }
+        match keep {
+          Some(Keep::Highest(count)) => write!(f, "kh{}", count),
+          Some(Keep::Lowest(count)) => write!(f, "kl{}", count),
+          None => Ok(()),
+        }
+      }
+      Dice::Flat(val) => write!(f, "{}", val),
+      Dice::Plus(ref l, ref r) => match **r {
+        Dice::Flat(val) if val < 0 => write!(f, "{}-{}", l, -i16::from(val)),
+        _ => {
+          write!(f, "{}+", l)?;
+          write_operand(f, r)
+        }
+      },
+      Dice::Minus(ref l, ref r) => match **r {
+        Dice::Flat(val) if val < 0 => write!(f, "{}-({})", l, val),
+        _ => {
+          write!(f, "{}-", l)?;
+          write_operand(f, r)
+        }
+      },
+      Dice::BestOf(count, ref dice) => write!(f, "BestOf({}, {})", count, dice),
+    }
+  }
+}
+
+/// Write the right-hand side of a sum or difference, parenthesizing it if necessary.
+fn write_operand(f: &mut fmt::Formatter, dice: &Dice) -> fmt::Result {
+  match *dice {
+    Dice::Plus(..) | Dice::Minus(..) => write!(f, "({})", dice),
+    _ => write!(f, "{}", dice),
+  }
+}
+
+/// Parse a dice expression. The syntax is:
+///
+/// - `NdS`: roll N dice with S sides. N defaults to 1, so `d20` is the same as `1d20`.
+/// - Any of these modifiers may follow a roll:
+///   - `!`: exploding dice; whenever a die shows its maximum, roll it again and add the result.
+///   - `r<T`: reroll (once) any die that shows less than T.
+///   - `khK` / `klK`: only count the highest / lowest K dice.
+/// - `N`: a flat number, which may be negative.
+/// - `A+B`, `A-B`: sums and differences, which may be parenthesized.
+/// - `BestOf(N, A)`: roll A N times and take the best total.
+///
+/// For example, `4d6kh3`, `2d20kl1`, `d6!`, `3d6-1d4`, and `2d6r<3+5` are all valid.
+impl FromStr for Dice {
+  type Err = GameError;
+  fn from_str(input: &str) -> Result<Dice, GameError> {
+    let mut parser = DiceParser { input, pos: 0 };
+    let dice = parser.sum()?;
+    parser.skip_whitespace();
+    if parser.pos != input.len() {
+      return Err(parser.error("unexpected trailing input"));
+    }
+    Ok(dice)
+  }
+}
+
+struct DiceParser<'a> {
+  input: &'a str,
+  pos: usize,
+}
+
+impl<'a> DiceParser<'a> {
+  fn error(&self, msg: &str) -> GameError {
+    GameError::InvalidDice(self.input.to_string(), format!("{} at position {}", msg, self.pos))
+  }
+
+  fn peek(&self) -> Option<u8> { self.input.as_bytes().get(self.pos).cloned() }
+
+  fn peek_digit(&self) -> bool { self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) }
+
+  fn skip_whitespace(&mut self) {
+    while self.peek().map(|c| c.is_ascii_whitespace()).unwrap_or(false) {
+      self.pos += 1;
+    }
+  }
+
+  /// Consume the given character if it's next (after any whitespace).
+  fn eat(&mut self, c: u8) -> bool {
+    self.skip_whitespace();
+    if self.peek() == Some(c) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, c: u8) -> Result<(), GameError> {
+    if self.eat(c) {
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected '{}'", c as char)))
+    }
+  }
+
+  fn number(&mut self) -> Result<u32, GameError> {
+    let start = self.pos;
+    while self.peek_digit() {
+      self.pos += 1;
+    }
+    if start == self.pos {
+      return Err(self.error("expected a number"));
+    }
+    self.input[start..self.pos].parse().map_err(|_| self.error("number is too large"))
+  }
+
+  fn small_number(&mut self) -> Result<u8, GameError> {
+    let num = self.number()?;
+    if num > u32::from(u8::max_value()) {
+      return Err(self.error("number is too large"));
+    }
+    Ok(num as u8)
+  }
+
+  fn flat(&mut self, negative: bool) -> Result<Dice, GameError> {
+    let num = i64::from(self.number()?);
+    let val = if negative { -num } else { num };
+    if val < i64::from(i8::min_value()) || val > i64::from(i8::max_value()) {
+      return Err(self.error("number is out of range"));
+    }
+    Ok(Dice::Flat(val as i8))
+  }
+
+  fn sum(&mut self) -> Result<Dice, GameError> {
+    let mut left = self.term()?;
+    loop {
+      if self.eat(b'+') {
+        let right = self.term()?;
+        left = Dice::Plus(Box::new(left), Box::new(right));
+      } else if self.eat(b'-') {
+        self.skip_whitespace();
+        // `X-3` is represented as `X+(-3)`, but `X-1d4` needs a real subtraction.
+        let start = self.pos;
+        if self.peek_digit() {
+          self.number()?;
+          let is_flat = self.peek() != Some(b'd');
+          self.pos = start;
+          if is_flat {
+            let right = self.flat(true)?;
+            left = Dice::Plus(Box::new(left), Box::new(right));
+            continue;
+          }
+        }
+        let right = self.term()?;
+        left = Dice::Minus(Box::new(left), Box::new(right));
+      } else {
+        return Ok(left);
+      }
+    }
+  }
+
+  fn term(&mut self) -> Result<Dice, GameError> {
+    self.skip_whitespace();
+    if self.eat(b'(') {
+      let dice = self.sum()?;
+      self.expect(b')')?;
+      return Ok(dice);
+    }
+    if self.input[self.pos..].starts_with("BestOf") {
+      self.pos += "BestOf".len();
+      self.expect(b'(')?;
+      self.skip_whitespace();
+      let count = self.small_number()?;
+      if count == 0 {
+        return Err(self.error("can't take the best of 0"));
+      }
+      self.expect(b',')?;
+      let dice = self.sum()?;
+      self.expect(b')')?;
+      return Ok(Dice::BestOf(count, Box::new(dice)));
+    }
+    if self.peek() == Some(b'-') {
+      self.pos += 1;
+      return self.flat(true);
+    }
+    let start = self.pos;
+    let num = if self.peek_digit() { Some(self.number()?) } else { None };
+    if self.peek() != Some(b'd') {
+      if num.is_none() {
+        return Err(self.error("expected dice or a number"));
+      }
+      self.pos = start;
+      return self.flat(false);
+    }
+    self.pos += 1;
+    let num = match num {
+      Some(num) if num > u32::from(u8::max_value()) => {
+        return Err(self.error("too many dice"));
+      }
+      Some(num) => num as u8,
+      None => 1,
+    };
+    self.roll(num)
+  }
+
+  /// Parse the size and modifiers of a roll, after the `d`.
+  fn roll(&mut self, num: u8) -> Result<Dice, GameError> {
+    let size = self.small_number()?;
+    if size == 0 {
+      return Err(self.error("dice must have at least one side"));
+    }
+    let mut explode = false;
+    let mut reroll_below = None;
+    let mut keep = None;
+    loop {
+      match self.peek() {
+        Some(b'!') if !explode => {
+          if size < 2 {
+            return Err(self.error("only dice with at least two sides can explode"));
+          }
+          self.pos += 1;
+          explode = true;
+        }
+        Some(b'r') if reroll_below.is_none() => {
+          self.pos += 1;
+          self.expect(b'<')?;
+          let threshold = self.small_number()?;
+          if threshold < 2 || threshold > size {
+            return Err(self.error("reroll threshold must be between 2 and the size of the die"));
+          }
+          reroll_below = Some(threshold);
+        }
+        Some(b'k') if keep.is_none() => {
+          self.pos += 1;
+          let highest = match self.peek() {
+            Some(b'h') => true,
+            Some(b'l') => false,
+            _ => return Err(self.error("expected 'kh' or 'kl'")),
+          };
+          self.pos += 1;
+          let count = self.small_number()?;
+          if count > num {
+            return Err(self.error("can't keep more dice than are rolled"));
+          }
+          keep = Some(if highest { Keep::Highest(count) } else { Keep::Lowest(count) });
+        }
+        _ => break,
+      }
+    }
+    if !explode && reroll_below.is_none() && keep.is_none() {
+      Ok(Dice::Expr { num, size })
+    } else {
+      Ok(Dice::Roll { num, size, explode, reroll_below, keep })
+    }
+  }
+}
+
+/// The structured representation of `Dice`, which mirrors its derived `Serialize`.
+#[derive(Deserialize)]
+enum DiceData {
+  Expr { num: u8, size: u8 },
+  Plus(Box<Dice>, Box<Dice>),
+  Flat(i8),
+  BestOf(u8, Box<Dice>),
+  Minus(Box<Dice>, Box<Dice>),
+  Roll {
+    num: u8,
+    size: u8,
+    #[serde(default)]
+    explode: bool,
+    #[serde(default)]
+    reroll_below: Option<u8>,
+    #[serde(default)]
+    keep: Option<Keep>,
+  },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DiceRepr {
+  Syntax(String),
+  Number(i8),
+  Data(DiceData),
+}
+
+impl<'de> de::Deserialize<'de> for Dice {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: de::Deserializer<'de>,
+  {
+    let repr: DiceRepr = de::Deserialize::deserialize(deserializer)?;
+    let dice = match repr {
+      DiceRepr::Syntax(st) => st.parse().map_err(de::Error::custom)?,
+      DiceRepr::Number(val) => Dice::Flat(val),
+      DiceRepr::Data(DiceData::Expr { num, size }) => Dice::Expr { num, size },
+      DiceRepr::Data(DiceData::Plus(l, r)) => Dice::Plus(l, r),
+      DiceRepr::Data(DiceData::Flat(val)) => Dice::Flat(val),
+      DiceRepr::Data(DiceData::BestOf(count, dice)) => Dice::BestOf(count, dice),
+      DiceRepr::Data(DiceData::Minus(l, r)) => Dice::Minus(l, r),
+      DiceRepr::Data(DiceData::Roll { num, size, explode, reroll_below, keep }) => {
+        Dice::Roll { num, size, explode, reroll_below, keep }
+      }
+    };
+    if let Some(msg) = structure_error(&dice) {
+      return Err(de::Error::custom(GameError::InvalidDice(dice.to_string(), msg.to_string())));
+    }
+    Ok(dice)
+  }
+}
+
+/// Check structured dice against the rules that `DiceParser` enforces on the syntax, so that
+/// nothing can be deserialized which couldn't be parsed. Nested dice are checked as they're
+/// deserialized themselves.
+fn structure_error(dice: &Dice) -> Option<&'static str> {
+  match *dice {
+    Dice::Expr { size: 0, .. } | Dice::Roll { size: 0, .. } => {
+      Some("dice must have at least one side")
+    }
+    Dice::BestOf(0, _) => Some("can't take the best of 0"),
+    Dice::Roll { num, size, explode, reroll_below, keep } => {
+      if explode && size < 2 {
+        return Some("only dice with at least two sides can explode");
+      }
+      if let Some(threshold) = reroll_below {
+        if threshold < 2 || threshold > size {
+          return Some("reroll threshold must be between 2 and the size of the die");
+        }
+      }
+      match keep {
+        Some(Keep::Highest(count)) | Some(Keep::Lowest(count)) if count > num => {
+          Some("can't keep more dice than are rolled")
+        }
+        _ => None,
+      }
+    }
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+pub mod test {
+  use std::collections::VecDeque;
+
+  use serde_json;
+  use serde_yaml;
+
+  use crate::dice::*;
+  use crate::types::CreatureEffect;
+
+  /// A `DiceRoller` which returns a predetermined series of rolls.
+  pub struct FixedRoller(pub VecDeque<i32>);
+
+  impl FixedRoller {
+    pub fn new(rolls: Vec<i32>) -> FixedRoller { FixedRoller(rolls.into_iter().collect()) }
+  }
+
+  impl DiceRoller for FixedRoller {
+    fn roll_die(&mut self, size: u32) -> i32 {
+      let roll = self.0.pop_front().expect("FixedRoller ran out of rolls");
+      assert!(roll >= 1 && roll <= size as i32, "FixedRoller roll {} doesn't fit d{}", roll, size);
+      roll
+    }
+  }
+
+  pub fn t_roller() -> SeededRoller { SeededRoller::new(0) }
+
+  #[test]
+  fn dice_plus() {
+    let d = Dice::flat(1).plus(Dice::flat(1));
+    assert_eq!(d.roll(&mut t_roller()), (vec![1, 1], 2));
+  }
+
+  #[test]
+  fn dice_negative() {
+    let d = Dice::flat(1).plus(Dice::flat(-5));
+    assert_eq!(d.roll(&mut t_roller()), (vec![1, -5], -4));
+  }
+
+  #[test]
+  fn dice_uses_roller() {
+    let d = Dice::expr(3, 6);
+    assert_eq!(d.roll(&mut FixedRoller::new(vec![1, 6, 3])), (vec![1, 6, 3], 10));
+  }
+
+  #[test]
+  fn best_of_keeps_highest_total() {
+    let d = Dice::BestOf(2, Box::new(Dice::expr(2, 6)));
+    assert_eq!(d.roll(&mut FixedRoller::new(vec![2, 3, 6, 1])), (vec![6, 1], 7));
+  }
+
+  /// The same seed always produces the same rolls.
+  #[test]
+  fn seeded_rolls_are_reproducible() {
+    let d = Dice::expr(10, 20);
+    let first = d.roll(&mut SeededRoller::new(1234));
+    let second = d.roll(&mut SeededRoller::new(1234));
+    assert_eq!(first, second);
+    for roll in first.0 {
+      assert!(roll >= 1 && roll <= 20);
+    }
+  }
+
+  #[test]
+  fn seeded_roller_counts_rolls() {
+    let mut rng = SeededRoller::new(0);
+    Dice::expr(3, 6).plus(Dice::flat(2)).roll(&mut rng);
+    assert_eq!(rng.rolls(), 3);
+  }
+
+  fn parse(s: &str) -> Dice { s.parse().expect("Couldn't parse dice") }
+
+  fn roll_of(
+    num: u8, size: u8, explode: bool, reroll_below: Option<u8>, keep: Option<Keep>
+  ) -> Dice {
+    Dice::Roll { num, size, explode, reroll_below, keep }
+  }
+
+  #[test]
+  fn parse_modifiers() {
+    assert_eq!(parse("4d6kh3"), roll_of(4, 6, false, None, Some(Keep::Highest(3))));
+    assert_eq!(parse("2d20kl1"), roll_of(2, 20, false, None, Some(Keep::Lowest(1))));
+    assert_eq!(parse("d6!"), roll_of(1, 6, true, None, None));
+    assert_eq!(parse("2d6r<3"), roll_of(2, 6, false, Some(3), None));
+    assert_eq!(parse("3d8"), Dice::expr(3, 8));
+  }
+
+  #[test]
+  fn parse_arithmetic() {
+    assert_eq!(parse("3d6-1d4"), Dice::expr(3, 6).minus(Dice::expr(1, 4)));
+    assert_eq!(parse("1d20 - 2"), Dice::expr(1, 20).plus(Dice::flat(-2)));
+    assert_eq!(parse("-3+d6"), Dice::flat(-3).plus(Dice::expr(1, 6)));
+    assert_eq!(
+      parse("1d6+(2d4-1d4)"),
+      Dice::expr(1, 6).plus(Dice::expr(2, 4).minus(Dice::expr(1, 4)))
+    );
+    assert_eq!(
+      parse("BestOf(2, 1d20+5)"),
+      Dice::BestOf(2, Box::new(Dice::expr(1, 20).plus(Dice::flat(5))))
+    );
+  }
+
+  #[test]
+  fn parse_errors() {
+    for bad in &["", "1d", "d0", "1d1!", "2d6kh3", "1d6r<1", "1d6+", "1d6)", "300d6", "1d6kx2"] {
+      match bad.parse::<Dice>() {
+        Err(GameError::InvalidDice(input, _)) => assert_eq!(&input, bad),
+        x => panic!("Unexpected result for {:?}: {:?}", bad, x),
+      }
+    }
+  }
+
+  #[test]
+  fn display_is_canonical() {
+    let cases = vec![
+      ("4d6kh3+2", "4d6kh3+2"),
+      ("d6!", "1d6!"),
+      ("3d6 - 1d4", "3d6-1d4"),
+      ("(1d6+2)", "1d6+2"),
+      ("BestOf(2,1d20)", "BestOf(2, 1d20)"),
+      ("1d6-(-3)", "1d6-(-3)"),
+      ("2d6kh1r<2!", "2d6!r<2kh1"),
+    ];
+    for (input, canonical) in cases {
+      let dice = parse(input);
+      assert_eq!(dice.to_string(), canonical);
+      assert_eq!(parse(canonical), dice);
+    }
+  }
+
+  #[test]
+  fn display_parenthesizes_nested_sums() {
+    let d = Dice::flat(1).minus(Dice::flat(2).plus(Dice::flat(-3)));
+    assert_eq!(d.to_string(), "1-(2-3)");
+    assert_eq!(parse(&d.to_string()), d);
+    assert_eq!(d.roll(&mut t_roller()).1, 2);
+  }
+
+  #[test]
+  fn keep_highest() {
+    let d = parse("4d6kh3");
+    assert_eq!(d.roll(&mut FixedRoller::new(vec![2, 5, 1, 6])), (vec![2, 5, 6], 13));
+  }
+
+  #[test]
+  fn keep_lowest() {
+    let d = parse("2d20kl1");
+    assert_eq!(d.roll(&mut FixedRoller::new(vec![17, 3])), (vec![3], 3));
+  }
+
+  #[test]
+  fn exploding_and_rerolling() {
+    // The first die rerolls its 1 into a 6, which explodes twice; the second die is a plain 4.
+    let d = parse("2d6!r<3");
+    assert_eq!(d.roll(&mut FixedRoller::new(vec![1, 6, 6, 2, 4])), (vec![14, 4], 18));
+  }
+
+  #[test]
+  fn explosions_are_limited() {
+    let d = parse("1d2!");
+    let mut rng = FixedRoller::new(vec![2; MAX_EXPLOSIONS as usize + 1]);
+    assert_eq!(d.roll(&mut rng).1, 2 * (i32::from(MAX_EXPLOSIONS) + 1));
+  }
+
+  #[test]
+  fn subtraction() {
+    let d = parse("3d6-1d4");
+    assert_eq!(d.roll(&mut FixedRoller::new(vec![1, 2, 3, 4])), (vec![1, 2, 3, -4], 2));
+  }
+
+  #[test]
+  fn deserialize_syntax_or_structure() {
+    let from_str: Dice = serde_json::from_str("\"4d6kh3\"").unwrap();
+    assert_eq!(from_str, parse("4d6kh3"));
+    let from_num: Dice = serde_json::from_str("5").unwrap();
+    assert_eq!(from_num, Dice::flat(5));
+    let d = parse("BestOf(2, 3d6-1d4+2)");
+    let structured = serde_json::to_string(&d).unwrap();
+    assert_eq!(serde_json::from_str::<Dice>(&structured).unwrap(), d);
+    assert!(serde_json::from_str::<Dice>("\"4d6kh5\"").is_err());
+  }
+
+  /// Structured dice are held to the same rules as the syntax.
+  #[test]
+  fn deserialize_invalid_structure() {
+    let bad = [
+      r#"{"Expr": {"num": 1, "size": 0}}"#,
+      r#"{"Roll": {"num": 1, "size": 0}}"#,
+      r#"{"Roll": {"num": 1, "size": 1, "explode": true}}"#,
+      r#"{"Roll": {"num": 1, "size": 6, "reroll_below": 0}}"#,
+      r#"{"Roll": {"num": 1, "size": 6, "reroll_below": 7}}"#,
+      r#"{"Roll": {"num": 2, "size": 6, "keep": {"Highest": 3}}}"#,
+      r#"{"BestOf": [0, "1d20"]}"#,
+    ];
+    for input in &bad {
+      assert!(serde_json::from_str::<Dice>(input).is_err(), "{} should be invalid", input);
+    }
+    let ok: Dice = serde_json::from_str(r#"{"Roll": {"num": 1, "size": 6, "reroll_below": 6}}"#)
+      .unwrap();
+    assert_eq!(ok, parse("1d6r<6"));
+  }
+
+  #[test]
+  fn deserialize_effect_with_syntax() {
+    let effect: CreatureEffect = serde_yaml::from_str("Damage: 2d6r<3+1").unwrap();
+    assert_eq!(effect, CreatureEffect::Damage(parse("2d6r<3+1")));
+  }
+
+  fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} is not close to {}", actual, expected);
+  }
+
+  #[test]
+  fn distribution_of_sum() {
+    let dist = parse("2d6+1").distribution().unwrap();
+    assert_eq!((dist.min(), dist.max()), (3, 13));
+    assert_close(dist.mean(), 8.0);
+    assert_close(dist.variance(), 35.0 / 6.0);
+    assert_close(dist.probability(8), 6.0 / 36.0);
+    assert_close(dist.at_least(12), 3.0 / 36.0);
+    assert_close(dist.at_least(-5), 1.0);
+    assert_close(dist.at_least(14), 0.0);
+  }
+
+  #[test]
+  fn distribution_of_subtraction() {
+    let dist = parse("3d6-1d4").distribution().unwrap();
+    assert_eq!((dist.min(), dist.max()), (-1, 17));
+    assert_close(dist.mean(), 8.0);
+  }
+
+  #[test]
+  fn distribution_of_keep() {
+    let dist = parse("4d6kh3").distribution().unwrap();
+    assert_eq!((dist.min(), dist.max()), (3, 18));
+    assert_close(dist.mean(), 15869.0 / 1296.0);
+    assert_close(dist.probability(18), 21.0 / 1296.0);
+    let dist = parse("2d20kl1").distribution().unwrap();
+    assert_close(dist.mean(), 7.175);
+  }
+
+  #[test]
+  fn distribution_of_best_of() {
+    let dist = parse("BestOf(2, 1d20)").distribution().unwrap();
+    assert_close(dist.mean(), 13.825);
+    assert_close(dist.probability(20), 39.0 / 400.0);
+  }
+
+  #[test]
+  fn distribution_of_modified_dice() {
+    let dist = parse("2d6r<3").distribution().unwrap();
+    assert_close(dist.mean(), 25.0 / 3.0);
+    let dist = parse("1d6!").distribution().unwrap();
+    assert_eq!(dist.max(), 6 * (i32::from(MAX_EXPLOSIONS) + 1));
+    assert_close(dist.iter().map(|(_, p)| p).sum(), 1.0);
+    assert!((dist.mean() - 4.2).abs() < 1e-6);
+    assert_close(dist.probability(6), 0.0);
+  }
+
+  #[test]
+  fn damage_distribution() {
+    let effect = CreatureEffect::MultiEffect(vec![
+      CreatureEffect::Damage(parse("1d4")),
+      CreatureEffect::Heal(parse("1d20")),
+      CreatureEffect::Damage(parse("1d6-3")),
+    ]);
+    let dist = effect.damage_distribution().unwrap();
+    // The second damage roll can't be negative, so it's 0 half of the time.
+    assert_eq!((dist.min(), dist.max()), (1, 7));
+    assert_close(dist.mean(), 2.5 + 1.0);
+    assert_eq!(CreatureEffect::Heal(parse("1d4")).damage_distribution(), None);
+  }
+
+  #[test]
+  fn distribution_of_huge_dice_is_not_calculated() {
+    assert_eq!(parse("255d255!").distribution(), None);
+    assert_eq!(parse("100d100!kh50").distribution(), None);
+    assert!(parse("20d100").distribution().is_some());
+    let effect = CreatureEffect::MultiEffect(vec![
+      CreatureEffect::Damage(parse("1d4")),
+      CreatureEffect::Damage(parse("255d255")),
+    ]);
+    assert_eq!(effect.damage_distribution(), None);
+  }
+}
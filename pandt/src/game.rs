@@ -10,8 +10,10 @@ use serde_yaml;
 use crate::types::*;
 use crate::combat::*;
 use crate::creature::ChangedCreature;
+use crate::dice::{DiceRoller, Distribution, SeededRoller};
 use foldertree::FolderPath;
-use crate::grid::line_through_point;
+use crate::grid::{filter_visible, footprints_visible, line_of_sight, line_through_point,
+                  point3_add_vec, point3_difference, point_in_direction};
 
 impl Game {
   pub fn export_module(&self, export_path: &FolderPath) -> Result<Game, GameError> {
@@ -163,9 +165,11 @@ impl Game {
     self.abilities.get(&abid).ok_or_else(|| GameError::NoAbility(abid).into())
   }
 
-  /// Perform a GameCommand on the current Game.
+  /// Perform a GameCommand on the current Game. All randomness needed to process the command comes
+  /// from `rng`.
   pub fn perform_command(
-    &self, cmd: GameCommand, saved_game_path: &Path, module_path: Option<&Path>
+    &self, cmd: GameCommand, saved_game_path: &Path, module_path: Option<&Path>,
+    rng: &mut dyn DiceRoller,
   ) -> Result<ChangedGame, GameError> {
     use self::GameCommand::*;
     let change = match cmd {
@@ -180,6 +184,7 @@ impl Game {
         })
       }
       SetActiveScene(m_sid) => self.change_with(GameLog::SetActiveScene(m_sid)),
+      SetOutOfCombatCost(cost) => self.change_with(GameLog::SetOutOfCombatCost(cost)),
       // ** Player Management **
       RegisterPlayer(ref pid) => self.change_with(GameLog::RegisterPlayer(pid.clone())),
       GiveCreaturesToPlayer(ref pid, ref cids) => {
@@ -200,7 +205,7 @@ impl Game {
       }
 
       // ** Attribute checks **
-      AttributeCheck(cid, check) => self.attribute_check(cid, &check),
+      AttributeCheck(cid, check) => self.attribute_check(cid, &check, rng),
       // ** Folder Management **
       CreateFolder(path) => self.change_with(GameLog::CreateFolder(path)),
       RenameFolder(path, name) => self.change_with(GameLog::RenameFolder(path, name)),
@@ -270,6 +275,17 @@ impl Game {
         .change_with(GameLog::RemoveSceneChallenge { scene_id, description: description.clone() }),
       SetFocusedSceneCreatures { scene_id, ref creatures } => self
         .change_with(GameLog::SetFocusedSceneCreatures { scene_id, creatures: creatures.clone() }),
+      AddSceneVolumeCondition { scene_id, point, volume, condition, duration } => {
+        self.get_scene(scene_id)?;
+        self.change_with(GameLog::AddVolumeCondition {
+          scene_id,
+          point,
+          volume,
+          condition_id: ConditionID::gen(),
+          condition,
+          duration,
+        })
+      }
       RemoveSceneVolumeCondition { scene_id, condition_id } => {
         self.change_with(GameLog::RemoveSceneVolumeCondition { scene_id, condition_id })
       }
@@ -282,13 +298,52 @@ impl Game {
         self.change_with(GameLog::EditCreatureDetails { creature_id, details })
       }
       PathCreature(scene, cid, pt) => Ok(self.path_creature(scene, cid, pt)?.0),
+      SetCreatureFaction(cid, faction_id) => {
+        self.change_with(GameLog::SetCreatureFaction(cid, faction_id))
+      }
+      CreateFaction(name) => {
+        let faction = Faction { id: FactionID::gen(), name, relationships: HashMap::new() };
+        self.change_with(GameLog::CreateFaction(faction))
+      }
+      SetFactionRelationship { faction_id, other, relationship } => {
+        self.change_with(GameLog::SetFactionRelationship { faction_id, other, relationship })
+      }
       SetCreaturePos(scene, cid, pt) => self.change_with(GameLog::SetCreaturePos(scene, cid, pt)),
       PathCurrentCombatCreature(pt) => self.get_combat()?.get_movement()?.move_current(pt),
-      CombatAct(abid, dtarget) => self.combat_act(abid, dtarget),
-      ActCreature(scene, cid, abid, dtarget) => self.ooc_act(scene, cid, abid, dtarget),
+      CombatAct(abid, dtarget) => self.combat_act(abid, dtarget, rng),
+      ActCreature(scene, cid, abid, dtarget) => self.ooc_act(scene, cid, abid, dtarget, rng),
       EditSceneTerrain { scene_id, ref terrain } => {
         self.change_with(GameLog::EditSceneTerrain { scene_id, terrain: terrain.clone() })
       }
+      EditSceneTerrainLevel { scene_id, level, ref terrain } => {
+        if let Some(pt) = terrain.iter().find(|pt| pt.z != level) {
+          bail!(GameError::PointNotOnLevel(*pt));
+        }
+        let terrain = terrain.clone();
+        self.change_with(GameLog::EditSceneTerrainLevel { scene_id, level, terrain })
+      }
+      EditSceneStairs { scene_id, ref stairs } => {
+        self.change_with(GameLog::EditSceneStairs { scene_id, stairs: stairs.clone() })
+      }
+      EditSceneTerrainCells { scene_id, ref terrain_cells } => {
+        let closed = terrain_cells.iter().filter(|&(_, cell)| !cell.is_open()).map(|(pt, _)| *pt);
+        self.check_unoccupied(scene_id, closed)?;
+        let terrain_cells = terrain_cells.clone();
+        self.change_with(GameLog::EditSceneTerrainCells { scene_id, terrain_cells })
+      }
+      OpenDoor(scene_id, pt) => {
+        self.check_door(scene_id, pt)?;
+        self.change_with(GameLog::OpenDoor(scene_id, pt))
+      }
+      CloseDoor(scene_id, pt) => {
+        self.check_door(scene_id, pt)?;
+        self.check_unoccupied(scene_id, vec![pt])?;
+        self.change_with(GameLog::CloseDoor(scene_id, pt))
+      }
+      DestroyWall(scene_id, pt) => match self.get_scene(scene_id)?.terrain_cells.get(&pt) {
+        Some(TerrainCell::DestructibleWall) => self.change_with(GameLog::DestroyWall(scene_id, pt)),
+        _ => bail!(GameError::NoDestructibleWall(pt)),
+      },
       EditSceneHighlights { scene_id, ref highlights } => {
         self.change_with(GameLog::EditSceneHighlights { scene_id, highlights: highlights.clone() })
       }
@@ -298,17 +353,29 @@ impl Game {
         .change_with(GameLog::EditSceneRelatedScenes { scene_id, related_scenes: related_scenes.clone() }),
       EditSceneSceneHotspots { scene_id, ref scene_hotspots } => self
         .change_with(GameLog::EditSceneSceneHotspots{ scene_id, scene_hotspots: scene_hotspots.clone() }),
-      StartCombat(scene, cids) => self.start_combat(scene, cids),
+      StartCombat(scene, cids, ref mode) => self.start_combat(scene, cids, mode, rng),
       StopCombat => self.change_with(GameLog::StopCombat),
-      AddCreatureToCombat(cid) => self.add_creature_to_combat(cid),
+      AddCreatureToCombat(cid) => self.add_creature_to_combat(cid, rng),
       RemoveCreatureFromCombat(cid) => self.change_with(GameLog::RemoveCreatureFromCombat(cid)),
-      RerollCombatInitiative => self.change().apply_combat(|c| c.reroll_initiative()),
+      RerollCombatInitiative => self.change().apply_combat(|c| c.reroll_initiative(rng)),
       ChangeCreatureInitiative(cid, new_pos) => {
         self.change_with(GameLog::CombatLog(CombatLog::ChangeCreatureInitiative(cid, new_pos)))
       }
       ForceNextTurn => self.change_with(GameLog::CombatLog(CombatLog::ForceNextTurn)),
       ForcePrevTurn => self.change_with(GameLog::CombatLog(CombatLog::ForcePrevTurn)),
-      Done => self.next_turn(),
+      SetActionBudget(budget) => {
+        self.change_with(GameLog::CombatLog(CombatLog::SetActionBudget(budget)))
+      }
+      Done => self.next_turn(rng),
+      DelayTurn => self.delay_turn(rng),
+      ResumeTurn(cid) => self.change_with(GameLog::CombatLog(CombatLog::ResumeTurn(cid))),
+      SwitchTurnMember(cid) => self.change().apply_combat(|c| c.switch_turn_member(cid)),
+      ReadyAction { ability_id, ref target, trigger } => {
+        self.ready_action(ability_id, target.clone(), trigger)
+      }
+      ReactToInterrupt => self.react_to_interrupt(rng),
+      DeclineInterrupt => self.change_with(GameLog::CombatLog(CombatLog::ResolveInterrupt)),
+      Rest(kind, ref cids) => self.rest(kind, cids),
 
       // These are handled by the app before being passed to the Game:
       Rollback(..) => bug("Game Rollback"),
@@ -317,23 +384,34 @@ impl Game {
   }
 
   fn start_combat(
-    &self, scene_id: SceneID, cids: Vec<CreatureID>
+    &self, scene_id: SceneID, cids: Vec<CreatureID>, mode: &InitiativeMode,
+    rng: &mut dyn DiceRoller,
   ) -> Result<ChangedGame, GameError> {
-    let cids_with_inits = Combat::roll_initiative(self, cids)?;
-    self.change_with(GameLog::StartCombat(scene_id, cids_with_inits))
+    let cids_with_inits = Combat::roll_initiative(self, mode, cids, rng)?;
+    let mut change = self.change_with(GameLog::StartCombat(scene_id, cids_with_inits))?;
+    if *mode != InitiativeMode::Individual {
+      let log = CombatLog::SetInitiativeMode(mode.clone());
+      change = change.apply_combat(|c| c.change_with(log))?;
+    }
+    change.start_turn(true, rng)
   }
 
-  fn add_creature_to_combat(&self, cid: CreatureID) -> Result<ChangedGame, GameError> {
+  fn add_creature_to_combat(
+    &self, cid: CreatureID, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedGame, GameError> {
     let creature = self.get_creature(cid)?;
-    let init = creature.creature.initiative.roll().1 as i16;
+    let init = match self.get_combat()?.combat.group_initiative(self, cid)? {
+      Some(init) => init,
+      None => creature.creature.initiative.roll(rng).1 as i16,
+    };
     self.change_with(GameLog::AddCreatureToCombat(cid, init))
   }
 
   fn attribute_check(
-    &self, cid: CreatureID, check: &AttributeCheck
+    &self, cid: CreatureID, check: &AttributeCheck, rng: &mut dyn DiceRoller
   ) -> Result<ChangedGame, GameError> {
     let creature = self.get_creature(cid)?;
-    let (rolled, success) = creature.creature.attribute_check(check)?;
+    let (rolled, success) = creature.creature.attribute_check(check, rng)?;
     self.change_with(GameLog::AttributeCheckResult(cid, check.clone(), rolled, success))
   }
 
@@ -349,25 +427,60 @@ impl Game {
   ) -> Result<(ChangedGame, u32units::Length), GameError> {
     let scene = self.get_scene(scene_id)?;
     let creature = self.get_creature(cid)?;
-    let (pts, distance) = self
+    let volume = Volume::AABB(creature.creature.size);
+    let (mut pts, mut distance) = self
       .tile_system
-      .find_path(
-        scene.get_pos(cid)?,
-        max_distance,
-        &scene.terrain,
-        Volume::AABB(creature.creature.size),
-        pt,
-      )
+      .find_path(scene.get_pos(cid)?, max_distance, scene.terrain_map(), volume, pt)
       .ok_or(GameError::NoPathFound)?;
     debug_assert!(distance <= max_distance);
 
+    // Combatants who leave a hostile creature's reach are stopped so that it can react.
+    let interruption = match self.get_combat() {
+      Ok(ref combat) if combat.scene.id == scene_id && combat.combat.contains_creature(cid) => {
+        combat.opportunity_attack(cid, &pts)?
+      }
+      _ => None,
+    };
+    let interrupt = match interruption {
+      Some((stop, interrupt)) => {
+        pts.truncate(stop + 1);
+        distance = self.tile_system.path_cost(scene.terrain_map(), volume, &pts).ok_or_else(|| {
+          GameError::BuggyProgram("Interrupted path must be walkable".to_string())
+        })?;
+        Some(interrupt)
+      }
+      None => None,
+    };
+
     let change = self.change_with(GameLog::PathCreature(scene_id, cid, pts))?;
+    let change = match interrupt {
+      Some(interrupt) => {
+        change.apply_combat(|c| c.change_with(CombatLog::QueueInterrupt(interrupt)))?
+      }
+      None => change,
+    };
+    let change = change.trigger_readied_actions(ReadyTrigger::CreatureMoves(cid))?;
     Ok((change, distance))
   }
 
-  fn next_turn(&self) -> Result<ChangedGame, GameError> {
-    let change = self.change().apply_combat(|c| c.next_turn())?;
-    change.apply_creature(self.current_combat.as_ref().unwrap().current_creature_id(), |c| c.tick())
+  fn next_turn(&self, rng: &mut dyn DiceRoller) -> Result<ChangedGame, GameError> {
+    let ending = self.get_combat()?.combat.turn_members(self)?;
+    let mut change = self.change();
+    for cid in ending {
+      change = change.apply_volume_conditions(cid, rng)?;
+      change = change.apply_creature(cid, |c| c.tick(TickTiming::EndOfTurn, rng))?;
+    }
+    let round = self.get_combat()?.combat.round;
+    let change = change.apply_combat(|c| c.next_turn())?;
+    let new_round = change.game.get_combat()?.combat.round != round;
+    change.start_turn(new_round, rng)
+  }
+
+  fn delay_turn(&self, rng: &mut dyn DiceRoller) -> Result<ChangedGame, GameError> {
+    let round = self.get_combat()?.combat.round;
+    let change = self.change().apply_combat(|c| c.delay_turn())?;
+    let new_round = change.game.get_combat()?.combat.round != round;
+    change.start_turn(new_round, rng)
   }
 
   fn link_folder_item(
@@ -469,6 +582,7 @@ impl Game {
       },
 
       SetActiveScene(m_sid) => self.active_scene = m_sid,
+      SetOutOfCombatCost(cost) => self.ooc_ability_cost = cost,
 
       // Player stuff
       RegisterPlayer(ref pid) => if self.players.contains_key(pid) {
@@ -510,7 +624,7 @@ impl Game {
       }
 
       // purely informational
-      ChatFromGM(..) | ChatFromPlayer(..) | AttributeCheckResult(..) => {}
+      ChatFromGM(..) | ChatFromPlayer(..) | AttributeCheckResult(..) | DiceSeed(..) => {}
 
       // purely informational
       CreateFolder(ref path) => self.campaign.make_folders(path, Folder::new()),
@@ -844,11 +958,84 @@ impl Game {
           })
           .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
       }
+      DecrementVolumeConditionRemaining { scene_id, condition_id } => {
+        let mut scene = self.get_scene(scene_id)?.clone();
+        {
+          let volume_condition = scene
+            .volume_conditions
+            .get_mut(&condition_id)
+            .ok_or_else(|| GameError::ConditionNotFound(condition_id))?;
+          match volume_condition.remaining {
+            Duration::Interminate | Duration::Rounds(0) => bail!(GameError::BuggyProgram(
+              "Tried to count down a volume condition that has run out".to_string()
+            )),
+            Duration::Rounds(ref mut remaining) => *remaining -= 1,
+          }
+        }
+        self.scenes.insert(scene);
+      }
 
       EditSceneTerrain { scene_id, ref terrain } => {
         self
           .scenes
-          .mutate(&scene_id, move |s| s.terrain = terrain.clone())
+          .mutate(&scene_id, move |s| {
+            s.terrain = terrain.clone();
+            s.sync_terrain_cells();
+          })
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      EditSceneTerrainLevel { scene_id, level, ref terrain } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| {
+            s.terrain.retain(|pt| pt.z != level);
+            s.terrain.extend(terrain.iter().cloned());
+            s.sync_terrain_cells();
+          })
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      EditSceneStairs { scene_id, ref stairs } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.stairs = stairs.clone())
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      EditSceneTerrainCells { scene_id, ref terrain_cells } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.set_terrain_cells(terrain_cells))
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      OpenDoor(scene_id, pt) => {
+        let door = TerrainCell::Door { open: true };
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.set_terrain_cell(pt, Some(door)))
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      CloseDoor(scene_id, pt) => {
+        let door = TerrainCell::Door { open: false };
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.set_terrain_cell(pt, Some(door)))
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      DestroyWall(scene_id, pt) => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.set_terrain_cell(pt, None))
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      AddSceneTerrain { scene_id, ref terrain } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.open_terrain(terrain))
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      RemoveSceneTerrain { scene_id, ref terrain } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.close_terrain(terrain))
           .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
       }
       EditSceneHighlights { scene_id, ref highlights } => {
@@ -896,12 +1083,36 @@ impl Game {
       AddCreatureToCombat(cid, init) => {
         let mut combat = self.current_combat.clone().ok_or(GameError::NotInCombat)?;
         self.check_creature_id(cid)?;
-        if combat.creatures.iter().any(|&(c, _)| c == cid) {
+        if combat.contains_creature(cid) {
           bail!(GameError::AlreadyInCombat(cid));
         }
-        combat.creatures.push((cid, init));
+        combat.add_creature(self, cid, init)?;
         self.current_combat = Some(combat);
       }
+      SetCreatureFaction(cid, faction_id) => {
+        if let Some(faction_id) = faction_id {
+          self.get_faction(faction_id)?;
+        }
+        self
+          .creatures
+          .mutate(&cid, |c| c.faction = faction_id)
+          .ok_or_else(|| GameError::CreatureNotFound(cid.to_string()))?;
+      }
+      CreateFaction(ref faction) => {
+        self
+          .factions
+          .try_insert(faction.clone())
+          .ok_or_else(|| GameError::FactionAlreadyExists(faction.id))?;
+      }
+      SetFactionRelationship { faction_id, other, relationship } => {
+        self.get_faction(other)?;
+        self
+          .factions
+          .mutate(&faction_id, |f| {
+            f.relationships.insert(other, relationship);
+          })
+          .ok_or_else(|| GameError::FactionNotFound(faction_id))?;
+      }
       RemoveCreatureFromCombat(cid) => {
         let combat = {
           let combat = self.get_combat()?;
@@ -975,6 +1186,24 @@ impl Game {
     }
   }
 
+  fn check_door(&self, scene_id: SceneID, pt: Point3) -> Result<(), GameError> {
+    match self.get_scene(scene_id)?.terrain_cells.get(&pt) {
+      Some(TerrainCell::Door { .. }) => Ok(()),
+      _ => Err(GameError::NoDoor(pt)),
+    }
+  }
+
+  /// Check that no creature is in the way of closing off some tiles.
+  fn check_unoccupied<I: IntoIterator<Item = Point3>>(
+    &self, scene_id: SceneID, pts: I
+  ) -> Result<(), GameError> {
+    let occupied = self.get_scene(scene_id)?.occupied_tiles(self)?;
+    match pts.into_iter().find(|pt| occupied.contains(pt)) {
+      Some(pt) => Err(GameError::TileOccupied(pt)),
+      None => Ok(()),
+    }
+  }
+
   pub fn is_in_combat(&self, cid: CreatureID) -> bool {
     match self.get_combat() {
       Ok(combat) => combat.combat.contains_creature(cid),
@@ -1008,22 +1237,72 @@ impl Game {
 
   // ** CONSIDER ** moving this chunk of code to... Scene.rs?
 
-  fn combat_act(&self, abid: AbilityID, target: DecidedTarget) -> Result<ChangedGame, GameError> {
+  fn combat_act(
+    &self, abid: AbilityID, target: DecidedTarget, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedGame, GameError> {
     let combat = self.get_combat()?;
+    if !combat.combat.pending_interrupts.is_empty() {
+      bail!(GameError::InterruptsPending);
+    }
     let scene = combat.scene;
     let actor = combat.combat.current_creature_id();
-    self._act(scene, actor, abid, target, true)
+    let slot = self.get_ability(abid)?.slot;
+    let change = self._act(scene, actor, abid, target, true, rng)?.use_action(slot)?;
+    change.trigger_readied_actions(ReadyTrigger::CreatureActs(actor))
+  }
+
+  fn ready_action(
+    &self, ability_id: AbilityID, target: DecidedTarget, trigger: ReadyTrigger
+  ) -> Result<ChangedGame, GameError> {
+    let creature = self.get_combat()?.current_creature()?;
+    if !creature.has_ability(ability_id) {
+      bail!(GameError::CreatureLacksAbility(creature.id(), ability_id));
+    }
+    let slot = self.get_ability(ability_id)?.slot;
+    let readied = ReadiedAction { ability_id, target, trigger };
+    let log = GameLog::CombatLog(CombatLog::ReadyAction(creature.id(), readied));
+    self.change_with(log)?.use_action(slot)
+  }
+
+  /// Resolve the first pending interrupt by having its creature use its ability, spending one of
+  /// its reactions.
+  fn react_to_interrupt(&self, rng: &mut dyn DiceRoller) -> Result<ChangedGame, GameError> {
+    let combat = self.get_combat()?;
+    let interrupt =
+      combat.combat.pending_interrupts.first().cloned().ok_or(GameError::NoInterruptPending)?;
+    let creature = self.get_creature(interrupt.creature_id)?;
+    if !creature.can_act() {
+      bail!(GameError::CannotAct(creature.id()));
+    }
+    let change = self.change().apply_combat(|c| {
+      c.change_with(CombatLog::ResolveInterrupt)?.apply(&CombatLog::UseReaction(creature.id()))
+    })?;
+    let ability = self.get_ability(interrupt.ability_id)?;
+    self.creature_act(&creature, combat.scene, ability, interrupt.target, change, true, rng)
+  }
+
+  fn rest(&self, kind: RestKind, cids: &[CreatureID]) -> Result<ChangedGame, GameError> {
+    let mut change = self.change();
+    for cid in cids {
+      if self.is_in_combat(*cid) {
+        bail!(GameError::AlreadyInCombat(*cid));
+      }
+      change = change.apply_creature(*cid, |c| c.rest(kind))?;
+    }
+    Ok(change)
   }
 
   fn ooc_act(
-    &self, scene: SceneID, cid: CreatureID, abid: AbilityID, target: DecidedTarget
+    &self, scene: SceneID, cid: CreatureID, abid: AbilityID, target: DecidedTarget,
+    rng: &mut dyn DiceRoller,
   ) -> Result<ChangedGame, GameError> {
     let scene = self.get_scene(scene)?;
-    self._act(scene, cid, abid, target, false)
+    self._act(scene, cid, abid, target, false, rng)
   }
 
   fn _act(
-    &self, scene: &Scene, cid: CreatureID, abid: AbilityID, target: DecidedTarget, in_combat: bool
+    &self, scene: &Scene, cid: CreatureID, abid: AbilityID, target: DecidedTarget, in_combat: bool,
+    rng: &mut dyn DiceRoller,
   ) -> Result<ChangedGame, GameError> {
     if !scene.creatures.contains_key(&cid) {
       bail!(GameError::CreatureNotFound(cid.to_string()));
@@ -1038,6 +1317,7 @@ impl Game {
           target,
           self.change(),
           in_combat,
+          rng,
         )
       } else {
         Err(GameError::CreatureLacksAbility(creature.id(), abid).into())
@@ -1049,114 +1329,225 @@ impl Game {
 
   pub fn creature_act(
     &self, creature: &DynamicCreature, scene: &Scene, ability: &Ability, target: DecidedTarget,
-    mut change: ChangedGame, in_combat: bool,
+    change: ChangedGame, in_combat: bool, rng: &mut dyn DiceRoller,
   ) -> Result<ChangedGame, GameError> {
-    let mut change = match ability.action {
-      Action::Creature { ref effect, target: tspec } => {
-        let targets = self.resolve_creature_targets(creature, scene, tspec, target)?;
-        for creature_id in &targets {
-          change = change.apply_creature(*creature_id, |c| c.apply_effect(effect))?;
-        }
-        change
-      }
-      Action::SceneVolume { ref effect, target: tspec } => {
-        match (effect, tspec, target) {
-          (
-            &SceneEffect::CreateVolumeCondition { duration, ref condition },
-            SceneTarget::RangedVolume { volume, .. },
-            // TODO: unimplemented!: honor and check `range`
-            DecidedTarget::Point(point),
-          ) => {
-            let log = GameLog::AddVolumeCondition {
-              condition_id: ConditionID::gen(),
-              scene_id: scene.id,
-              point,
-              volume: volume,
-              condition: condition.clone(),
-              duration,
-            };
-            change = change.apply(&log)?;
-          }
-          _ => bail!(GameError::BuggyProgram("Ugh".to_string())),
-        }
-        change
-      }
-    };
+    let cooldown = creature.ability_cooldown(ability.id);
+    if cooldown > 0 {
+      bail!(GameError::AbilityOnCooldown(creature.id(), ability.id, cooldown));
+    }
+    let action = &ability.action;
+    let mut change =
+      change.apply_action(creature.id(), scene.id, action, ability.propagation, target, rng)?;
 
-    if in_combat {
+    if in_combat || self.ooc_ability_cost == OutOfCombatCost::Charged {
       change = change.apply_creature(creature.id(), |c| c.creature.reduce_energy(ability.cost))?;
+      if ability.cooldown > 0 {
+        let log = CreatureLog::StartCooldown(ability.id, ability.cooldown);
+        change = change.apply_creature(creature.id(), |c| c.creature.change_with(log))?;
+      }
     }
     Ok(change)
   }
 
   pub fn resolve_creature_targets(
     &self, creature: &DynamicCreature, scene: &Scene, target: CreatureTarget,
-    decision: DecidedTarget,
+    propagation: VolumePropagation, decision: DecidedTarget,
   ) -> Result<Vec<CreatureID>, GameError> {
     match (target, decision) {
-      (CreatureTarget::Melee, DecidedTarget::Creature(cid)) => {
-        if self.tile_system.points_within_distance(
-          scene.get_pos(creature.id())?,
-          scene.get_pos(cid)?,
-          MELEE_RANGE,
-        ) {
-          Ok(vec![cid])
-        } else {
-          Err(GameError::CreatureOutOfRange(cid).into())
-        }
+      (CreatureTarget::Melee { .. }, DecidedTarget::Creature(cid)) => {
+        self.check_creature_targetable(scene, creature.id(), cid, MELEE_RANGE)?;
+        self.check_creature_allowed(creature.id(), target, cid)
+      }
+      (CreatureTarget::Range { distance, .. }, DecidedTarget::Creature(cid)) => {
+        self.check_creature_targetable(scene, creature.id(), cid, distance)?;
+        self.check_creature_allowed(creature.id(), target, cid)
       }
-      (CreatureTarget::Range(max), DecidedTarget::Creature(cid)) => if self
-        .tile_system
-        .points_within_distance(scene.get_pos(creature.id())?, scene.get_pos(cid)?, max)
-      {
-        Ok(vec![cid])
-      } else {
-        Err(GameError::CreatureOutOfRange(cid).into())
-      },
       (CreatureTarget::Actor, DecidedTarget::Actor) => Ok(vec![creature.id()]),
+      // Choosing a single creature is the same as choosing a list of just that creature.
+      (CreatureTarget::SomeCreaturesInVolumeInRange { .. }, DecidedTarget::Creature(cid)) => {
+        let decision = DecidedTarget::Creatures(vec![cid]);
+        self.resolve_creature_targets(creature, scene, target, propagation, decision)
+      }
+      (CreatureTarget::SomeCreaturesInVolumeInRange { .. }, DecidedTarget::Creatures(cids)) => {
+        self.some_creatures_in_volume_targets(scene, creature.id(), target, propagation, cids)
+      }
+      (CreatureTarget::SomeCreaturesInVolumeInRange { .. }, decided) => {
+        Err(GameError::InvalidTargetForTargetSpec(target, decided).into())
+      }
       (_, DecidedTarget::Point(pt)) => {
-        self.volume_creature_targets(scene, creature.creature.id, target, pt)
+        self.volume_creature_targets(scene, creature.creature.id, target, propagation, pt)
       }
       (spec, decided) => Err(GameError::InvalidTargetForTargetSpec(spec, decided).into()),
     }
   }
 
-  // TODO: unimplemented! Honor terrain!
-  // 1. `pt` must be visible to the caster
-  // 2. volumes must not go through blocked terrain
-  // 3. volumes must (generally) not go around corners
+  /// Check that the filter of a single-creature target lets the actor affect the chosen creature.
+  fn check_creature_allowed(
+    &self, actor_id: CreatureID, target: CreatureTarget, cid: CreatureID
+  ) -> Result<Vec<CreatureID>, GameError> {
+    if !target.filter().allows(self.creature_relationship(actor_id, cid)?) {
+      bail!(GameError::InvalidTargetForTargetSpec(target, DecidedTarget::Creature(cid)));
+    }
+    Ok(vec![cid])
+  }
+
+  /// Check that a creature targeted by an ability is within the ability's `range` of the actor,
+  /// and that the actor can see it. Creatures that take up more than one tile can be targeted
+  /// through any of their tiles.
+  fn check_creature_targetable(
+    &self, scene: &Scene, actor_id: CreatureID, target_id: CreatureID, range: u32units::Length
+  ) -> Result<(), GameError> {
+    let actor_tiles = scene.creature_tiles(self, actor_id)?;
+    let target_tiles = scene.creature_tiles(self, target_id)?;
+    if !self.tile_system.footprints_within_distance(&actor_tiles, &target_tiles, range) {
+      bail!(GameError::CreatureOutOfRange(target_id));
+    }
+    if !footprints_visible(&scene.terrain, &actor_tiles, &target_tiles) {
+      bail!(GameError::CreatureNotVisible(target_id));
+    }
+    Ok(())
+  }
+
+  /// Check that a point targeted by an ability is within the ability's `range` of the actor, and
+  /// that the actor can see it.
+  fn check_point_targetable(
+    &self, scene: &Scene, actor_id: CreatureID, pt: Point3, range: u32units::Length
+  ) -> Result<(), GameError> {
+    let actor_pos = scene.get_pos(actor_id)?;
+    if !self.tile_system.points_within_distance(actor_pos, pt, range) {
+      bail!(GameError::PointOutOfRange(pt));
+    }
+    if !line_of_sight(&scene.terrain, actor_pos, pt) {
+      bail!(GameError::PointNotVisible(pt));
+    }
+    Ok(())
+  }
+
+  /// Validate the creatures chosen for a `SomeCreaturesInVolumeInRange` ability. The first creature
+  /// is the primary target, which must be within `range` of the actor and visible to it; the
+  /// volume is centered on it, and every other chosen creature must be inside that volume and
+  /// allowed by the target's filter.
+  fn some_creatures_in_volume_targets(
+    &self, scene: &Scene, actor_id: CreatureID, target: CreatureTarget,
+    propagation: VolumePropagation, cids: Vec<CreatureID>,
+  ) -> Result<Vec<CreatureID>, GameError> {
+    let (volume, maximum, range, filter) = match target {
+      CreatureTarget::SomeCreaturesInVolumeInRange { volume, maximum, range, filter } => {
+        (volume, maximum, range, filter)
+      }
+      _ => bail!(GameError::BuggyProgram("Expected SomeCreaturesInVolumeInRange".to_string())),
+    };
+    let unique: HashSet<CreatureID> = cids.iter().cloned().collect();
+    if cids.is_empty() || cids.len() > maximum as usize || unique.len() != cids.len() {
+      bail!(GameError::InvalidTargetForTargetSpec(target, DecidedTarget::Creatures(cids)));
+    }
+    self.check_creature_targetable(scene, actor_id, cids[0], range)?;
+    let primary_pos = scene.get_pos(cids[0])?;
+    let in_volume = scene.creatures_in_volume(self, primary_pos, volume, propagation)?;
+    for cid in &cids {
+      if !in_volume.contains(cid) || !filter.allows(self.creature_relationship(actor_id, *cid)?) {
+        bail!(GameError::InvalidTarget(*cid));
+      }
+    }
+    Ok(cids)
+  }
+
+  /// Find the creatures affected by a volume-targeting ability aimed at a point. Lines are always
+  /// stopped by walls; other volumes spread according to `propagation`. Creatures that the
+  /// target's filter doesn't allow are left out.
   fn volume_creature_targets(
-    &self, scene: &Scene, actor_id: CreatureID, target: CreatureTarget, pt: Point3
+    &self, scene: &Scene, actor_id: CreatureID, target: CreatureTarget,
+    propagation: VolumePropagation, pt: Point3,
   ) -> Result<Vec<CreatureID>, GameError> {
-    match target {
-      CreatureTarget::AllCreaturesInVolumeInRange { volume, .. } => {
-        // TODO: unimplemented! honor and check `range`
-        Ok(scene.creatures_in_volume(self.tile_system, pt, volume))
+    let cids = match target {
+      CreatureTarget::AllCreaturesInVolumeInRange { volume, range, .. } => {
+        self.check_point_targetable(scene, actor_id, pt, range)?;
+        scene.creatures_in_volume(self, pt, volume, propagation)?
       }
-      CreatureTarget::LineFromActor { distance } => {
+      CreatureTarget::LineFromActor { distance, .. } => {
         let actor_pos = scene.get_pos(actor_id)?;
         let volume = line_through_point(actor_pos, pt, distance);
-        let cids = scene.creatures_in_volume(self.tile_system, actor_pos, volume);
+        let mut cids =
+          scene.creatures_in_volume(self, actor_pos, volume, VolumePropagation::Burst)?;
         // TODO: *ideally* we should start the line adjacent to the caster, but filtering out
         // also works.
-        let cids = cids.into_iter().filter(|cid| *cid != actor_id).collect();
-        Ok(cids)
+        cids.retain(|cid| *cid != actor_id);
+        cids
       }
       _ => bail!(GameError::InvalidTargetForTargetSpec(target, DecidedTarget::Point(pt))),
+    };
+    self.filter_targets(actor_id, target.filter(), cids)
+  }
+
+  /// Leave out the creatures that a target filter doesn't allow the actor to affect.
+  fn filter_targets(
+    &self, actor_id: CreatureID, filter: TargetFilter, cids: Vec<CreatureID>
+  ) -> Result<Vec<CreatureID>, GameError> {
+    let mut allowed = vec![];
+    for cid in cids {
+      if filter.allows(self.creature_relationship(actor_id, cid)?) {
+        allowed.push(cid);
+      }
+    }
+    Ok(allowed)
+  }
+
+  /// How one creature regards another. Creatures are allies of themselves and of the rest of their
+  /// faction, and creatures without a faction are enemies of everyone else.
+  pub fn creature_relationship(
+    &self, from: CreatureID, to: CreatureID
+  ) -> Result<Relationship, GameError> {
+    if from == to {
+      return Ok(Relationship::Ally);
     }
+    let from_faction = self.get_creature(from)?.creature.faction;
+    let to_faction = self.get_creature(to)?.creature.faction;
+    Ok(match (from_faction, to_faction) {
+      (Some(from_faction), Some(to_faction)) => {
+        if from_faction == to_faction {
+          Relationship::Ally
+        } else {
+          let faction = self.get_faction(from_faction)?;
+          faction.relationships.get(&to_faction).cloned().unwrap_or(Relationship::Enemy)
+        }
+      }
+      _ => Relationship::Enemy,
+    })
   }
 
   /// Calculate which *points* and which *creatures* will be affected by an ability targeted at a
-  /// point.
+  /// point, along with the distribution of the damage it would deal to each of those creatures.
   pub fn preview_volume_targets(
     &self, scene: &Scene, actor_id: CreatureID, ability_id: AbilityID, pt: Point3
-  ) -> Result<(Vec<CreatureID>, Vec<Point3>), GameError> {
+  ) -> Result<(Vec<CreatureID>, Vec<Point3>, Option<Distribution>), GameError> {
     let ability = self.get_ability(ability_id)?;
 
+    match ability.action {
+      Action::Creature { target: CreatureTarget::AllCreaturesInVolumeInRange { range, .. }, .. }
+      | Action::Creature { target: CreatureTarget::SomeCreaturesInVolumeInRange { range, .. }, .. }
+      | Action::SceneVolume { target: SceneTarget::RangedVolume { range, .. }, .. } => {
+        self.check_point_targetable(scene, actor_id, pt, range)?
+      }
+      _ => {}
+    }
+
     let cids = match ability.action {
-      Action::Creature { target, .. } => self.volume_creature_targets(scene, actor_id, target, pt)?,
+      // Any of the creatures in the volume could be chosen.
+      Action::Creature {
+        target: CreatureTarget::SomeCreaturesInVolumeInRange { volume, filter, .. },
+        ..
+      } => {
+        let cids = scene.creatures_in_volume(self, pt, volume, ability.propagation)?;
+        self.filter_targets(actor_id, filter, cids)?
+      }
+      Action::Creature { target, .. } => {
+        self.volume_creature_targets(scene, actor_id, target, ability.propagation, pt)?
+      }
       Action::SceneVolume { target: SceneTarget::RangedVolume { volume, .. }, .. } => {
-        scene.creatures_in_volume(self.tile_system, pt, volume)
+        scene.creatures_in_volume(self, pt, volume, ability.propagation)?
+      }
+      Action::MoveActor { .. } | Action::Multi(..) => {
+        bail!(GameError::InvalidTargetForAction(ability.action.clone(), DecidedTarget::Point(pt)))
       }
     };
     let tiles = match ability.action {
@@ -1164,18 +1555,34 @@ impl Game {
         target: CreatureTarget::AllCreaturesInVolumeInRange { volume, .. },
         ..
       }
+      | Action::Creature {
+        target: CreatureTarget::SomeCreaturesInVolumeInRange { volume, .. },
+        ..
+      }
       | Action::SceneVolume { target: SceneTarget::RangedVolume { volume, .. }, .. } => {
-        // TODO: unimplemented! honor and check `range`
-        scene.open_terrain_in_volume(self, pt, volume)?
+        scene.open_terrain_in_volume(self, pt, volume, ability.propagation)?
       }
-      Action::Creature { target: CreatureTarget::LineFromActor { distance }, .. } => {
+      Action::Creature { target: CreatureTarget::LineFromActor { distance, .. }, .. } => {
         let actor_pos = scene.get_pos(actor_id)?;
         let volume = line_through_point(actor_pos, pt, distance);
-        scene.open_terrain_in_volume(self, actor_pos, volume)?
+        scene.open_terrain_in_volume(self, actor_pos, volume, VolumePropagation::Burst)?
       }
       _ => vec![],
     };
-    Ok((cids, tiles))
+    let damage = match ability.action {
+      Action::Creature { ref effect, .. } => effect.damage_distribution(),
+      Action::SceneVolume { .. } | Action::MoveActor { .. } | Action::Multi(..) => None,
+    };
+    Ok((cids, tiles, damage))
+  }
+
+  /// Get all of the open tiles in a scene that a creature can see.
+  pub fn get_visible_tiles(
+    &self, scene: SceneID, creature_id: CreatureID
+  ) -> Result<Vec<Point3>, GameError> {
+    let scene = self.get_scene(scene)?;
+    let pos = scene.get_pos(creature_id)?;
+    Ok(filter_visible(&scene.terrain, pos, scene.terrain.iter().cloned().collect()))
   }
 
   pub fn get_movement_options(
@@ -1186,7 +1593,7 @@ impl Game {
     if creature.can_move() {
       Ok(self.tile_system.get_all_accessible(
         scene.get_pos(creature_id)?,
-        &scene.terrain,
+        scene.terrain_map(),
         Volume::AABB(creature.creature.size),
         creature.speed(),
       ))
@@ -1200,26 +1607,78 @@ impl Game {
     &self, scene: SceneID, creature_id: CreatureID, ability_id: AbilityID
   ) -> Result<PotentialTargets, GameError> {
     let ability = self.get_ability(ability_id)?;
+    self.action_target_options(scene, creature_id, &ability.action)
+  }
+
+  /// Get the possible targets for the next part of a multi-part ability, given the targets already
+  /// decided for the parts before it. Those parts are carried out on a copy of the game first, so
+  /// that a strike after a dash can target the creatures next to wherever the dash ends. Any dice
+  /// rolled along the way are thrown away.
+  pub fn get_multi_part_target_options(
+    &self, scene: SceneID, creature_id: CreatureID, ability_id: AbilityID,
+    decided: &[DecidedTarget],
+  ) -> Result<PotentialTargets, GameError> {
+    let ability = self.get_ability(ability_id)?;
+    let invalid = || GameError::InvalidTargetForAction(
+      ability.action.clone(),
+      DecidedTarget::Multi(decided.to_vec()),
+    );
+    let parts = match ability.action {
+      Action::Multi(ref parts) if decided.len() < parts.len() => parts,
+      _ => bail!(invalid()),
+    };
+    let mut change = self.change();
+    let mut rng = SeededRoller::new(0);
+    let propagation = ability.propagation;
+    for (&(_, ref part), target) in parts.iter().zip(decided) {
+      let target = target.clone();
+      change = change.apply_action(creature_id, scene, part, propagation, target, &mut rng)?;
+    }
+    change.game.action_target_options(scene, creature_id, &parts[decided.len()].1)
+  }
 
+  /// Get the possible targets for an action. The options for every part of an `Action::Multi` are
+  /// based on the scene as it is now, so they don't account for anything the earlier parts do; use
+  /// `get_multi_part_target_options` to find the options for a part after the first.
+  fn action_target_options(
+    &self, scene: SceneID, creature_id: CreatureID, action: &Action
+  ) -> Result<PotentialTargets, GameError> {
     use crate::types::Action as A;
     use crate::types::CreatureTarget as CT;
-    Ok(match ability.action {
-      A::Creature { target: CT::Melee, .. } => {
-        self.creatures_in_range(scene, creature_id, MELEE_RANGE)?
+    Ok(match *action {
+      A::Creature { target: CT::Melee { filter }, .. } => {
+        self.creatures_in_range(scene, creature_id, MELEE_RANGE, filter)?
       }
-      A::Creature { target: CT::Range(distance), .. } => {
-        self.creatures_in_range(scene, creature_id, distance)?
+      A::Creature { target: CT::Range { distance, filter }, .. } => {
+        self.creatures_in_range(scene, creature_id, distance, filter)?
       }
       A::Creature { target: CT::Actor, .. } => PotentialTargets::CreatureIDs(vec![creature_id]),
       A::Creature { target: CT::AllCreaturesInVolumeInRange { range, .. }, .. }
       | A::SceneVolume { target: SceneTarget::RangedVolume { range, .. }, .. } => {
         self.open_terrain_in_range(scene, creature_id, range)?
       }
-      A::Creature { target: CT::LineFromActor { distance }, .. } => {
+      A::Creature { target: CT::LineFromActor { distance, .. }, .. } => {
         self.open_terrain_in_range(scene, creature_id, distance)?
       }
-      A::Creature { target: CT::SomeCreaturesInVolumeInRange { .. }, .. } => {
-        unimplemented!("SomeCreaturesInVolumeInRange not implemented")
+      A::Creature { target: CT::SomeCreaturesInVolumeInRange { range, filter, .. }, .. } => {
+        self.creatures_in_range(scene, creature_id, range, filter)?
+      }
+      A::MoveActor { distance } => {
+        let scene = self.get_scene(scene)?;
+        let creature = self.get_creature(creature_id)?;
+        PotentialTargets::Points(self.tile_system.get_all_accessible(
+          scene.get_pos(creature_id)?,
+          scene.terrain_map(),
+          Volume::AABB(creature.creature.size),
+          distance,
+        ))
+      }
+      A::Multi(ref parts) => {
+        let mut options = vec![];
+        for &(ref name, ref part) in parts {
+          options.push((name.clone(), self.action_target_options(scene, creature_id, part)?));
+        }
+        PotentialTargets::Multi(options)
       }
     })
   }
@@ -1229,19 +1688,30 @@ impl Game {
   ) -> Result<PotentialTargets, GameError> {
     let scene = self.get_scene(scene)?;
     let creature_pos = scene.get_pos(creature_id)?;
-    let pts = self.tile_system.open_points_in_range(creature_pos, &scene.terrain, range);
-    Ok(PotentialTargets::Points(pts))
+    let ts = self.tile_system;
+    // open_points_in_range gives us a square, but not all of its corners are necessarily in range.
+    let pts = ts
+      .open_points_in_range(creature_pos, &scene.terrain, range)
+      .into_iter()
+      .filter(|pt| ts.points_within_distance(creature_pos, *pt, range))
+      .collect();
+    Ok(PotentialTargets::Points(filter_visible(&scene.terrain, creature_pos, pts)))
   }
 
   fn creatures_in_range(
-    &self, scene: SceneID, creature_id: CreatureID, distance: u32units::Length
+    &self, scene: SceneID, creature_id: CreatureID, distance: u32units::Length,
+    filter: TargetFilter,
   ) -> Result<PotentialTargets, GameError> {
     let scene = self.get_scene(scene)?;
-    let my_pos = scene.get_pos(creature_id)?;
+    let my_tiles = scene.creature_tiles(self, creature_id)?;
     let mut results = vec![];
-    for (creature_id, &(creature_pos, _)) in &scene.creatures {
-      if self.tile_system.points_within_distance(my_pos, creature_pos, distance) {
-        results.push(*creature_id);
+    for target_id in scene.creatures.keys() {
+      let tiles = scene.creature_tiles(self, *target_id)?;
+      if self.tile_system.footprints_within_distance(&my_tiles, &tiles, distance)
+        && footprints_visible(&scene.terrain, &my_tiles, &tiles)
+        && filter.allows(self.creature_relationship(creature_id, *target_id)?)
+      {
+        results.push(*target_id);
       }
     }
     Ok(PotentialTargets::CreatureIDs(results))
@@ -1253,6 +1723,10 @@ impl Game {
     self.classes.get(&class).ok_or_else(|| GameError::ClassNotFound(class).into())
   }
 
+  pub fn get_faction(&self, faction_id: FactionID) -> Result<&Faction, GameError> {
+    self.factions.get(&faction_id).ok_or_else(|| GameError::FactionNotFound(faction_id).into())
+  }
+
   pub fn change(&self) -> ChangedGame { ChangedGame { game: self.clone(), logs: vec![] } }
 
   pub fn change_with(&self, log: GameLog) -> Result<ChangedGame, GameError> {
@@ -1268,9 +1742,11 @@ pub struct ChangedGame {
 }
 
 impl ChangedGame {
+  /// Apply a log to the game, and record it so that it's saved and replayed along with the rest.
   pub fn apply(&self, log: &GameLog) -> Result<ChangedGame, GameError> {
     let mut new = self.clone();
     new.game = self.game.apply_log(log)?;
+    new.logs.push(log.clone());
     Ok(new)
   }
 
@@ -1287,65 +1763,346 @@ impl ChangedGame {
     Ok(new)
   }
 
-  pub fn apply_creature<F>(&self, cid: CreatureID, f: F) -> Result<ChangedGame, GameError>
-  where
-    F: FnOnce(DynamicCreature) -> Result<ChangedCreature, GameError>,
-  {
-    let creature = self.game.get_creature(cid)?;
-    let change = f(creature)?;
-    let mut new = self.clone();
-    let (creature, logs) = change.done();
-    new.game.creatures.mutate(&cid, move |c| *c = creature);
-    new.logs.extend(creature_logs_into_game_logs(cid, logs));
-    Ok(new)
+  /// Queue up any readied actions that are set off by a trigger. This does nothing outside of
+  /// combat.
+  pub fn trigger_readied_actions(self, trigger: ReadyTrigger) -> Result<ChangedGame, GameError> {
+    if self.game.current_combat.is_none() {
+      return Ok(self);
+    }
+    self.apply_combat(|c| c.trigger_readied_actions(trigger))
   }
 
-  pub fn done(self) -> (Game, Vec<GameLog>) { (self.game, self.logs) }
-}
-
-fn bug<T>(msg: &str) -> Result<T, GameError> {
-  Err(GameError::BuggyProgram(msg.to_string()).into())
-}
-
-pub fn load_app_from_path(
-  saved_game_path: &Path, module_path: Option<&Path>, source: ModuleSource, filename: &str
-) -> Result<App, GameError> {
-  let filename = match (source, module_path) {
-    (ModuleSource::Module, Some(module_path)) => module_path.join(filename),
-    (ModuleSource::Module, None) => return Err(GameError::NoModuleSource),
-    (ModuleSource::SavedGame, _) => saved_game_path.join(filename),
-  };
-  let mut appf = File::open(filename.clone())
-    .map_err(|e| GameError::CouldNotOpenAppFile(filename.to_string_lossy().into(), e))?;
-  let mut apps = String::new();
-  appf.read_to_string(&mut apps).unwrap();
-  let app: App = serde_yaml::from_str(&apps).map_err(|e| GameError::CouldNotParseApp(e))?;
-  app.current_game.validate_campaign()?;
-  Ok(app)
-}
+  /// Whether the changes so far have left interrupts waiting to be resolved in combat.
+  fn interrupted(&self) -> bool {
+    self.game.current_combat.as_ref().map_or(false, |combat| !combat.pending_interrupts.is_empty())
+  }
 
-#[cfg(test)]
-pub mod test {
-  use std::collections::HashSet;
-  use std::iter::FromIterator;
-  use std::path::PathBuf;
+  /// Take up one of the current creature's actions for the turn, unless the slot is free.
+  fn use_action(self, slot: ActionSlot) -> Result<ChangedGame, GameError> {
+    if slot == ActionSlot::Free {
+      return Ok(self);
+    }
+    self.apply_combat(|c| c.change_with(CombatLog::UseAction(slot)))
+  }
 
-  use crate::combat::test::*;
-  use crate::game::*;
-  use indexed::IndexedHashMap;
-  use crate::types::test::*;
+  /// Do everything that happens as the current turn starts for each creature taking it, including
+  /// the start of a new round when we've just gone around the order. A creature resuming a delayed
+  /// turn already had its turn started.
+  fn start_turn(
+    self, new_round: bool, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedGame, GameError> {
+    let (cids, members, resuming) = {
+      let combat = self.game.get_combat()?.combat;
+      let current = combat.current_creature_id();
+      (combat.creature_ids(), combat.turn_members(&self.game)?, combat.resuming.contains(&current))
+    };
+    let mut change = self;
+    if new_round {
+      for cid in cids {
+        change = change.apply_creature(cid, |c| c.tick(TickTiming::StartOfRound, rng))?;
+      }
+      change = change.count_down_volume_conditions()?;
+    }
+    if resuming {
+      return Ok(change);
+    }
+    for cid in members {
+      change = change.apply_creature(cid, |c| c.tick(TickTiming::StartOfTurn, rng))?;
+      change = change.apply_volume_conditions(cid, rng)?;
+      change = change.apply_hazards(cid, rng)?;
+    }
+    Ok(change)
+  }
 
-  pub fn t_start_combat(game: &Game, combatants: Vec<CreatureID>) -> Game {
-    t_perform(game, GameCommand::StartCombat(t_scene_id(), combatants))
+  /// Set off the hazards a creature is standing in at the start of its turn.
+  fn apply_hazards(
+    self, cid: CreatureID, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedGame, GameError> {
+    let effects = self.game.get_combat()?.scene.creature_hazards(&self.game, cid)?;
+    let mut change = self;
+    for effect in effects {
+      change = change.apply_creature(cid, |c| c.apply_effect(&effect, rng))?;
+    }
+    Ok(change)
   }
 
-  pub fn t_game_act(game: &Game, ability_id: AbilityID, target: DecidedTarget) -> Game {
-    t_perform(game, GameCommand::CombatAct(ability_id, target))
+  /// Set off the recurring effects of the volume conditions a creature is standing in, at the
+  /// start or end of its turn. Each volume condition only affects a creature once per turn.
+  fn apply_volume_conditions(
+    self, cid: CreatureID, rng: &mut dyn DiceRoller
+  ) -> Result<ChangedGame, GameError> {
+    let effects = {
+      let combat = self.game.get_combat()?;
+      let creature = self.game.get_creature(cid)?;
+      let mut effects = vec![];
+      for (condition_id, volume_condition) in
+        combat.scene.creature_volume_conditions(&self.game, creature.creature)?
+      {
+        // Volume conditions go off at turn boundaries whenever they declare that they tick, until
+        // they run out.
+        if volume_condition.remaining == Duration::Rounds(0) {
+          continue;
+        }
+        if let Some((_, effect)) = volume_condition.condition.recurring_effect() {
+          let applied = combat.combat.volume_conditions_applied.get(&cid);
+          if !applied.map_or(false, |applied| applied.contains(&condition_id)) {
+            effects.push((condition_id, effect.clone()));
+          }
+        }
+      }
+      effects
+    };
+    if effects.is_empty() {
+      return Ok(self);
+    }
+    let applied =
+      CombatLog::VolumeConditionsApplied(cid, effects.iter().map(|&(id, _)| id).collect());
+    let mut change = self.apply_combat(|c| c.change_with(applied))?;
+    for (_, effect) in effects {
+      change = change.apply_creature(cid, |c| c.apply_effect(&effect, rng))?;
+    }
+    Ok(change)
   }
 
-  pub fn t_game() -> Game {
-    let mut game: Game = Default::default();
-    game.abilities = t_abilities();
+  /// Count down the volume conditions in the combat's scene, getting rid of the ones that have run
+  /// out.
+  fn count_down_volume_conditions(self) -> Result<ChangedGame, GameError> {
+    let logs: Vec<GameLog> = {
+      let scene = self.game.get_combat()?.scene;
+      scene
+        .volume_conditions
+        .iter()
+        .filter_map(|(condition_id, volume_condition)| {
+          let (scene_id, condition_id) = (scene.id, *condition_id);
+          match volume_condition.remaining {
+            Duration::Interminate => None,
+            Duration::Rounds(0) => {
+              Some(GameLog::RemoveSceneVolumeCondition { scene_id, condition_id })
+            }
+            Duration::Rounds(_) => {
+              Some(GameLog::DecrementVolumeConditionRemaining { scene_id, condition_id })
+            }
+          }
+        })
+        .collect()
+    };
+    let mut change = self;
+    for log in &logs {
+      change = change.apply(log)?;
+    }
+    Ok(change)
+  }
+
+  /// Have a creature carry out an action in a scene. The targets are resolved against the game
+  /// as it is after all of the changes so far, so each part of an `Action::Multi` sees what the
+  /// parts before it did.
+  pub fn apply_action(
+    self, actor: CreatureID, scene_id: SceneID, action: &Action, propagation: VolumePropagation,
+    target: DecidedTarget, rng: &mut dyn DiceRoller,
+  ) -> Result<ChangedGame, GameError> {
+    let mut change = self;
+    match *action {
+      Action::Creature { ref effect, target: tspec } => {
+        let targets = {
+          let game = &change.game;
+          let creature = game.get_creature(actor)?;
+          let scene = game.get_scene(scene_id)?;
+          game.resolve_creature_targets(&creature, scene, tspec, propagation, target)?
+        };
+        for creature_id in &targets {
+          change = change.apply_creature_effect(actor, scene_id, *creature_id, effect, rng)?;
+        }
+      }
+      Action::SceneVolume { ref effect, target: SceneTarget::RangedVolume { volume, range } } => {
+        let point = match target {
+          DecidedTarget::Point(point) => point,
+          target => bail!(GameError::InvalidTargetForAction(action.clone(), target)),
+        };
+        let log = {
+          let game = &change.game;
+          let scene = game.get_scene(scene_id)?;
+          game.check_point_targetable(scene, actor, point, range)?;
+          match *effect {
+            SceneEffect::CreateVolumeCondition { duration, ref condition } => {
+              GameLog::AddVolumeCondition {
+                condition_id: ConditionID::gen(),
+                scene_id,
+                point,
+                volume,
+                condition: condition.clone(),
+                duration,
+              }
+            }
+            SceneEffect::CarveTerrain => GameLog::AddSceneTerrain {
+              scene_id,
+              terrain: scene.closed_terrain_in_volume(game, point, volume),
+            },
+            SceneEffect::RaiseTerrain => GameLog::RemoveSceneTerrain {
+              scene_id,
+              terrain: scene.unoccupied_terrain_in_volume(game, point, volume)?,
+            },
+          }
+        };
+        change = change.apply(&log)?;
+      }
+      Action::MoveActor { distance } => {
+        let point = match target {
+          DecidedTarget::Point(point) => point,
+          target => bail!(GameError::InvalidTargetForAction(action.clone(), target)),
+        };
+        let (moved, _) = change.game.path_creature_distance(scene_id, actor, point, distance)?;
+        change.logs.extend(moved.logs);
+        change.game = moved.game;
+      }
+      Action::Multi(ref parts) => match target {
+        DecidedTarget::Multi(ref decisions) if decisions.len() == parts.len() => {
+          for (&(_, ref part), decision) in parts.iter().zip(decisions) {
+            let decision = decision.clone();
+            change = change.apply_action(actor, scene_id, part, propagation, decision, rng)?;
+            // Nothing else can happen until an interrupt is resolved, so a part that sets one off
+            // (like a dash past a hostile creature) forfeits the rest.
+            if change.interrupted() {
+              break;
+            }
+          }
+        }
+        target => bail!(GameError::InvalidTargetForAction(action.clone(), target)),
+      },
+    }
+    Ok(change)
+  }
+
+  /// Apply an effect to a creature targeted by an action.
+  fn apply_creature_effect(
+    self, actor: CreatureID, scene_id: SceneID, target: CreatureID, effect: &CreatureEffect,
+    rng: &mut dyn DiceRoller,
+  ) -> Result<ChangedGame, GameError> {
+    match *effect {
+      CreatureEffect::MultiEffect(ref effects) => {
+        let mut change = self;
+        for effect in effects {
+          change = change.apply_creature_effect(actor, scene_id, target, effect, rng)?;
+        }
+        Ok(change)
+      }
+      CreatureEffect::Push(..)
+      | CreatureEffect::Pull(..)
+      | CreatureEffect::Knockback { .. }
+      | CreatureEffect::Teleport(..) => self.force_movement(actor, scene_id, target, effect, rng),
+      _ => self.apply_creature(target, |c| c.apply_effect(effect, rng)),
+    }
+  }
+
+  /// Move a creature in a straight line relative to the actor that's forcing it to move. Creatures
+  /// only move through (or to) terrain they fit in.
+  fn force_movement(
+    self, actor: CreatureID, scene_id: SceneID, target: CreatureID, effect: &CreatureEffect,
+    rng: &mut dyn DiceRoller,
+  ) -> Result<ChangedGame, GameError> {
+    let (log, blocked) = {
+      let game = &self.game;
+      let ts = game.tile_system;
+      let scene = game.get_scene(scene_id)?;
+      let size = game.get_creature(target)?.creature.size;
+      let actor_pos = scene.get_pos(actor)?;
+      let start = scene.get_pos(target)?;
+      let away = point3_add_vec(start, point3_difference(start, actor_pos));
+      match *effect {
+        CreatureEffect::Push(distance) | CreatureEffect::Knockback { distance, .. } => {
+          let dest = point_in_direction(start, away, distance);
+          let path = ts.forced_path(&scene.terrain, Volume::AABB(size), start, dest);
+          let blocked = path.last().cloned().unwrap_or(start) != dest;
+          (GameLog::PathCreature(scene_id, target, path), blocked)
+        }
+        CreatureEffect::Pull(distance) => {
+          let dest = point_in_direction(start, actor_pos, distance);
+          let actor_tiles = scene.creature_tiles(game, actor)?;
+          let path = ts
+            .forced_path(&scene.terrain, Volume::AABB(size), start, dest)
+            .into_iter()
+            .take_while(|pt| !ts.footprint(*pt, size).iter().any(|t| actor_tiles.contains(t)))
+            .collect();
+          (GameLog::PathCreature(scene_id, target, path), false)
+        }
+        CreatureEffect::Teleport(distance) => {
+          let dest = point_in_direction(start, away, distance);
+          let landing =
+            ts.first_fit_on_line(&scene.terrain, Volume::AABB(size), dest, start).unwrap_or(start);
+          (GameLog::SetCreaturePos(scene_id, target, landing), false)
+        }
+        _ => return bug("force_movement called with an effect that isn't forced movement"),
+      }
+    };
+    let change = self.apply(&log)?;
+    match *effect {
+      CreatureEffect::Knockback { ref impact, .. } if blocked => {
+        let impact = CreatureEffect::Damage(impact.clone());
+        change.apply_creature(target, |c| c.apply_effect(&impact, rng))
+      }
+      _ => Ok(change),
+    }
+  }
+
+  pub fn apply_creature<F>(&self, cid: CreatureID, f: F) -> Result<ChangedGame, GameError>
+  where
+    F: FnOnce(DynamicCreature) -> Result<ChangedCreature, GameError>,
+  {
+    let creature = self.game.get_creature(cid)?;
+    let change = f(creature)?;
+    let mut new = self.clone();
+    let (creature, logs) = change.done();
+    new.game.creatures.mutate(&cid, move |c| *c = creature);
+    new.logs.extend(creature_logs_into_game_logs(cid, logs));
+    Ok(new)
+  }
+
+  pub fn done(self) -> (Game, Vec<GameLog>) { (self.game, self.logs) }
+}
+
+fn bug<T>(msg: &str) -> Result<T, GameError> {
+  Err(GameError::BuggyProgram(msg.to_string()).into())
+}
+
+pub fn load_app_from_path(
+  saved_game_path: &Path, module_path: Option<&Path>, source: ModuleSource, filename: &str
+) -> Result<App, GameError> {
+  let filename = match (source, module_path) {
+    (ModuleSource::Module, Some(module_path)) => module_path.join(filename),
+    (ModuleSource::Module, None) => return Err(GameError::NoModuleSource),
+    (ModuleSource::SavedGame, _) => saved_game_path.join(filename),
+  };
+  let mut appf = File::open(filename.clone())
+    .map_err(|e| GameError::CouldNotOpenAppFile(filename.to_string_lossy().into(), e))?;
+  let mut apps = String::new();
+  appf.read_to_string(&mut apps).unwrap();
+  let app: App = serde_yaml::from_str(&apps).map_err(|e| GameError::CouldNotParseApp(e))?;
+  app.current_game.validate_campaign()?;
+  Ok(app)
+}
+
+#[cfg(test)]
+pub mod test {
+  use std::collections::HashSet;
+  use std::iter::FromIterator;
+  use std::path::PathBuf;
+
+  use crate::combat::test::*;
+  use crate::dice::test::*;
+  use crate::game::*;
+  use indexed::IndexedHashMap;
+  use crate::types::test::*;
+
+  pub fn t_start_combat(game: &Game, combatants: Vec<CreatureID>) -> Game {
+    t_perform(game, GameCommand::StartCombat(t_scene_id(), combatants, InitiativeMode::Individual))
+  }
+
+  pub fn t_game_act(game: &Game, ability_id: AbilityID, target: DecidedTarget) -> Game {
+    t_perform(game, GameCommand::CombatAct(ability_id, target))
+  }
+
+  pub fn t_game() -> Game {
+    let mut game: Game = Default::default();
+    game.abilities = t_abilities();
     game.classes = t_classes();
     let mut rogue = t_rogue("rogue");
     rogue.id = cid_rogue();
@@ -1388,6 +2145,8 @@ pub mod test {
         abilities: rogue_abs,
         conditions: vec![],
         color: "purple".to_string(),
+        opportunity_attack: None,
+        energy_regen: Energy(0),
       },
       Class {
         id: classid_ranger(),
@@ -1395,6 +2154,8 @@ pub mod test {
         abilities: ranger_abs,
         conditions: vec![],
         color: "darkgreen".to_string(),
+        opportunity_attack: None,
+        energy_regen: Energy(0),
       },
       Class {
         id: classid_cleric(),
@@ -1402,12 +2163,14 @@ pub mod test {
         abilities: cleric_abs,
         conditions: vec![],
         color: "lightgreen".to_string(),
+        opportunity_attack: None,
+        energy_regen: Energy(0),
       },
     ])
   }
 
   pub fn perf(game: &Game, cmd: GameCommand) -> Result<ChangedGame, GameError> {
-    game.perform_command(cmd, &PathBuf::from(""), None)
+    game.perform_command(cmd, &PathBuf::from(""), None, &mut t_roller())
   }
 
   pub fn t_perform(game: &Game, cmd: GameCommand) -> Game { perf(game, cmd).unwrap().game }
@@ -1417,9 +2180,10 @@ pub mod test {
     let game = t_game();
     let non = CreatureID::gen();
     let result = game.perform_command(
-      GameCommand::StartCombat(t_scene_id(), vec![non]),
+      GameCommand::StartCombat(t_scene_id(), vec![non], InitiativeMode::Individual),
       &PathBuf::from(""),
       None,
+      &mut t_roller(),
     );
     match result {
       Err(GameError::CreatureNotFound(id)) => assert_eq!(id, non.to_string()),
@@ -1431,9 +2195,10 @@ pub mod test {
   fn combat_must_have_creatures() {
     let game = t_game();
     let result = game.perform_command(
-      GameCommand::StartCombat(t_scene_id(), vec![]),
+      GameCommand::StartCombat(t_scene_id(), vec![], InitiativeMode::Individual),
       &PathBuf::from(""),
       None,
+      &mut t_roller(),
     );
     match result {
       Err(GameError::CombatMustHaveCreatures) => {}
@@ -1477,10 +2242,7 @@ pub mod test {
   #[test]
   fn three_char_infinite_combat() {
     let game = t_game();
-    let game = t_perform(
-      &game,
-      GameCommand::StartCombat(t_scene_id(), vec![cid_rogue(), cid_ranger(), cid_cleric()]),
-    );
+    let game = t_start_combat(&game, vec![cid_rogue(), cid_ranger(), cid_cleric()]);
     let iter = |game: &Game| -> Result<Game, GameError> {
       let game = t_game_act(game, abid_punch(), DecidedTarget::Creature(cid_ranger()));
       let game = t_perform(&game, GameCommand::Done);
@@ -1529,8 +2291,9 @@ pub mod test {
       GameCommand::SetCreaturePos(t_scene_id(), cid_cleric(), Point3::new(600, 0, 0)),
     );
     let scene = game.get_scene(t_scene_id()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
 
-    let cids = scene.creatures_in_volume(game.tile_system, pt, volume);
+    let cids = scene.creatures_in_volume(&game, pt, volume, prop).unwrap();
     let cids = HashSet::<CreatureID>::from_iter(cids);
     assert_eq!(cids, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
   }
@@ -1541,6 +2304,7 @@ pub mod test {
     let target_spec = CreatureTarget::AllCreaturesInVolumeInRange {
       range: u32cm(1000),
       volume: Volume::Sphere(u32cm(200)),
+      filter: TargetFilter::Any,
     };
     let pt = Point3::new(500, 0, 0);
 
@@ -1554,16 +2318,102 @@ pub mod test {
     );
 
     let scene = game.get_scene(t_scene_id()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
 
-    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, pt).unwrap();
+    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, prop, pt).unwrap();
     let targets = HashSet::<CreatureID>::from_iter(targets);
     assert_eq!(targets, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
   }
 
+  /// Put the rogue and the cleric in a faction together, leaving the ranger on its own.
+  fn t_party_game() -> Game {
+    let faction_id = FactionID(uuid_0());
+    let name = "Party".to_string();
+    let party = Faction { id: faction_id, name, relationships: HashMap::new() };
+    let game = t_game().change().apply(&GameLog::CreateFaction(party)).unwrap().game;
+    let game = t_perform(&game, GameCommand::SetCreatureFaction(cid_rogue(), Some(faction_id)));
+    t_perform(&game, GameCommand::SetCreatureFaction(cid_cleric(), Some(faction_id)))
+  }
+
+  #[test]
+  fn factions_decide_relationships() {
+    let game = t_party_game();
+    let relationship = |from, to| game.creature_relationship(from, to).unwrap();
+    assert_eq!(relationship(cid_rogue(), cid_cleric()), Relationship::Ally);
+    assert_eq!(relationship(cid_rogue(), cid_ranger()), Relationship::Enemy);
+    assert_eq!(relationship(cid_ranger(), cid_ranger()), Relationship::Ally);
+
+    let wildlife_id = FactionID(uuid_1());
+    let name = "Wildlife".to_string();
+    let wildlife = Faction { id: wildlife_id, name, relationships: HashMap::new() };
+    let game = game.change().apply(&GameLog::CreateFaction(wildlife)).unwrap().game;
+    let game = t_perform(&game, GameCommand::SetCreatureFaction(cid_ranger(), Some(wildlife_id)));
+    let game = t_perform(
+      &game,
+      GameCommand::SetFactionRelationship {
+        faction_id: FactionID(uuid_0()),
+        other: wildlife_id,
+        relationship: Relationship::Neutral,
+      },
+    );
+    let relationship = |from, to| game.creature_relationship(from, to).unwrap();
+    assert_eq!(relationship(cid_rogue(), cid_ranger()), Relationship::Neutral);
+    assert_eq!(relationship(cid_ranger(), cid_rogue()), Relationship::Enemy);
+  }
+
+  #[test]
+  fn volume_targets_respect_filters() {
+    let game = t_party_game();
+    let game = t_perform(
+      &game,
+      GameCommand::SetCreaturePos(t_scene_id(), cid_ranger(), Point3::new(400, 0, 0)),
+    );
+    let game = t_perform(
+      &game,
+      GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), Point3::new(500, 0, 0)),
+    );
+    let game = t_perform(
+      &game,
+      GameCommand::SetCreaturePos(t_scene_id(), cid_cleric(), Point3::new(600, 0, 0)),
+    );
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
+    let pt = Point3::new(500, 0, 0);
+    let volume = Volume::Sphere(u32cm(200));
+    let targets = |filter| {
+      let spec = CreatureTarget::AllCreaturesInVolumeInRange { range: u32cm(1000), volume, filter };
+      let targets = game.volume_creature_targets(scene, cid_cleric(), spec, prop, pt).unwrap();
+      HashSet::<CreatureID>::from_iter(targets)
+    };
+    assert_eq!(targets(TargetFilter::Enemies), HashSet::from_iter(vec![cid_ranger()]));
+    assert_eq!(targets(TargetFilter::Allies), HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
+  }
+
+  #[test]
+  fn single_targets_respect_filters() {
+    let mut game = t_party_game();
+    let target = CreatureTarget::Range { distance: u32cm(1000), filter: TargetFilter::Enemies };
+    game.abilities.mutate(&abid_punch(), |ab| {
+      ab.action = Action::Creature { target, effect: CreatureEffect::Damage(Dice::flat(3)) }
+    });
+    let options = game.get_target_options(t_scene_id(), cid_rogue(), abid_punch()).unwrap();
+    assert_eq!(options, PotentialTargets::CreatureIDs(vec![cid_ranger()]));
+    let act = |target| GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid_punch(), target);
+    match perf(&game, act(DecidedTarget::Creature(cid_cleric()))) {
+      Err(GameError::InvalidTargetForTargetSpec(_, DecidedTarget::Creature(cid))) => {
+        assert_eq!(cid, cid_cleric())
+      }
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    let game = t_perform(&game, act(DecidedTarget::Creature(cid_ranger())));
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+  }
+
   #[test]
   fn test_line_targets() {
     let game = t_game();
-    let target_spec = CreatureTarget::LineFromActor { distance: u32cm(1000) };
+    let target_spec =
+      CreatureTarget::LineFromActor { distance: u32cm(1000), filter: TargetFilter::Any };
     let pt = Point3::new(100, 0, 0);
 
     let game = t_perform(
@@ -1575,12 +2425,748 @@ pub mod test {
       GameCommand::SetCreaturePos(t_scene_id(), cid_cleric(), Point3::new(200, 0, 0)),
     );
     let scene = game.get_scene(t_scene_id()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
 
-    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, pt).unwrap();
+    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, prop, pt).unwrap();
     let targets = HashSet::<CreatureID>::from_iter(targets);
     assert_eq!(targets, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
   }
 
+  /// A game where the rogue is a big 2x2 ogre.
+  fn t_ogre_game() -> Game {
+    let mut game = t_game();
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.size = AABB { x: u32cm(200), y: u32cm(200), z: u32cm(100) }
+    });
+    game
+  }
+
+  #[test]
+  fn large_creatures_are_hit_by_volumes_touching_any_of_their_tiles() {
+    let pos = Point3::new(500, 0, 0);
+    let set_pos = GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), pos);
+    let game = t_perform(&t_ogre_game(), set_pos);
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let target_spec = CreatureTarget::AllCreaturesInVolumeInRange {
+      range: u32cm(1000),
+      volume: Volume::Sphere(u32cm(100)),
+      filter: TargetFilter::Any,
+    };
+    let prop = VolumePropagation::Unobstructed;
+    let pt = Point3::new(700, 0, 0);
+    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, prop, pt).unwrap();
+    assert_eq!(targets, vec![cid_rogue()]);
+  }
+
+  #[test]
+  fn large_creatures_are_in_range_if_any_of_their_tiles_are() {
+    // The ogre's closest tile is 5 meters away, in range of the ranger's shot, but a normal-sized
+    // creature at the same position would be 6 meters away.
+    let pos = Point3::new(-600, 0, 0);
+    let shoot = GameCommand::ActCreature(
+      t_scene_id(),
+      cid_ranger(),
+      abid_shoot(),
+      DecidedTarget::Creature(cid_rogue()),
+    );
+    let game = t_perform(&t_game(), GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), pos));
+    match perf(&game, shoot.clone()) {
+      Err(GameError::CreatureOutOfRange(cid)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    let set_pos = GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), pos);
+    let game = t_perform(&t_ogre_game(), set_pos);
+    perf(&game, shoot).unwrap();
+
+    // The ogre can punch from its closest tile, too.
+    let pos = Point3::new(-200, 0, 0);
+    let game = t_perform(&game, GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), pos));
+    let target = DecidedTarget::Creature(cid_ranger());
+    perf(&game, GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid_punch(), target)).unwrap();
+  }
+
+  fn t_some_creatures_target() -> CreatureTarget {
+    CreatureTarget::SomeCreaturesInVolumeInRange {
+      volume: Volume::Sphere(u32cm(200)),
+      maximum: 2,
+      range: u32cm(1000),
+      filter: TargetFilter::Any,
+    }
+  }
+
+  /// A game where the rogue is at 500,0,0, the cleric is at 600,0,0, and the ranger is at 0,0,0.
+  fn t_spread_out_game() -> Game {
+    let game = t_game();
+    let game = t_perform(
+      &game,
+      GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), Point3::new(500, 0, 0)),
+    );
+    let cleric_pos = Point3::new(600, 0, 0);
+    t_perform(&game, GameCommand::SetCreaturePos(t_scene_id(), cid_cleric(), cleric_pos))
+  }
+
+  #[test]
+  fn some_creatures_in_volume_targets() {
+    let game = t_spread_out_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let ranger = game.get_creature(cid_ranger()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
+    let target = t_some_creatures_target();
+
+    let decision = DecidedTarget::Creatures(vec![cid_rogue(), cid_cleric()]);
+    let targets = game.resolve_creature_targets(&ranger, scene, target, prop, decision).unwrap();
+    assert_eq!(targets, vec![cid_rogue(), cid_cleric()]);
+
+    let decision = DecidedTarget::Creature(cid_cleric());
+    let targets = game.resolve_creature_targets(&ranger, scene, target, prop, decision).unwrap();
+    assert_eq!(targets, vec![cid_cleric()]);
+  }
+
+  /// A game where the rogue has an ability that punches someone and then heals the rogue.
+  fn t_multi_game() -> (Game, AbilityID) {
+    let mut game = t_game();
+    let abid: AbilityID = "00000000-0000-0000-0000-000000000010".parse().unwrap();
+    let action = Action::Multi(vec![
+      (
+        "Strike".to_string(),
+        Action::Creature {
+          target: CreatureTarget::Melee { filter: TargetFilter::Any },
+          effect: CreatureEffect::Damage(Dice::flat(3)),
+        },
+      ),
+      (
+        "Mend".to_string(),
+        Action::Creature {
+          target: CreatureTarget::Actor,
+          effect: CreatureEffect::Heal(Dice::flat(2)),
+        },
+      ),
+    ]);
+    let name = "Strike and Mend".to_string();
+    game.abilities.insert(Ability { id: abid, name, action, ..t_punch() });
+    game.classes.mutate(&classid_rogue(), |c| c.abilities.push(abid));
+    game.creatures.mutate(&cid_rogue(), |c| c.cur_health = HP(5));
+    (game, abid)
+  }
+
+  #[test]
+  fn multi_actions_apply_every_part() {
+    let (game, abid) = t_multi_game();
+    let target =
+      DecidedTarget::Multi(vec![DecidedTarget::Creature(cid_ranger()), DecidedTarget::Actor]);
+    let game = t_perform(&game, GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid, target));
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+    assert_eq!(game.get_creature(cid_rogue()).unwrap().creature.cur_health, HP(7));
+  }
+
+  #[test]
+  fn multi_actions_are_atomic() {
+    let (game, abid) = t_multi_game();
+    let bad_part = DecidedTarget::Multi(vec![
+      DecidedTarget::Creature(cid_ranger()),
+      DecidedTarget::Creature(cid_ranger()),
+    ]);
+    match perf(&game, GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid, bad_part)) {
+      Err(GameError::InvalidTargetForTargetSpec(CreatureTarget::Actor, _)) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    let too_few = DecidedTarget::Multi(vec![DecidedTarget::Creature(cid_ranger())]);
+    match perf(&game, GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid, too_few)) {
+      Err(GameError::InvalidTargetForAction(..)) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn multi_action_target_options() {
+    let (game, abid) = t_multi_game();
+    let options = game.get_target_options(t_scene_id(), cid_rogue(), abid).unwrap();
+    match options {
+      PotentialTargets::Multi(parts) => {
+        let names: Vec<&str> = parts.iter().map(|&(ref name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Strike", "Mend"]);
+        assert_eq!(parts[1].1, PotentialTargets::CreatureIDs(vec![cid_rogue()]));
+      }
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  /// A game where the rogue has an ability that dashes somewhere and then punches someone.
+  fn t_dash_game() -> (Game, AbilityID) {
+    let mut game = t_spread_out_game();
+    let abid = t_give_rogue_dash(&mut game);
+    (game, abid)
+  }
+
+  /// Give the rogue an ability to dash up to 4 meters and then strike a creature in melee range.
+  pub fn t_give_rogue_dash(game: &mut Game) -> AbilityID {
+    let abid: AbilityID = "00000000-0000-0000-0000-000000000011".parse().unwrap();
+    let action = Action::Multi(vec![
+      ("Dash".to_string(), Action::MoveActor { distance: u32cm(400) }),
+      (
+        "Strike".to_string(),
+        Action::Creature {
+          target: CreatureTarget::Melee { filter: TargetFilter::Any },
+          effect: CreatureEffect::Damage(Dice::flat(3)),
+        },
+      ),
+    ]);
+    let name = "Dash and Strike".to_string();
+    game.abilities.insert(Ability { id: abid, name, action, ..t_punch() });
+    game.classes.mutate(&classid_rogue(), |c| c.abilities.push(abid));
+    abid
+  }
+
+  #[test]
+  fn multi_actions_can_move_the_actor() {
+    let (game, abid) = t_dash_game();
+    let target = DecidedTarget::Multi(vec![
+      DecidedTarget::Point(Point3::new(100, 0, 0)),
+      DecidedTarget::Creature(cid_ranger()),
+    ]);
+    let game = t_perform(&game, GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid, target));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_rogue()).unwrap(), Point3::new(100, 0, 0));
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+  }
+
+  /// The options for a later part are worked out from where the earlier parts leave things.
+  #[test]
+  fn multi_part_target_options_follow_earlier_parts() {
+    let (game, abid) = t_dash_game();
+    let strike_options = |decided: &[DecidedTarget]| -> Vec<CreatureID> {
+      match game.get_multi_part_target_options(t_scene_id(), cid_rogue(), abid, decided).unwrap() {
+        PotentialTargets::CreatureIDs(cids) => cids,
+        x => panic!("Unexpected result: {:?}", x),
+      }
+    };
+    let sidestepped = strike_options(&[DecidedTarget::Point(Point3::new(500, 100, 0))]);
+    assert!(sidestepped.contains(&cid_cleric()));
+    assert!(!sidestepped.contains(&cid_ranger()));
+    let dashed = strike_options(&[DecidedTarget::Point(Point3::new(100, 0, 0))]);
+    assert!(dashed.contains(&cid_ranger()));
+    assert!(!dashed.contains(&cid_cleric()));
+    // Nothing actually moved.
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_rogue()).unwrap(), Point3::new(500, 0, 0));
+
+    match game.get_multi_part_target_options(t_scene_id(), cid_rogue(), abid, &[]) {
+      Ok(PotentialTargets::Points(_)) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn multi_actions_dont_move_the_actor_if_a_later_part_fails() {
+    let (game, abid) = t_dash_game();
+    let target = DecidedTarget::Multi(vec![
+      DecidedTarget::Point(Point3::new(300, 0, 0)),
+      DecidedTarget::Creature(cid_ranger()),
+    ]);
+    match perf(&game, GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid, target)) {
+      Err(GameError::CreatureOutOfRange(cid)) => assert_eq!(cid, cid_ranger()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn some_creatures_in_volume_must_be_in_volume() {
+    let game = t_spread_out_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let ranger = game.get_creature(cid_ranger()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
+    let decision = DecidedTarget::Creatures(vec![cid_rogue(), cid_ranger()]);
+    match game.resolve_creature_targets(&ranger, scene, t_some_creatures_target(), prop, decision) {
+      Err(GameError::InvalidTarget(cid)) => assert_eq!(cid, cid_ranger()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn some_creatures_in_volume_respects_maximum() {
+    let game = t_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let ranger = game.get_creature(cid_ranger()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
+    let target = t_some_creatures_target();
+    for cids in vec![vec![], vec![cid_rogue(), cid_cleric(), cid_ranger()], vec![cid_rogue(); 2]] {
+      let decision = DecidedTarget::Creatures(cids);
+      match game.resolve_creature_targets(&ranger, scene, target, prop, decision.clone()) {
+        Err(GameError::InvalidTargetForTargetSpec(spec, decided)) => {
+          assert_eq!((spec, decided), (target, decision))
+        }
+        x => panic!("Unexpected result: {:?}", x),
+      }
+    }
+  }
+
+  #[test]
+  fn some_creatures_in_volume_primary_must_be_in_range() {
+    let game = t_perform(
+      &t_game(),
+      GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), Point3::new(1500, 0, 0)),
+    );
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let ranger = game.get_creature(cid_ranger()).unwrap();
+    let prop = VolumePropagation::Unobstructed;
+    let decision = DecidedTarget::Creatures(vec![cid_rogue()]);
+    match game.resolve_creature_targets(&ranger, scene, t_some_creatures_target(), prop, decision) {
+      Err(GameError::CreatureOutOfRange(cid)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn some_creatures_in_volume_options_and_preview() {
+    let mut game = t_spread_out_game();
+    let mut ability = t_fireball();
+    ability.action = Action::Creature {
+      effect: CreatureEffect::Damage(Dice::flat(3)),
+      target: t_some_creatures_target(),
+    };
+    game.abilities.insert(ability);
+
+    let options = game.get_target_options(t_scene_id(), cid_ranger(), abid_fireball()).unwrap();
+    match options {
+      PotentialTargets::CreatureIDs(cids) => assert_eq!(
+        HashSet::<CreatureID>::from_iter(cids),
+        hashset!{cid_ranger(), cid_rogue(), cid_cleric()}
+      ),
+      x => panic!("Unexpected options: {:?}", x),
+    }
+
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let pt = Point3::new(600, 0, 0);
+    let preview = game.preview_volume_targets(scene, cid_ranger(), abid_fireball(), pt).unwrap();
+    assert_eq!(HashSet::<CreatureID>::from_iter(preview.0), hashset!{cid_rogue(), cid_cleric()});
+    assert!(!preview.1.is_empty());
+  }
+
+  #[test]
+  fn volume_abilities_must_target_points_in_range() {
+    let mut game = t_game();
+    game.classes.mutate(&classid_cleric(), |c| c.abilities.push(abid_thorn_patch()));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    // The thorn patch has a range of 10 meters, and the fireball has a range of 20 meters.
+    let cases = vec![
+      (abid_thorn_patch(), Point3::new(1100, 0, 0)),
+      (abid_fireball(), Point3::new(2100, 0, 0)),
+    ];
+    for (abid, pt) in cases {
+      match game.preview_volume_targets(scene, cid_cleric(), abid, pt) {
+        Err(GameError::PointOutOfRange(x)) => assert_eq!(x, pt),
+        x => panic!("Unexpected result: {:?}", x),
+      }
+      let target = DecidedTarget::Point(pt);
+      match perf(&game, GameCommand::ActCreature(t_scene_id(), cid_cleric(), abid, target)) {
+        Err(GameError::PointOutOfRange(x)) => assert_eq!(x, pt),
+        x => panic!("Unexpected result: {:?}", x),
+      }
+    }
+  }
+
+  #[test]
+  fn volume_target_options_are_in_range() {
+    let game = t_game();
+    let options = game.get_target_options(t_scene_id(), cid_cleric(), abid_thorn_patch()).unwrap();
+    let origin = Point3::new(0, 0, 0);
+    match options {
+      PotentialTargets::Points(pts) => {
+        assert!(pts.contains(&Point3::new(1000, 0, 0)));
+        assert!(!pts.contains(&Point3::new(1000, 1000, 0)));
+        for pt in pts {
+          assert!(game.tile_system.points_within_distance(origin, pt, u32cm(1000)));
+        }
+      }
+      x => panic!("Unexpected options: {:?}", x),
+    }
+  }
+
+  /// A game with a wall running north-south at x=2m, with the ranger on the west side of it at
+  /// 0,0,0 and the rogue on the east side at 4,0,0.
+  fn t_walled_game() -> Game {
+    let mut game = t_game();
+    game.scenes.mutate(&t_scene_id(), |scene| {
+      let wall: Vec<Point3> = (-20..20).map(|y| Point3::new(200, y * 100, 0)).collect();
+      scene.terrain.retain(|pt| !wall.contains(pt));
+    });
+    t_perform(&game, GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), Point3::new(400, 0, 0)))
+  }
+
+  #[test]
+  fn ranged_targets_must_be_visible() {
+    let game = t_walled_game();
+    let target = DecidedTarget::Creature(cid_rogue());
+    match perf(&game, GameCommand::ActCreature(t_scene_id(), cid_ranger(), abid_shoot(), target)) {
+      Err(GameError::CreatureNotVisible(cid)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    match game.get_target_options(t_scene_id(), cid_ranger(), abid_shoot()).unwrap() {
+      PotentialTargets::CreatureIDs(cids) => assert_eq!(
+        HashSet::<CreatureID>::from_iter(cids),
+        hashset!{cid_ranger(), cid_cleric()}
+      ),
+      x => panic!("Unexpected options: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn volume_targets_must_be_visible() {
+    let game = t_walled_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let pt = Point3::new(400, 0, 0);
+    match game.preview_volume_targets(scene, cid_cleric(), abid_fireball(), pt) {
+      Err(GameError::PointNotVisible(x)) => assert_eq!(x, pt),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    match game.get_target_options(t_scene_id(), cid_cleric(), abid_fireball()).unwrap() {
+      PotentialTargets::Points(pts) => {
+        assert!(pts.contains(&Point3::new(100, 0, 0)));
+        assert!(!pts.contains(&pt));
+      }
+      x => panic!("Unexpected options: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn volumes_propagate_through_terrain() {
+    let mut game = t_walled_game();
+    let pt = Point3::new(100, 0, 0);
+    let preview = |game: &Game| {
+      let scene = game.get_scene(t_scene_id()).unwrap();
+      let (cids, tiles, _) =
+        game.preview_volume_targets(scene, cid_cleric(), abid_fireball(), pt).unwrap();
+      (HashSet::<CreatureID>::from_iter(cids), tiles)
+    };
+
+    let (cids, tiles) = preview(&game);
+    assert_eq!(cids, hashset!{cid_ranger(), cid_cleric(), cid_rogue()});
+    assert!(tiles.contains(&Point3::new(400, 0, 0)));
+
+    for propagation in vec![VolumePropagation::Burst, VolumePropagation::Spreading] {
+      game.abilities.mutate(&abid_fireball(), |ab| ab.propagation = propagation);
+      let (cids, tiles) = preview(&game);
+      assert_eq!(cids, hashset!{cid_ranger(), cid_cleric()});
+      assert!(tiles.contains(&Point3::new(-900, 0, 0)));
+      assert!(!tiles.contains(&Point3::new(400, 0, 0)));
+    }
+  }
+
+  #[test]
+  fn edit_scene_terrain_level() {
+    let set_level = |game: &Game, terrain: Terrain| {
+      let level = i64cm(100);
+      t_perform(game, GameCommand::EditSceneTerrainLevel { scene_id: t_scene_id(), level, terrain })
+    };
+    let balcony_tiles = |game: &Game| {
+      let scene = game.get_scene(t_scene_id()).unwrap();
+      let mut tiles: Vec<Point3> =
+        scene.terrain.iter().filter(|pt| pt.z == i64cm(100)).cloned().collect();
+      tiles.sort();
+      tiles
+    };
+    let game = set_level(&t_game(), hashset!{Point3::new(0, 0, 100), Point3::new(100, 0, 100)});
+    assert_eq!(balcony_tiles(&game), vec![Point3::new(0, 0, 100), Point3::new(100, 0, 100)]);
+    // Editing a level replaces all of the tiles on that level, and only that level.
+    let game = set_level(&game, hashset!{Point3::new(500, 0, 100)});
+    assert_eq!(balcony_tiles(&game), vec![Point3::new(500, 0, 100)]);
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.terrain.len(), t_scene().terrain.len() + 1);
+  }
+
+  #[test]
+  fn edit_scene_terrain_level_requires_points_on_level() {
+    let cmd = GameCommand::EditSceneTerrainLevel {
+      scene_id: t_scene_id(),
+      level: i64cm(100),
+      terrain: hashset!{Point3::new(0, 0, 100), Point3::new(0, 0, 0)},
+    };
+    match perf(&t_game(), cmd) {
+      Err(GameError::PointNotOnLevel(pt)) => assert_eq!(pt, Point3::new(0, 0, 0)),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn movement_options_include_other_levels() {
+    let game = t_game();
+    let terrain = hashset!{Point3::new(100, 0, 100), Point3::new(200, 0, 100)};
+    let level = i64cm(100);
+    let cmd = GameCommand::EditSceneTerrainLevel { scene_id: t_scene_id(), level, terrain };
+    let game = t_perform(&game, cmd);
+    let balcony = Point3::new(200, 0, 100);
+
+    let options = game.get_movement_options(t_scene_id(), cid_rogue()).unwrap();
+    assert!(!options.contains(&balcony));
+
+    let stairs = hashset!{Point3::new(100, 0, 0)};
+    let game = t_perform(&game, GameCommand::EditSceneStairs { scene_id: t_scene_id(), stairs });
+    let options = game.get_movement_options(t_scene_id(), cid_rogue()).unwrap();
+    assert!(options.contains(&balcony));
+    let game = t_perform(&game, GameCommand::PathCreature(t_scene_id(), cid_rogue(), balcony));
+    assert_eq!(game.get_scene(t_scene_id()).unwrap().get_pos(cid_rogue()).unwrap(), balcony);
+  }
+
+  /// The volume conditions that abilities create are recorded in the logs, so replaying the logs
+  /// recreates them.
+  #[test]
+  fn volume_conditions_from_abilities_survive_replay() {
+    let mut game = t_game();
+    game.classes.mutate(&classid_cleric(), |c| c.abilities.push(abid_thorn_patch()));
+    let target = DecidedTarget::Point(Point3::new(500, 0, 0));
+    let cmd = GameCommand::ActCreature(t_scene_id(), cid_cleric(), abid_thorn_patch(), target);
+    let change = perf(&game, cmd).unwrap();
+    match change.logs.as_slice() {
+      [GameLog::AddVolumeCondition { .. }] => {}
+      x => panic!("Unexpected logs: {:?}", x),
+    }
+    let mut replayed = game.clone();
+    for log in &change.logs {
+      replayed = replayed.apply_log(log).unwrap();
+    }
+    let conditions = |game: &Game| game.get_scene(t_scene_id()).unwrap().volume_conditions.clone();
+    assert_eq!(conditions(&change.game).len(), 1);
+    assert_eq!(conditions(&replayed), conditions(&change.game));
+  }
+
+  #[test]
+  fn carving_and_raising_terrain() {
+    let mut game = t_walled_game();
+    let volume = Volume::Sphere(u32cm(100));
+    let target = SceneTarget::RangedVolume { volume, range: u32cm(1000) };
+    game.abilities.mutate(&abid_fireball(), |ab| {
+      ab.action = Action::SceneVolume { effect: SceneEffect::CarveTerrain, target }
+    });
+    game.abilities.mutate(&abid_heal(), |ab| {
+      ab.action = Action::SceneVolume { effect: SceneEffect::RaiseTerrain, target }
+    });
+    let wall = Point3::new(200, 0, 0);
+    let act = |abid| {
+      GameCommand::ActCreature(t_scene_id(), cid_cleric(), abid, DecidedTarget::Point(wall))
+    };
+
+    let change = perf(&game, act(abid_fireball())).unwrap();
+    let hole = hashset!{Point3::new(200, -100, 0), wall, Point3::new(200, 100, 0)};
+    assert_eq!(
+      change.logs,
+      vec![GameLog::AddSceneTerrain { scene_id: t_scene_id(), terrain: hole.clone() }]
+    );
+    let game = change.game;
+    assert!(hole.iter().all(|pt| game.get_scene(t_scene_id()).unwrap().terrain.contains(pt)));
+
+    // The rogue is standing on one of the tiles in the volume, so it stays open.
+    let set_pos = GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), Point3::new(300, 0, 0));
+    let game = t_perform(&game, set_pos);
+    let game = t_perform(&game, act(abid_heal()));
+    let terrain = &game.get_scene(t_scene_id()).unwrap().terrain;
+    assert!(!hole.iter().any(|pt| terrain.contains(pt)));
+    assert!(!terrain.contains(&Point3::new(100, 0, 0)));
+    assert!(terrain.contains(&Point3::new(300, 0, 0)));
+  }
+
+  /// Have the rogue use an ability with the given effect on the ranger, in the walled game.
+  fn t_force(effect: CreatureEffect, rogue: Point3, ranger: Point3) -> ChangedGame {
+    let mut game = t_walled_game();
+    game.abilities.mutate(&abid_punch(), |ab| {
+      let target = CreatureTarget::Range { distance: u32cm(1000), filter: TargetFilter::Any };
+      ab.action = Action::Creature { target, effect }
+    });
+    let game = t_perform(&game, GameCommand::SetCreaturePos(t_scene_id(), cid_rogue(), rogue));
+    let game = t_perform(&game, GameCommand::SetCreaturePos(t_scene_id(), cid_ranger(), ranger));
+    let target = DecidedTarget::Creature(cid_ranger());
+    perf(&game, GameCommand::ActCreature(t_scene_id(), cid_rogue(), abid_punch(), target)).unwrap()
+  }
+
+  fn t_ranger_pos(change: &ChangedGame) -> Point3 {
+    change.game.get_scene(t_scene_id()).unwrap().get_pos(cid_ranger()).unwrap()
+  }
+
+  #[test]
+  fn push_moves_target_away() {
+    let push = CreatureEffect::Push(u32cm(300));
+    let change = t_force(push, Point3::new(500, 0, 0), Point3::new(600, 0, 0));
+    let path = vec![Point3::new(700, 0, 0), Point3::new(800, 0, 0), Point3::new(900, 0, 0)];
+    assert_eq!(change.logs, vec![GameLog::PathCreature(t_scene_id(), cid_ranger(), path)]);
+    assert_eq!(t_ranger_pos(&change), Point3::new(900, 0, 0));
+  }
+
+  #[test]
+  fn knockback_into_a_wall_deals_damage() {
+    let knockback = CreatureEffect::Knockback { distance: u32cm(300), impact: Dice::flat(2) };
+    let change = t_force(knockback, Point3::new(500, 0, 0), Point3::new(300, 0, 0));
+    assert_eq!(t_ranger_pos(&change), Point3::new(300, 0, 0));
+    let ranger = change.game.get_creature(cid_ranger()).unwrap();
+    assert_eq!(ranger.creature.cur_health, HP(8));
+  }
+
+  #[test]
+  fn pull_stops_next_to_actor() {
+    let pull = CreatureEffect::Pull(u32cm(1000));
+    let change = t_force(pull, Point3::new(400, 0, 0), Point3::new(900, 0, 0));
+    assert_eq!(t_ranger_pos(&change), Point3::new(500, 0, 0));
+  }
+
+  #[test]
+  fn teleport_passes_through_walls() {
+    let teleport = CreatureEffect::Teleport(u32cm(200));
+    let change = t_force(teleport, Point3::new(500, 0, 0), Point3::new(300, 0, 0));
+    let dest = Point3::new(100, 0, 0);
+    assert_eq!(change.logs, vec![GameLog::SetCreaturePos(t_scene_id(), cid_ranger(), dest)]);
+    assert_eq!(t_ranger_pos(&change), dest);
+  }
+
+  fn t_cells_game(pt: Point3, cell: TerrainCell) -> Game {
+    let terrain_cells = hashmap!{pt => cell};
+    let cmd = GameCommand::EditSceneTerrainCells { scene_id: t_scene_id(), terrain_cells };
+    t_perform(&t_walled_game(), cmd)
+  }
+
+  #[test]
+  fn doors_open_and_close() {
+    let door = Point3::new(200, 0, 0);
+    let beyond = Point3::new(300, 0, 0);
+    let game = t_cells_game(door, TerrainCell::Door { open: false });
+    match perf(&game, GameCommand::PathCreature(t_scene_id(), cid_ranger(), beyond)) {
+      Err(GameError::NoPathFound) => {}
+      x => panic!("Unexpected result: {:?}", x),
+    }
+
+    let game = t_perform(&game, GameCommand::OpenDoor(t_scene_id(), door));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.terrain_cells[&door], TerrainCell::Door { open: true });
+    let game = t_perform(&game, GameCommand::PathCreature(t_scene_id(), cid_ranger(), beyond));
+    assert_eq!(game.get_scene(t_scene_id()).unwrap().get_pos(cid_ranger()).unwrap(), beyond);
+
+    let game = t_perform(&game, GameCommand::CloseDoor(t_scene_id(), door));
+    assert!(!game.get_scene(t_scene_id()).unwrap().terrain.contains(&door));
+  }
+
+  #[test]
+  fn occupied_tiles_cannot_be_closed_off() {
+    let door = Point3::new(200, 0, 0);
+    let game = t_cells_game(door, TerrainCell::Door { open: true });
+    let game = t_perform(&game, GameCommand::SetCreaturePos(t_scene_id(), cid_ranger(), door));
+    match perf(&game, GameCommand::CloseDoor(t_scene_id(), door)) {
+      Err(GameError::TileOccupied(pt)) => assert_eq!(pt, door),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    let terrain_cells = hashmap!{door => TerrainCell::DestructibleWall};
+    let cmd = GameCommand::EditSceneTerrainCells { scene_id: t_scene_id(), terrain_cells };
+    match perf(&game, cmd) {
+      Err(GameError::TileOccupied(pt)) => assert_eq!(pt, door),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn removing_a_closed_door_opens_its_tile() {
+    let door = Point3::new(200, 0, 0);
+    let game = t_cells_game(door, TerrainCell::Door { open: false });
+    assert!(!game.get_scene(t_scene_id()).unwrap().terrain.contains(&door));
+    let terrain_cells = HashMap::new();
+    let cmd = GameCommand::EditSceneTerrainCells { scene_id: t_scene_id(), terrain_cells };
+    let game = t_perform(&game, cmd);
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert!(scene.terrain_cells.is_empty());
+    assert!(scene.terrain.contains(&door));
+    let beyond = Point3::new(300, 0, 0);
+    let game = t_perform(&game, GameCommand::PathCreature(t_scene_id(), cid_ranger(), beyond));
+    assert_eq!(game.get_scene(t_scene_id()).unwrap().get_pos(cid_ranger()).unwrap(), beyond);
+  }
+
+  /// Replacing the terrain gets rid of the cells on tiles whose openness it changes.
+  #[test]
+  fn editing_terrain_drops_cells_it_contradicts() {
+    let door = Point3::new(200, 0, 0);
+    let game = t_cells_game(door, TerrainCell::Door { open: false });
+    let mut terrain = game.get_scene(t_scene_id()).unwrap().terrain.clone();
+    terrain.insert(door);
+    let game = t_perform(&game, GameCommand::EditSceneTerrain { scene_id: t_scene_id(), terrain });
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert!(scene.terrain_cells.is_empty());
+    assert!(scene.terrain.contains(&door));
+  }
+
+  #[test]
+  fn destroyed_walls_become_open_terrain() {
+    let wall = Point3::new(200, 0, 0);
+    let game = t_cells_game(wall, TerrainCell::DestructibleWall);
+    match perf(&game, GameCommand::OpenDoor(t_scene_id(), wall)) {
+      Err(GameError::NoDoor(pt)) => assert_eq!(pt, wall),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+    let game = t_perform(&game, GameCommand::DestroyWall(t_scene_id(), wall));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert!(scene.terrain.contains(&wall));
+    assert_eq!(scene.terrain_cells.get(&wall), None);
+    match perf(&game, GameCommand::DestroyWall(t_scene_id(), wall)) {
+      Err(GameError::NoDestructibleWall(pt)) => assert_eq!(pt, wall),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn out_of_combat_abilities_can_be_charged() {
+    let mut game = t_game();
+    game.abilities.mutate(&abid_punch(), |ab| {
+      ab.cost = Energy(4);
+      ab.cooldown = 1;
+    });
+    let punch = GameCommand::ActCreature(
+      t_scene_id(),
+      cid_rogue(),
+      abid_punch(),
+      DecidedTarget::Creature(cid_ranger()),
+    );
+    let game = t_perform(&game, punch.clone());
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    assert_eq!(rogue.creature.cur_energy, Energy(10));
+    assert_eq!(rogue.ability_cooldown(abid_punch()), 0);
+
+    let game = t_perform(&game, GameCommand::SetOutOfCombatCost(OutOfCombatCost::Charged));
+    let game = t_perform(&game, punch.clone());
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    assert_eq!(rogue.creature.cur_energy, Energy(6));
+    assert_eq!(rogue.ability_cooldown(abid_punch()), 1);
+    match perf(&game, punch) {
+      Err(GameError::AbilityOnCooldown(cid, _, 1)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn resting_restores_resources() {
+    let mut game = t_game();
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.cur_energy = Energy(3);
+      c.cur_health = HP(4);
+      c.cooldowns.insert(abid_punch(), 2);
+    });
+    let game = t_perform(&game, GameCommand::Rest(RestKind::Short, vec![cid_rogue()]));
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    assert_eq!(rogue.creature.cur_energy, Energy(10));
+    assert_eq!(rogue.creature.cur_health, HP(4));
+    assert_eq!(rogue.ability_cooldown(abid_punch()), 0);
+
+    let game = t_perform(&game, GameCommand::Rest(RestKind::Long, vec![cid_rogue()]));
+    assert_eq!(game.get_creature(cid_rogue()).unwrap().creature.cur_health, HP(10));
+
+    let game = t_start_combat(&game, vec![cid_rogue()]);
+    match perf(&game, GameCommand::Rest(RestKind::Long, vec![cid_rogue()])) {
+      Err(GameError::AlreadyInCombat(cid)) => assert_eq!(cid, cid_rogue()),
+      x => panic!("Unexpected result: {:?}", x),
+    }
+  }
+
+  #[test]
+  fn visible_tiles() {
+    let game = t_walled_game();
+    let tiles = game.get_visible_tiles(t_scene_id(), cid_ranger()).unwrap();
+    assert!(tiles.contains(&Point3::new(100, 500, 0)));
+    assert!(!tiles.contains(&Point3::new(200, 0, 0)));
+    assert!(!tiles.contains(&Point3::new(300, 0, 0)));
+  }
+
   #[test]
   fn preview_volume_targets_shows_creatures_for_scene_volume_actions() {
     // When previewing volume targets for an action that affects a Scene Volume, the creatures
@@ -1594,6 +3180,16 @@ pub mod test {
       game.preview_volume_targets(scene, cleric, ability_id, Point3::new(0, 0, 0)).unwrap();
     let expected = hashset!{cid_cleric(), cid_ranger(), cid_rogue()};
     assert_eq!(HashSet::from_iter(preview.0), expected);
+    assert_eq!(preview.2, None);
+  }
+
+  #[test]
+  fn preview_volume_targets_includes_damage_distribution() {
+    let game = t_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let pt = Point3::new(0, 0, 0);
+    let preview = game.preview_volume_targets(scene, cid_cleric(), abid_fireball(), pt).unwrap();
+    assert_eq!(preview.2, Dice::flat(3).distribution());
   }
 
   #[test]
@@ -1659,6 +3255,8 @@ pub mod test {
       abilities: vec![],
       conditions: vec![],
       color: "blue".to_string(),
+      opportunity_attack: None,
+      energy_regen: Energy(0),
     };
     module.classes.insert(class);
     module.link_folder_item(&FolderPath::root(), &FolderItemID::ClassID(classid)).unwrap();
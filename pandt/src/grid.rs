@@ -1,7 +1,7 @@
 use std::cmp;
-use std::collections::HashSet;
+use std::f64::consts::FRAC_PI_2;
+use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
-use bresenham;
 use odds::vec::VecExt;
 
 use nalgebra as na;
@@ -10,25 +10,26 @@ use ncollide::shape;
 use ncollide::shape::Cuboid;
 use ncollide::query::PointQuery;
 use ncollide::world;
-use num::range;
 use num_traits::Signed;
 use uom::si::length::{centimeter, meter};
 
 use crate::types::{up_length, CollisionData, CollisionWorld, ConditionID, Creature, Point3, Terrain,
-            TileSystem, Volume, VolumeCondition, i64cm, i64meter, u32cm, u32units};
+            TerrainCell, TileSystem, Volume, VolumeCondition, VolumePropagation, AABB, i64cm,
+            i64meter, i64units, u32cm, u32units};
 
-// unimplemented!: "burst"-style AoE effects, and "wrap-around-corner" AoE effects.
-// This needs to be implemented for both Spheres and Circles (or VerticalCylinder?)
+// Area-of-effect volumes can interact with terrain in a few ways, as described by
+// `VolumePropagation`.
 //
 // Grenades should be "burst"-style spheres. These basically "cast" the effect outward from the
-// center, and are blocked by any solid terrain. This could be implemented by raycasting from
-// the origin to every point within a particular radius of the origin.
+// center, and are blocked by any solid terrain. This is implemented by checking line of sight from
+// the origin to every point within the volume.
 //
-// Abilities like D&D's "fireball" and P&T's "thorn patch" should be "wrap-around-corner" AoE
-// effects: Fireball is a sphere and Thorn Patch is a Circle or VerticalCylinder{height=1}.
+// Abilities like D&D's "fireball" and P&T's "thorn patch" should be "wrap-around-corner"
+// ("spreading") AoE effects: Fireball is a sphere and Thorn Patch is a Circle or
+// VerticalCylinder{height=1}.
 //
-// These "crawl" out from the origin point and can go anywhere within the radius that has a valid.
-// path. This could be implemented exactly the same way we implement potential walk-targets.
+// These "crawl" out from the origin point and can go anywhere within the radius that has a valid
+// path. This is implemented exactly the same way we implement potential walk-targets.
 // However, for 3d shapes this might get expensive...
 
 // I got curious about how to implement this in integer math.
@@ -65,6 +66,19 @@ pub fn line_through_point(origin: Point3, clicked: Point3, length: u32units::Len
   Volume::Line { vector: na_vector_to_point3(new_vec) }
 }
 
+/// Find the tile that's `length` away from `origin`, horizontally, in the direction of `toward`.
+/// If the two points are in the same column, there's no direction to go, so this is `origin`.
+pub fn point_in_direction(origin: Point3, toward: Point3, length: u32units::Length) -> Point3 {
+  if origin.x == toward.x && origin.y == toward.y {
+    return origin;
+  }
+  let mut navec = na_vector(point3_difference(toward, origin));
+  navec[2] = 0.0;
+  let offset = navec.normalize() * (f64::from(length.get(centimeter)) / 100.0);
+  let (x, y) = (offset[0].round() as i64, offset[1].round() as i64);
+  Point3::from_quantities(origin.x + i64meter(x), origin.y + i64meter(y), origin.z)
+}
+
 /// Get the vector difference between two points, i.e., the offset of pt2 from pt1.
 /// This returns a plain old Point3 to represent the delta... Is that the best thing to do?
 pub fn point3_difference(pt1: Point3, pt2: Point3) -> Point3 {
@@ -77,10 +91,115 @@ pub fn point3_add_vec(pt: Point3, diff: Point3) -> Point3 {
 
 fn is_open(terrain: &Terrain, pt: Point3) -> bool { terrain.contains(&pt) }
 
+/// Check whether there's an unobstructed line of sight from one point to another. Every tile
+/// strictly between the two points must be open, and the line can't squeeze diagonally between two
+/// blocked tiles.
+pub fn line_of_sight(terrain: &Terrain, from: Point3, to: Point3) -> bool {
+  line_is_clear(|pt| is_open(terrain, pt), from, to)
+}
+
+/// Check whether any tile of one footprint can see any tile of another.
+pub fn footprints_visible(terrain: &Terrain, from: &[Point3], to: &[Point3]) -> bool {
+  from.iter().any(|f| to.iter().any(|t| line_of_sight(terrain, *f, *t)))
+}
+
+/// Filter some points down to the ones which are visible from a point.
+pub fn filter_visible(terrain: &Terrain, from: Point3, points: Vec<Point3>) -> Vec<Point3> {
+  points.into_iter().filter(|pt| line_of_sight(terrain, from, *pt)).collect()
+}
+
+fn line_is_clear<F: Fn(Point3) -> bool>(is_open: F, from: Point3, to: Point3) -> bool {
+  let tiles = tiles_on_line(tile_coords(from), tile_coords(to));
+  for (idx, pair) in tiles.windows(2).enumerate() {
+    let (prev, next) = (pair[0], pair[1]);
+    if prev.0 != next.0
+      && prev.1 != next.1
+      && !is_open(tile_point((next.0, prev.1, prev.2)))
+      && !is_open(tile_point((prev.0, next.1, prev.2)))
+    {
+      return false;
+    }
+    let is_destination = idx + 2 == tiles.len();
+    if !is_destination && !is_open(tile_point(next)) {
+      return false;
+    }
+  }
+  true
+}
+
+type TileCoords = (i64, i64, i64);
+
+fn tile_coords(pt: Point3) -> TileCoords { (pt.x.get(meter), pt.y.get(meter), pt.z.get(meter)) }
+
+fn tile_point(coords: TileCoords) -> Point3 {
+  Point3::new(coords.0 * 100, coords.1 * 100, coords.2 * 100)
+}
+
+/// Find the tiles on a straight line between two tiles, including both ends. This steps along the
+/// axis with the largest difference, rounding the other coordinates to the nearest tile.
+fn tiles_on_line(start: TileCoords, end: TileCoords) -> Vec<TileCoords> {
+  let delta = (end.0 - start.0, end.1 - start.1, end.2 - start.2);
+  let steps = cmp::max(delta.0.abs(), cmp::max(delta.1.abs(), delta.2.abs()));
+  if steps == 0 {
+    return vec![start];
+  }
+  (0..steps + 1)
+    .map(|i| {
+      let offset = |d: i64| round_div(d * i, steps);
+      (start.0 + offset(delta.0), start.1 + offset(delta.1), start.2 + offset(delta.2))
+    })
+    .collect()
+}
+
+/// Divide `n` by the positive number `d`, rounding down.
+fn floor_div(n: i64, d: i64) -> i64 {
+  if n >= 0 {
+    n / d
+  } else {
+    -((d - 1 - n) / d)
+  }
+}
+
+/// Divide `n` by the positive number `d`, rounding to the nearest integer (away from zero on ties).
+fn round_div(n: i64, d: i64) -> i64 {
+  if n >= 0 {
+    (2 * n + d) / (2 * d)
+  } else {
+    -((d - 2 * n) / (2 * d))
+  }
+}
+
+/// Everything about a scene's terrain that affects movement: the open tiles, the stairs between
+/// levels, and any special cells which make movement more expensive.
+#[derive(Clone, Copy)]
+pub struct TerrainMap<'a> {
+  pub open: &'a Terrain,
+  pub stairs: Option<&'a HashSet<Point3>>,
+  pub cells: Option<&'a HashMap<Point3, TerrainCell>>,
+}
+
+impl<'a> TerrainMap<'a> {
+  /// A map of open terrain with no stairs or special cells.
+  pub fn plain(open: &'a Terrain) -> TerrainMap<'a> {
+    TerrainMap { open, stairs: None, cells: None }
+  }
+
+  /// How much it costs to enter a tile, given the cost of entering it normally.
+  fn cost_to_enter(&self, pt: Point3, base: u32units::Length) -> u32units::Length {
+    match self.cells.and_then(|cells| cells.get(&pt)) {
+      Some(cell) => base * cell.cost_multiplier(),
+      None => base,
+    }
+  }
+}
+
 impl TileSystem {
   /// Get the distance between two points, considering the system being used.
   /// In DnD, an angular distance is "equivalent" to a horizontal/vertical distance.
   /// i.e., The distance from 0,0 to 1,1 is sqrt(2) in Realistic and 1.0 in DnD.
+  /// Now that scenes have levels, both systems count vertical distance too, so that a creature on a
+  /// balcony isn't in melee range of the one standing below it. In DnD the distance is the largest
+  /// difference along any of the three axes.
   pub fn point3_distance(&self, pos1: Point3, pos2: Point3) -> u32units::Length {
     match *self {
       TileSystem::Realistic => {
@@ -93,7 +212,8 @@ impl TileSystem {
       TileSystem::DnD => {
         let xdiff = (pos1.x - pos2.x).abs();
         let ydiff = (pos1.y - pos2.y).abs();
-        u32cm(cmp::max(xdiff, ydiff).get(centimeter) as u32)
+        let zdiff = (pos1.z - pos2.z).abs();
+        u32cm(cmp::max(xdiff, cmp::max(ydiff, zdiff)).get(centimeter) as u32)
       }
     }
   }
@@ -103,67 +223,112 @@ impl TileSystem {
     self.point3_distance(c1, c2) <= d
   }
 
-  /// Garbage Function
+  /// Find the items positioned within a volume placed at a point. Items are single points; things
+  /// that take up more than one tile can be checked by passing in an item for each of their tiles.
   pub fn items_within_volume<I: Clone + Eq + Hash>(
     &self, volume: Volume, pt: Point3, items: &HashMap<I, Point3>
   ) -> Vec<I> {
-    // TODO: unimplemented! this doesn't support non-1x1 items
-    // TODO: this function is really dumb, and instead should probably work on a HashSet of Point3s,
-    // or maybe a HashMap<Point3, I>. And it should make use of points_in_volume.
     let mut results = vec![];
     match volume {
-      Volume::Sphere(radius) => for (item, item_pos) in items {
-        if self.point3_distance(pt, *item_pos) <= radius {
-          results.push(item.clone());
-        }
-      },
-      Volume::AABB(_) => unimplemented!("unimplemented: items_within_volume for AABB"),
-      Volume::Line { vector } => {
-        let dest = point3_add_vec(pt, vector);
-        let start = (pt.x.get(meter) as isize, pt.y.get(meter) as isize);
-        let end = (dest.x.get(meter) as isize, dest.y.get(meter) as isize);
-        let line_pts: HashSet<Point3> = HashSet::from_iter(
-          bresenham::Bresenham::new(start, end)
-            .map(|(x, y)| Point3::new(x as i64 * 100, y as i64 * 100, 0)),
-        );
+      // Lines are made of specific tiles, so find those once instead of once per item.
+      Volume::Line { .. } => {
+        let line_pts: HashSet<Point3> = HashSet::from_iter(self.points_in_volume(volume, pt));
         for (item, item_pos) in items {
           if line_pts.contains(item_pos) {
             results.push(item.clone());
           }
         }
       }
-      Volume::VerticalCylinder { .. } => unimplemented!("items_within_volume for VerticalCylinder"),
+      _ => for (item, item_pos) in items {
+        if self.volume_contains(volume, pt, *item_pos) {
+          results.push(item.clone());
+        }
+      },
     }
     results
   }
 
+  /// Get the tiles that something of the given size takes up when it's at a point.
+  /// Everything takes up at least the tile it's at.
+  pub fn footprint(&self, pt: Point3, size: AABB) -> Vec<Point3> {
+    let tiles = self.points_in_volume(Volume::AABB(size), pt);
+    if tiles.is_empty() {
+      vec![pt]
+    } else {
+      tiles
+    }
+  }
+
+  /// Check whether any tile of one footprint is within some distance of any tile of another.
+  pub fn footprints_within_distance(
+    &self, tiles1: &[Point3], tiles2: &[Point3], d: u32units::Length
+  ) -> bool {
+    tiles1.iter().any(|t1| tiles2.iter().any(|t2| self.points_within_distance(*t1, *t2, d)))
+  }
+
+  /// Find the items within a volume, taking into account how the volume propagates through the
+  /// terrain from the point it's placed at.
+  pub fn items_affected_by_volume<I: Clone + Eq + Hash>(
+    &self, volume: Volume, pt: Point3, propagation: VolumePropagation, terrain: &Terrain,
+    items: &HashMap<I, Point3>,
+  ) -> Vec<I> {
+    let mut results = self.items_within_volume(volume, pt, items);
+    match propagation {
+      VolumePropagation::Unobstructed => {}
+      VolumePropagation::Burst => results.retain(|item| line_of_sight(terrain, pt, items[item])),
+      VolumePropagation::Spreading => {
+        let reachable = self.volume_spread(volume, pt, terrain);
+        results.retain(|item| reachable.contains(&items[item]));
+      }
+    }
+    results
+  }
+
+  /// Find the open points that a spreading volume can crawl to from its origin, by pathing through
+  /// the terrain up to the volume's reach.
+  fn volume_spread(&self, volume: Volume, pt: Point3, terrain: &Terrain) -> HashSet<Point3> {
+    let tile = Volume::AABB(AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100) });
+    let reach = self.volume_reach(volume);
+    let accessible = self.get_all_accessible(pt, TerrainMap::plain(terrain), tile, reach);
+    let mut points: HashSet<Point3> = HashSet::from_iter(accessible);
+    if is_open(terrain, pt) {
+      points.insert(pt);
+    }
+    points
+  }
+
+  /// The furthest distance from its origin that any part of a volume extends.
+  fn volume_reach(&self, volume: Volume) -> u32units::Length {
+    let origin = Point3::new(0, 0, 0);
+    match volume {
+      Volume::Sphere(radius) => radius,
+      Volume::VerticalCylinder { radius, .. } => radius,
+      Volume::Line { vector } => self.point3_distance(origin, vector),
+      Volume::AABB(aabb) => self.point3_distance(
+        origin,
+        Point3::from_quantities(up_length(aabb.x), up_length(aabb.y), up_length(aabb.z)),
+      ),
+    }
+  }
+
+  /// Find the open points within a cube around a point, on every level.
   pub fn open_points_in_range(
     &self, start: Point3, terrain: &Terrain, speed: u32units::Length
   ) -> Vec<Point3> {
     let speed = up_length(speed);
-    let lowx = start.x - speed;
-    // why am I adding 1 to these highs???
-    let highx = start.x + speed + i64meter(1);
-    let lowy = start.y - speed;
-    let highy = start.y + speed + i64meter(1);
-    let mut open = vec![];
-    for x_meters in range(lowx.get(meter), highx.get(meter)) {
-      for y_meters in range(lowy.get(meter), highy.get(meter)) {
-        let end_point = Point3::new(x_meters * 100, y_meters * 100, 0);
-        if !is_open(terrain, end_point) {
-          continue;
-        }
-        open.push(end_point);
-      }
-    }
-    open
+    let in_range = |a: i64units::Length, b: i64units::Length| (a - b).abs() <= speed;
+    terrain
+      .iter()
+      .filter(|pt| in_range(pt.x, start.x) && in_range(pt.y, start.y) && in_range(pt.z, start.z))
+      .cloned()
+      .collect()
   }
 
   /// Get the set of points which can be pathed to from some point.
   pub fn get_all_accessible(
-    &self, start: Point3, terrain: &Terrain, volume: Volume, speed: u32units::Length
+    &self, start: Point3, map: TerrainMap, volume: Volume, speed: u32units::Length
   ) -> Vec<Point3> {
-    let points_to_check = self.open_points_in_range(start, terrain, speed);
+    let points_to_check = self.open_points_in_range(start, map.open, speed);
     let mut success_fns: Vec<Box<Fn(&Point3) -> bool>> = vec![];
     for pt in points_to_check {
       if pt != start {
@@ -173,7 +338,7 @@ impl TileSystem {
     let mut final_points = vec![];
     let path_result = astar_multi(
       &start,
-      |n| self.point3_neighbors(terrain, volume, *n),
+      |n| self.point3_neighbors(map, volume, *n),
       |n| self.point3_distance(start, *n),
       speed,
       success_fns,
@@ -192,13 +357,13 @@ impl TileSystem {
   /// Find a path from some start point to some destination point. If one can be found, a Vec of
   /// points on the way to the destination is returned, along with the total length of that path.
   pub fn find_path(
-    &self, start: Point3, speed: u32units::Length, terrain: &Terrain, volume: Volume,
+    &self, start: Point3, speed: u32units::Length, map: TerrainMap, volume: Volume,
     destination: Point3,
   ) -> Option<(Vec<Point3>, u32units::Length)> {
     let success = Box::new(move |n: &Point3| *n == destination);
     let result = astar_multi(
       &start,
-      |n| self.point3_neighbors(terrain, volume, *n),
+      |n| self.point3_neighbors(map, volume, *n),
       |n| self.point3_distance(start, *n),
       speed,
       vec![success],
@@ -210,41 +375,136 @@ impl TileSystem {
     }
   }
 
+  /// Find the total cost of walking along a path of neighboring points, or None if some step of the
+  /// path can't be taken.
+  pub fn path_cost(
+    &self, map: TerrainMap, volume: Volume, path: &[Point3]
+  ) -> Option<u32units::Length> {
+    let mut total = u32cm(0);
+    for step in path.windows(2) {
+      let neighbors = self.point3_neighbors(map, volume, step[0]);
+      let &(_, cost) = neighbors.iter().find(|&&(pt, _)| pt == step[1])?;
+      total = total + cost;
+    }
+    Some(total)
+  }
+
   /// Determine which points a volume occupies.
   /// The way a volume fits at a point is specific to the volume type.
   /// AABB: top-left
   /// Sphere: center
   /// Line: origin
   /// VerticalCylinder: center of base
-  // TODO FIXME XXX: Implement for more volume types.
-  // TODO: Should this use ncollide?
-  fn points_in_volume(&self, volume: Volume, pt: Point3) -> Vec<Point3> {
+  /// The far edges of AABBs and the tops of cylinders are exclusive, as is the end of a line.
+  pub fn points_in_volume(&self, volume: Volume, pt: Point3) -> Vec<Point3> {
     match volume {
-      Volume::Sphere(..) => {
-        unimplemented!("unimplemented: points_in_volume for Sphere");
+      Volume::Line { vector } => {
+        let end = point3_add_vec(pt, vector);
+        let mut tiles = tiles_on_line(tile_coords(pt), tile_coords(end));
+        tiles.pop();
+        tiles.into_iter().map(tile_point).collect()
       }
-      // sadly uom doesn't implement Step for Quantity
+      _ => {
+        let (low, high) = self.volume_bounds(volume, pt);
+        // sadly uom doesn't implement Step for Quantity
+        let tiles = |low: i64units::Length, high: i64units::Length| {
+          floor_div(low.get(centimeter), 100)..(floor_div(high.get(centimeter), 100) + 1)
+        };
+        let mut points = vec![];
+        for x in tiles(low.x, high.x) {
+          for y in tiles(low.y, high.y) {
+            for z in tiles(low.z, high.z) {
+              let tile = tile_point((x, y, z));
+              if self.volume_contains(volume, pt, tile) {
+                points.push(tile);
+              }
+            }
+          }
+        }
+        points
+      }
+    }
+  }
+
+  /// Check whether a point is inside of a volume placed at some other point.
+  fn volume_contains(&self, volume: Volume, origin: Point3, pt: Point3) -> bool {
+    match volume {
+      Volume::Sphere(radius) => self.point3_distance(origin, pt) <= radius,
       Volume::AABB(aabb) => {
-        let max = aabb.get_max(pt);
-        (pt.x.get(meter)..(max.x.get(meter)))
-          .flat_map(|x| {
-            (pt.y.get(meter)..(max.y.get(meter))).flat_map(move |y| {
-              (pt.z.get(meter)..(max.z.get(meter)))
-                .map(move |z| Point3::new(x * 100, y * 100, z * 100))
-            })
-          })
-          .collect()
+        let max = aabb.get_max(origin);
+        origin.x <= pt.x && pt.x < max.x && origin.y <= pt.y && pt.y < max.y && origin.z <= pt.z
+          && pt.z < max.z
       }
-      Volume::Line { .. } => unimplemented!("points_in_volume for Line"),
-      Volume::VerticalCylinder { .. } => {
-        unimplemented!("unimplemented: points_in_volume for VerticalCylinder")
+      Volume::VerticalCylinder { radius, height } => {
+        let level = Point3::from_quantities(pt.x, pt.y, origin.z);
+        self.point3_distance(origin, level) <= radius && origin.z <= pt.z
+          && pt.z < origin.z + up_length(height)
       }
+      Volume::Line { .. } => self.points_in_volume(volume, origin).contains(&pt),
     }
   }
 
+  /// Get the lowest and highest corners of a box that contains the entire volume.
+  fn volume_bounds(&self, volume: Volume, pt: Point3) -> (Point3, Point3) {
+    let offset = |x: i64units::Length, y: i64units::Length, z: i64units::Length| {
+      Point3::from_quantities(pt.x + x, pt.y + y, pt.z + z)
+    };
+    let zero = i64cm(0);
+    match volume {
+      Volume::Sphere(radius) => {
+        let r = up_length(radius);
+        (offset(-r, -r, -r), offset(r, r, r))
+      }
+      Volume::AABB(aabb) => (pt, aabb.get_max(pt)),
+      Volume::VerticalCylinder { radius, height } => {
+        let r = up_length(radius);
+        (offset(-r, -r, zero), offset(r, r, up_length(height)))
+      }
+      Volume::Line { vector } => {
+        let end = point3_add_vec(pt, vector);
+        let low = Point3::from_quantities(
+          cmp::min(pt.x, end.x),
+          cmp::min(pt.y, end.y),
+          cmp::min(pt.z, end.z),
+        );
+        let high = Point3::from_quantities(
+          cmp::max(pt.x, end.x),
+          cmp::max(pt.y, end.y),
+          cmp::max(pt.z, end.z),
+        );
+        (low, high)
+      }
+    }
+  }
+
+  /// Find the points a volume passes through when it's forced in a straight line from `start` to
+  /// `dest`, ignoring movement costs. The volume stops short if it runs into terrain it doesn't
+  /// fit in. The start point isn't included.
+  pub fn forced_path(
+    &self, terrain: &Terrain, volume: Volume, start: Point3, dest: Point3
+  ) -> Vec<Point3> {
+    tiles_on_line(tile_coords(start), tile_coords(dest))
+      .into_iter()
+      .skip(1)
+      .map(tile_point)
+      .take_while(|pt| self.volume_fits_at_point(volume, terrain, *pt))
+      .collect()
+  }
+
+  /// Find the first point on a straight line from `from` to `to` (including both ends) where a
+  /// volume fits, without caring what's in between.
+  pub fn first_fit_on_line(
+    &self, terrain: &Terrain, volume: Volume, from: Point3, to: Point3
+  ) -> Option<Point3> {
+    tiles_on_line(tile_coords(from), tile_coords(to))
+      .into_iter()
+      .map(tile_point)
+      .find(|pt| self.volume_fits_at_point(volume, terrain, *pt))
+  }
+
   /// Determine whether a volume will not collide *with terrain* if it is placed at a point.
   /// Note that this doesn't consider other creatures or other map objects.
-  fn volume_fits_at_point(&self, volume: Volume, terrain: &Terrain, pt: Point3) -> bool {
+  pub fn volume_fits_at_point(&self, volume: Volume, terrain: &Terrain, pt: Point3) -> bool {
     for pt in self.points_in_volume(volume, pt) {
       if !terrain.contains(&pt) {
         return false;
@@ -254,9 +514,12 @@ impl TileSystem {
   }
 
   /// Find neighbors of the given point that the given volume can fit in, given the terrain.
+  /// Neighbors are usually on the same level, but stairs lead to the tile directly above them.
+  /// The cost of moving to a neighbor is scaled by any terrain cell it has.
   fn point3_neighbors(
-    &self, terrain: &Terrain, volume: Volume, pt: Point3
+    &self, map: TerrainMap, volume: Volume, pt: Point3
   ) -> Vec<(Point3, u32units::Length)> {
+    let terrain = map.open;
     let diagonal_distance = match *self {
       TileSystem::Realistic => u32cm(141),
       TileSystem::DnD => u32cm(100),
@@ -286,10 +549,20 @@ impl TileSystem {
           {
             continue;
           }
-          results.push((neighbor, cost));
+          results.push((neighbor, map.cost_to_enter(neighbor, cost)));
         }
       }
     }
+    let above = Point3::from_quantities(pt.x, pt.y, pt.z + i64meter(1));
+    let below = Point3::from_quantities(pt.x, pt.y, pt.z - i64meter(1));
+    for &(neighbor, stair) in [(above, pt), (below, below)].into_iter() {
+      if map.stairs.map_or(false, |stairs| stairs.contains(&stair))
+        && is_open(terrain, neighbor)
+        && self.volume_fits_at_point(volume, terrain, neighbor)
+      {
+        results.push((neighbor, map.cost_to_enter(neighbor, straight_distance)));
+      }
+    }
     results
   }
 }
@@ -318,11 +591,11 @@ where
   let mut idx = 0;
 
   for (creature, pos) in creatures {
-    let volume = Volume::AABB(creature.size);
+    let (iso, shape) = volume_to_na_shape(Volume::AABB(creature.size), pos);
     world.deferred_add(
       idx,
-      na_iso(pos),
-      volume_to_na_shape(volume),
+      iso,
+      shape,
       creature_group,
       query,
       CollisionData::Creature(creature.id),
@@ -331,10 +604,11 @@ where
   }
 
   for (condition_id, volume_condition) in volume_conditions {
+    let (iso, shape) = volume_to_na_shape(volume_condition.volume, volume_condition.point);
     world.deferred_add(
       idx,
-      na_iso(volume_condition.point),
-      volume_to_na_shape(volume_condition.volume),
+      iso,
+      shape,
       condition_group,
       query,
       CollisionData::ConditionVolume(condition_id),
@@ -362,16 +636,29 @@ where
   results
 }
 
-fn volume_to_na_shape(volume: Volume) -> shape::ShapeHandle3<f64> {
+/// Get an ncollide shape for a volume placed at a point, along with the position of that shape.
+fn volume_to_na_shape(volume: Volume, pt: Point3) -> (Isometry3<f64>, shape::ShapeHandle3<f64>) {
+  let meters = |l: u32units::Length| f64::from(l.get(centimeter)) / 100.0;
   match volume {
-    Volume::Sphere(r) => shape::ShapeHandle3::new(shape::Ball::new(r.get(centimeter) as f64)),
-    Volume::AABB(aabb) => shape::ShapeHandle3::new(shape::Cuboid::new(Vector3::new(
-      (f64::from(aabb.x.get(centimeter))) / 2.0,
-      (f64::from(aabb.y.get(centimeter))) / 2.0,
-      (f64::from(aabb.z.get(centimeter))) / 2.0,
-    ))),
-    Volume::Line { .. } => unimplemented!("volume_to_na_shape for Line"),
-    Volume::VerticalCylinder { .. } => unimplemented!("volume_to_na_shape for VerticalCylinder"),
+    Volume::Sphere(r) => (na_iso(pt), shape::ShapeHandle3::new(shape::Ball::new(meters(r)))),
+    // Cuboids are centered on their position, but AABBs have their origin at the top-left.
+    Volume::AABB(aabb) => {
+      let half_extents = Vector3::new(meters(aabb.x), meters(aabb.y), meters(aabb.z)) / 2.0;
+      let iso = Isometry3::new(na_vector(pt) + half_extents, na::zero());
+      (iso, shape::ShapeHandle3::new(shape::Cuboid::new(half_extents)))
+    }
+    Volume::Line { vector } => {
+      let segment = shape::Segment::new(na::Point3::origin(), na_point(vector));
+      (na_iso(pt), shape::ShapeHandle3::new(segment))
+    }
+    // Cylinders are centered on their position and run along the Y axis, so they need to be
+    // stood up along the Z axis.
+    Volume::VerticalCylinder { radius, height } => {
+      let half_height = meters(height) / 2.0;
+      let center = na_vector(pt) + Vector3::new(0.0, 0.0, half_height);
+      let iso = Isometry3::new(center, Vector3::new(FRAC_PI_2, 0.0, 0.0));
+      (iso, shape::ShapeHandle3::new(shape::Cylinder::new(half_height, meters(radius))))
+    }
   }
 }
 
@@ -471,15 +758,20 @@ where
 
 #[cfg(test)]
 pub mod test {
+  use std::collections::{HashMap, HashSet};
+  use std::iter::FromIterator;
+
+  use rand::{Rng, SeedableRng, XorShiftRng};
+
   use crate::grid::*;
   use crate::types::*;
 
   /// A map containing a single open block of terrain at 0,0,0
-  fn box_map() -> Terrain { vec![Point3::new(0, 0, 0)] }
+  fn box_map() -> Terrain { hashset!{Point3::new(0, 0, 0)} }
 
   /// A map shaped like a dumbbell, with two 2x3 rooms connected by a 1x1 passage
   fn dumbbell_map() -> Terrain {
-    vec![
+    hashset!{
       Point3::new(0, 0, 0),
       Point3::new(100, 0, 0),
       Point3::new(300, 0, 0),
@@ -493,15 +785,15 @@ pub mod test {
       Point3::new(100, 200, 0),
       Point3::new(300, 200, 0),
       Point3::new(400, 200, 0),
-    ]
+    }
   }
 
   /// A map containing a large 40-meter square of open terrain.
   pub fn huge_box() -> Terrain {
-    let mut map = vec![];
+    let mut map = HashSet::new();
     for x in -20..20 {
       for y in -20..20 {
-        map.push(Point3::new(x * 100, y * 100, 0));
+        map.insert(Point3::new(x * 100, y * 100, 0));
       }
     }
     map
@@ -525,11 +817,20 @@ pub mod test {
     assert_eq!(TileSystem::Realistic.point3_distance(pos1, pos2), u32cm(141));
   }
 
+  #[test]
+  fn test_dnd_distance_counts_vertical_distance() {
+    let pos1 = Point3::new(0, 0, 0);
+    assert_eq!(TileSystem::DnD.point3_distance(pos1, Point3::new(100, 100, 0)), u32cm(100));
+    assert_eq!(TileSystem::DnD.point3_distance(pos1, Point3::new(100, 0, 300)), u32cm(300));
+    assert_eq!(TileSystem::DnD.point3_distance(pos1, Point3::new(0, 0, -200)), u32cm(200));
+  }
+
   #[test]
   fn test_neighbors() {
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let mut pts = TileSystem::Realistic.point3_neighbors(&terrain, size, Point3::new(0, 0, 0));
+    let map = TerrainMap::plain(&terrain);
+    let mut pts = TileSystem::Realistic.point3_neighbors(map, size, Point3::new(0, 0, 0));
     pts.sort();
     let mut expected = vec![
       (Point3::new(-100, 0, 0), u32cm(100)),
@@ -548,10 +849,10 @@ pub mod test {
   /// a diagonal neighbor is not considered accessible if it "goes around" a blocked corner
   #[test]
   fn test_neighbors_around_corners() {
-    let terrain = vec![Point3::new(100, 0, 0)];
+    let terrain = hashset!{Point3::new(100, 0, 0)};
     let size = Volume::AABB(medium_size());
     let pts: Vec<Point3> = TileSystem::Realistic
-      .point3_neighbors(&terrain, size, Point3::new(0, 0, 0))
+      .point3_neighbors(TerrainMap::plain(&terrain), size, Point3::new(0, 0, 0))
       .iter()
       .map(|&(p, _)| p)
       .collect();
@@ -564,9 +865,10 @@ pub mod test {
     let start = Point3::new(0, 0, 0);
     let success = Box::new(|n: &Point3| *n == Point3::new(200, 200, 0));
     let size = Volume::AABB(medium_size());
+    let terrain = huge_box();
     let paths_and_costs = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| TileSystem::Realistic.point3_neighbors(TerrainMap::plain(&terrain), size, *n),
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(u32::max_value() / 64 - 1), // FIXME this is a workaround for uom bug #55
       vec![success],
@@ -580,9 +882,10 @@ pub mod test {
     let start = Point3::new(0, 0, 0);
     let success = Box::new(|n: &Point3| *n == Point3::new(500, 0, 0));
     let size = Volume::AABB(medium_size());
+    let terrain = huge_box();
     let result = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| TileSystem::Realistic.point3_neighbors(TerrainMap::plain(&terrain), size, *n),
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(499),
       vec![success],
@@ -597,9 +900,10 @@ pub mod test {
     let start = Point3::new(0, 0, 0);
     let success = Box::new(|n: &Point3| *n == Point3::new(500, 0, 0));
     let size = Volume::AABB(medium_size());
+    let terrain = huge_box();
     let result = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| TileSystem::Realistic.point3_neighbors(TerrainMap::plain(&terrain), size, *n),
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(500),
       vec![success],
@@ -630,9 +934,10 @@ pub mod test {
       Box::new(|n: &Point3| *n == Point3::new(-100, -100, 0)),
     ];
     let size = Volume::AABB(medium_size());
+    let terrain = huge_box();
     let paths_and_costs = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| TileSystem::Realistic.point3_neighbors(TerrainMap::plain(&terrain), size, *n),
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(u32::max_value() / 64 - 1), // FIXME this is a workaround for uom bug #55
       successes,
@@ -647,7 +952,12 @@ pub mod test {
     let terrain = box_map();
     let size = Volume::AABB(medium_size());
     assert_eq!(
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(1000)),
+      TileSystem::Realistic.get_all_accessible(
+        Point3::new(0, 0, 0),
+        TerrainMap::plain(&terrain),
+        size,
+        u32cm(1000)
+      ),
       vec![]
     );
   }
@@ -657,8 +967,12 @@ pub mod test {
     // a speed of 100 means you can only move on the axes
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let mut pts =
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(100));
+    let mut pts = TileSystem::Realistic.get_all_accessible(
+      Point3::new(0, 0, 0),
+      TerrainMap::plain(&terrain),
+      size,
+      u32cm(100),
+    );
     pts.sort();
     let mut expected = vec![
       Point3::new(-100, 0, 0),
@@ -675,8 +989,12 @@ pub mod test {
     // a speed of 141 means you can also move diagonally, but only once
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let mut pts =
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(141));
+    let mut pts = TileSystem::Realistic.get_all_accessible(
+      Point3::new(0, 0, 0),
+      TerrainMap::plain(&terrain),
+      size,
+      u32cm(141),
+    );
     pts.sort();
     let mut expected = vec![
       Point3::new(-100, 0, 0),
@@ -703,14 +1021,113 @@ pub mod test {
   fn test_accessible_average_speed() {
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let pts =
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(1000));
+    let pts = TileSystem::Realistic.get_all_accessible(
+      Point3::new(0, 0, 0),
+      TerrainMap::plain(&terrain),
+      size,
+      u32cm(1000),
+    );
     // NOTE: The reason this isn't 314 (pie are square of radius=100) is that we only allow
     // 8 degrees of movement, which leaves certain positions within a circle impossible to
     // reach even if you can technically move the radius of the circle in one turn.
     assert_eq!(pts.len(), 284);
   }
 
+  #[test]
+  fn terrain_cells_multiply_movement_cost() {
+    let ts = TileSystem::Realistic;
+    let terrain = huge_box();
+    let cells = hashmap!{
+      Point3::new(100, 0, 0) => TerrainCell::Difficult(3),
+      Point3::new(0, 100, 0) => TerrainCell::Water,
+    };
+    let map = TerrainMap { open: &terrain, stairs: None, cells: Some(&cells) };
+    let size = Volume::AABB(medium_size());
+    let neighbors: HashMap<Point3, u32units::Length> =
+      ts.point3_neighbors(map, size, Point3::new(0, 0, 0)).into_iter().collect();
+    assert_eq!(neighbors[&Point3::new(100, 0, 0)], u32cm(300));
+    assert_eq!(neighbors[&Point3::new(0, 100, 0)], u32cm(200));
+    assert_eq!(neighbors[&Point3::new(-100, 0, 0)], u32cm(100));
+
+    let pts = ts.get_all_accessible(Point3::new(0, 0, 0), map, size, u32cm(200));
+    assert!(!pts.contains(&Point3::new(100, 0, 0)));
+    assert!(pts.contains(&Point3::new(0, 100, 0)));
+  }
+
+  /// A 3x1 room, with a 3x1 balcony directly above it, and stairs up to the balcony at the east
+  /// end of the room.
+  fn two_level_map() -> (Terrain, HashSet<Point3>) {
+    let mut terrain = HashSet::new();
+    for x in 0..3 {
+      terrain.insert(Point3::new(x * 100, 0, 0));
+      terrain.insert(Point3::new(x * 100, 0, 100));
+    }
+    (terrain, hashset!{Point3::new(200, 0, 0)})
+  }
+
+  #[test]
+  fn test_accessible_up_stairs() {
+    let ts = TileSystem::Realistic;
+    let (terrain, stairs) = two_level_map();
+    let size = Volume::AABB(medium_size());
+    let start = Point3::new(0, 0, 0);
+
+    let mut pts = ts.get_all_accessible(start, TerrainMap::plain(&terrain), size, u32cm(1000));
+    pts.sort();
+    assert_eq!(pts, vec![Point3::new(100, 0, 0), Point3::new(200, 0, 0)]);
+
+    // It takes 5 meters to walk to the stairs, climb them, and walk back along the balcony.
+    let map = TerrainMap { open: &terrain, stairs: Some(&stairs), cells: None };
+    let mut pts = ts.get_all_accessible(start, map, size, u32cm(500));
+    pts.sort();
+    let mut expected = vec![
+      Point3::new(100, 0, 0),
+      Point3::new(200, 0, 0),
+      Point3::new(0, 0, 100),
+      Point3::new(100, 0, 100),
+      Point3::new(200, 0, 100),
+    ];
+    expected.sort();
+    assert_eq!(pts, expected);
+    let pts = ts.get_all_accessible(start, map, size, u32cm(400));
+    assert!(!pts.contains(&Point3::new(0, 0, 100)));
+  }
+
+  #[test]
+  fn find_path_down_stairs() {
+    let ts = TileSystem::Realistic;
+    let (terrain, stairs) = two_level_map();
+    let size = Volume::AABB(medium_size());
+    let start = Point3::new(0, 0, 100);
+    let map = TerrainMap { open: &terrain, stairs: Some(&stairs), cells: None };
+    let path = ts.find_path(start, u32cm(1000), map, size, Point3::new(0, 0, 0));
+    let expected = vec![
+      Point3::new(0, 0, 100),
+      Point3::new(100, 0, 100),
+      Point3::new(200, 0, 100),
+      Point3::new(200, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(0, 0, 0),
+    ];
+    assert_eq!(path, Some((expected, u32cm(500))));
+  }
+
+  #[test]
+  fn open_points_in_range_spans_levels() {
+    let (terrain, _) = two_level_map();
+    let start = Point3::new(0, 0, 0);
+    let mut pts = TileSystem::Realistic.open_points_in_range(start, &terrain, u32cm(100));
+    pts.sort();
+    let mut expected = vec![
+      Point3::new(0, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(0, 0, 100),
+      Point3::new(100, 0, 100),
+    ];
+    expected.sort();
+    assert_eq!(pts, expected);
+  }
+
   #[test]
   fn items_within_volume_sphere() {
     let ts = TileSystem::Realistic;
@@ -779,6 +1196,168 @@ pub mod test {
     );
   }
 
+  #[test]
+  fn points_in_volume_sphere() {
+    let vol = Volume::Sphere(u32cm(100));
+    let pt = Point3::new(0, 0, 0);
+    let mut results = TileSystem::Realistic.points_in_volume(vol, pt);
+    results.sort();
+    let mut expected = vec![
+      Point3::new(-100, 0, 0),
+      Point3::new(0, -100, 0),
+      Point3::new(0, 0, -100),
+      Point3::new(0, 0, 0),
+      Point3::new(0, 0, 100),
+      Point3::new(0, 100, 0),
+      Point3::new(100, 0, 0),
+    ];
+    expected.sort();
+    assert_eq!(results, expected);
+    // In DnD, spheres are cubes.
+    assert_eq!(TileSystem::DnD.points_in_volume(vol, pt).len(), 27);
+  }
+
+  #[test]
+  fn points_in_volume_line() {
+    let vol = Volume::Line { vector: Point3::new(300, 100, 0) };
+    let results = TileSystem::Realistic.points_in_volume(vol, Point3::new(100, 0, 0));
+    assert_eq!(
+      results,
+      vec![Point3::new(100, 0, 0), Point3::new(200, 0, 0), Point3::new(300, 100, 0)]
+    );
+  }
+
+  #[test]
+  fn points_in_volume_vertical_cylinder() {
+    let vol = Volume::VerticalCylinder { radius: u32cm(100), height: u32cm(200) };
+    let mut results = TileSystem::Realistic.points_in_volume(vol, Point3::new(0, 0, 0));
+    let mut expected = vec![];
+    for z in vec![0, 100] {
+      expected.push(Point3::new(-100, 0, z));
+      expected.push(Point3::new(0, -100, z));
+      expected.push(Point3::new(0, 0, z));
+      expected.push(Point3::new(0, 100, z));
+      expected.push(Point3::new(100, 0, z));
+    }
+    results.sort();
+    expected.sort();
+    assert_eq!(results, expected);
+  }
+
+  #[test]
+  fn items_within_volume_aabb() {
+    let ts = TileSystem::Realistic;
+    let vol = Volume::AABB(large_size());
+    let items = hashmap!{
+      "Inside" => Point3::new(200, 200, 0),
+      "Past the edge" => Point3::new(300, 100, 0),
+      "Above" => Point3::new(100, 100, 100),
+    };
+    assert_eq!(ts.items_within_volume(vol, Point3::new(100, 100, 0), &items), vec!["Inside"]);
+  }
+
+  #[test]
+  fn items_within_volume_vertical_cylinder() {
+    let ts = TileSystem::Realistic;
+    let vol = Volume::VerticalCylinder { radius: u32cm(200), height: u32cm(100) };
+    let items = hashmap!{
+      "Inside" => Point3::new(100, 100, 0),
+      "Too far" => Point3::new(200, 100, 0),
+      "Above" => Point3::new(0, 0, 100),
+    };
+    assert_eq!(ts.items_within_volume(vol, Point3::new(0, 0, 0), &items), vec!["Inside"]);
+  }
+
+  /// Generate a random volume, and a random tile to place it at.
+  fn random_volume<R: Rng>(rng: &mut R) -> (Volume, Point3) {
+    let pt = Point3::new(
+      rng.gen_range(-10, 10) * 100,
+      rng.gen_range(-10, 10) * 100,
+      rng.gen_range(-3, 3) * 100,
+    );
+    let volume = match rng.gen_range(0, 4) {
+      0 => Volume::Sphere(u32cm(rng.gen_range(0, 600))),
+      1 => Volume::AABB(AABB {
+        x: u32cm(rng.gen_range(1, 500)),
+        y: u32cm(rng.gen_range(1, 500)),
+        z: u32cm(rng.gen_range(1, 500)),
+      }),
+      2 => Volume::VerticalCylinder {
+        radius: u32cm(rng.gen_range(0, 500)),
+        height: u32cm(rng.gen_range(1, 400)),
+      },
+      _ => Volume::Line {
+        vector: Point3::new(
+          rng.gen_range(-6, 7) * 100,
+          rng.gen_range(-6, 7) * 100,
+          rng.gen_range(-3, 4) * 100,
+        ),
+      },
+    };
+    (volume, pt)
+  }
+
+  /// All of the tiles within 7 meters of a tile on every axis; more than big enough to contain
+  /// any volume made by `random_volume`.
+  fn tiles_around(pt: Point3) -> Vec<Point3> {
+    let (x, y, z) = tile_coords(pt);
+    let mut tiles = vec![];
+    for dx in -7..8 {
+      for dy in -7..8 {
+        for dz in -7..8 {
+          tiles.push(tile_point((x + dx, y + dy, z + dz)));
+        }
+      }
+    }
+    tiles
+  }
+
+  #[test]
+  fn items_within_volume_agrees_with_points_in_volume() {
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    for _ in 0..100 {
+      let (volume, pt) = random_volume(&mut rng);
+      let items: HashMap<Point3, Point3> = tiles_around(pt).into_iter().map(|t| (t, t)).collect();
+      for ts in vec![TileSystem::Realistic, TileSystem::DnD] {
+        let mut points = ts.points_in_volume(volume, pt);
+        let mut within = ts.items_within_volume(volume, pt, &items);
+        points.sort();
+        within.sort();
+        assert_eq!(points, within, "{:?} at {:?} in {:?}", volume, pt, ts);
+      }
+    }
+  }
+
+  #[test]
+  fn volume_shapes_agree_with_points_in_volume() {
+    let ts = TileSystem::Realistic;
+    let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+    for _ in 0..100 {
+      let (volume, pt) = random_volume(&mut rng);
+      let (iso, shape) = volume_to_na_shape(volume, pt);
+      let query = shape.as_point_query().expect("Volume shapes must support point queries");
+      let points: HashSet<Point3> = HashSet::from_iter(ts.points_in_volume(volume, pt));
+      // Distances are truncated to the centimeter, and lines are made of the tiles they pass
+      // closest to, so points can be a little bit outside of their shape.
+      let tolerance = match volume {
+        Volume::Line { .. } => 0.75,
+        _ => 0.01,
+      };
+      for tile in tiles_around(pt) {
+        let na_pt = na_point(tile);
+        if points.contains(&tile) {
+          let distance = query.distance_to_point(&iso, &na_pt, true);
+          assert!(distance <= tolerance, "{:?} at {:?}: {:?} is outside", volume, pt, tile);
+        } else {
+          // Points on the far edges of a shape aren't in the volume.
+          let strictly_inside = query.contains_point(&iso, &na_pt)
+            && query.distance_to_point(&iso, &na_pt, false) > 0.001;
+          assert!(!strictly_inside, "{:?} at {:?}: {:?} is inside", volume, pt, tile);
+        }
+      }
+    }
+  }
+
   #[test]
   fn line_through_point_simple() {
     let line = line_through_point(Point3::new(0, 0, 0), Point3::new(100, 0, 0), u32cm(200));
@@ -802,8 +1381,9 @@ pub mod test {
     let ts = TileSystem::Realistic;
     let dumbbell = dumbbell_map();
     let big_guy = Volume::AABB(large_size());
-    let path =
-      ts.find_path(Point3::new(0, 0, 0), u32cm(1000), &dumbbell, big_guy, Point3::new(300, 0, 0));
+    let dest = Point3::new(300, 0, 0);
+    let map = TerrainMap::plain(&dumbbell);
+    let path = ts.find_path(Point3::new(0, 0, 0), u32cm(1000), map, big_guy, dest);
     assert_eq!(path, None);
   }
 
@@ -811,10 +1391,11 @@ pub mod test {
   fn large_creature_can_fit_through_large_opening() {
     let ts = TileSystem::Realistic;
     let mut dumbbell = dumbbell_map();
-    dumbbell.push(Point3::new(200, 200, 0));
+    dumbbell.insert(Point3::new(200, 200, 0));
     let big_guy = Volume::AABB(large_size());
-    let path =
-      ts.find_path(Point3::new(0, 0, 0), u32cm(1000), &dumbbell, big_guy, Point3::new(300, 0, 0));
+    let dest = Point3::new(300, 0, 0);
+    let map = TerrainMap::plain(&dumbbell);
+    let path = ts.find_path(Point3::new(0, 0, 0), u32cm(1000), map, big_guy, dest);
     assert_eq!(
       path,
       Some((
@@ -829,4 +1410,93 @@ pub mod test {
       ))
     );
   }
+
+  /// A 5x3 room with a wall down the middle, except for a gap in the top row.
+  fn walled_map() -> Terrain {
+    let mut map = HashSet::new();
+    for x in 0..5 {
+      for y in 0..3 {
+        if x != 2 || y == 2 {
+          map.insert(Point3::new(x * 100, y * 100, 0));
+        }
+      }
+    }
+    map
+  }
+
+  #[test]
+  fn tiles_on_a_line() {
+    assert_eq!(
+      tiles_on_line((0, 0, 0), (4, 1, 0)),
+      vec![(0, 0, 0), (1, 0, 0), (2, 1, 0), (3, 1, 0), (4, 1, 0)]
+    );
+    assert_eq!(tiles_on_line((0, 0, 0), (-2, 0, 2)), vec![(0, 0, 0), (-1, 0, 1), (-2, 0, 2)]);
+    assert_eq!(tiles_on_line((3, 3, 3), (3, 3, 3)), vec![(3, 3, 3)]);
+  }
+
+  #[test]
+  fn line_of_sight_blocked_by_walls() {
+    let terrain = walled_map();
+    assert!(!line_of_sight(&terrain, Point3::new(0, 0, 0), Point3::new(400, 0, 0)));
+    assert!(!line_of_sight(&terrain, Point3::new(100, 100, 0), Point3::new(300, 100, 0)));
+    assert!(line_of_sight(&terrain, Point3::new(0, 200, 0), Point3::new(400, 200, 0)));
+    assert!(line_of_sight(&terrain, Point3::new(0, 0, 0), Point3::new(100, 200, 0)));
+    // Points right next to each other can always see each other.
+    assert!(line_of_sight(&terrain, Point3::new(100, 100, 0), Point3::new(200, 200, 0)));
+  }
+
+  #[test]
+  fn line_of_sight_not_between_diagonal_walls() {
+    let terrain = hashset!{Point3::new(0, 0, 0), Point3::new(100, 100, 0)};
+    assert!(!line_of_sight(&terrain, Point3::new(0, 0, 0), Point3::new(100, 100, 0)));
+    let terrain = hashset!{
+      Point3::new(0, 0, 0),
+      Point3::new(100, 100, 0),
+      Point3::new(100, 0, 0),
+    };
+    assert!(line_of_sight(&terrain, Point3::new(0, 0, 0), Point3::new(100, 100, 0)));
+  }
+
+  #[test]
+  fn volume_propagation() {
+    let ts = TileSystem::Realistic;
+    let terrain = walled_map();
+    let pt = Point3::new(100, 0, 0);
+    let items = hashmap!{
+      "In the open" => Point3::new(100, 200, 0),
+      "Behind the wall" => Point3::new(300, 0, 0),
+    };
+    let affected = |volume, propagation| {
+      let mut results = ts.items_affected_by_volume(volume, pt, propagation, &terrain, &items);
+      results.sort();
+      results
+    };
+    let big = Volume::Sphere(u32cm(700));
+    let small = Volume::Sphere(u32cm(500));
+    let both = vec!["Behind the wall", "In the open"];
+    assert_eq!(affected(big, VolumePropagation::Unobstructed), both);
+    assert_eq!(affected(big, VolumePropagation::Burst), vec!["In the open"]);
+    // The path around the wall is 6 meters long.
+    assert_eq!(affected(big, VolumePropagation::Spreading), both);
+    assert_eq!(affected(small, VolumePropagation::Spreading), vec!["In the open"]);
+  }
+
+  #[test]
+  fn filter_visible_points() {
+    let terrain = walled_map();
+    let points = terrain.iter().cloned().collect();
+    let mut visible = filter_visible(&terrain, Point3::new(0, 0, 0), points);
+    visible.sort();
+    let mut expected = vec![
+      Point3::new(0, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(0, 100, 0),
+      Point3::new(100, 100, 0),
+      Point3::new(0, 200, 0),
+      Point3::new(100, 200, 0),
+      Point3::new(200, 200, 0),
+    ];
+    expected.sort();
+    assert_eq!(visible, expected);
+  }
 }
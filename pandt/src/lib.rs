@@ -2,7 +2,6 @@
 
 //! Phone and Tablet.
 
-extern crate bresenham;
 #[macro_use]
 extern crate derive_more;
 #[macro_use]
@@ -38,6 +37,7 @@ extern crate serde_json;
 pub mod app;
 pub mod combat;
 pub mod creature;
+pub mod dice;
 pub mod game;
 pub mod grid;
 pub mod scene;
@@ -1,7 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::types::*;
-use crate::grid::{make_world, query_world};
+use crate::grid::{make_world, query_world, TerrainMap};
 
 impl Scene {
   pub fn create(creation: SceneCreation) -> Scene {
@@ -11,7 +11,9 @@ impl Scene {
       background_image_url: creation.background_image_url.clone(),
       background_image_offset: creation.background_image_offset,
       background_image_scale: creation.background_image_scale,
-      terrain: vec![],
+      terrain: HashSet::new(),
+      stairs: HashSet::new(),
+      terrain_cells: HashMap::new(),
       highlights: HashMap::new(),
       annotations: HashMap::new(),
       scene_hotspots: HashMap::new(),
@@ -39,6 +41,66 @@ impl Scene {
     }
     Ok(new)
   }
+  /// Put a terrain cell at a point (or remove the one that's there), opening or closing the
+  /// terrain at that point to match. Removing a cell leaves open terrain behind.
+  pub fn set_terrain_cell(&mut self, pt: Point3, cell: Option<TerrainCell>) {
+    let open = cell.as_ref().map_or(true, |cell| cell.is_open());
+    match cell {
+      Some(cell) => {
+        self.terrain_cells.insert(pt, cell);
+      }
+      None => {
+        self.terrain_cells.remove(&pt);
+      }
+    }
+    if open {
+      self.terrain.insert(pt);
+    } else {
+      self.terrain.remove(&pt);
+    }
+  }
+
+  /// Replace all of the terrain cells. Tiles whose cells are removed are left open, just like when
+  /// a single cell is removed.
+  pub fn set_terrain_cells(&mut self, cells: &HashMap<Point3, TerrainCell>) {
+    let removed: Vec<Point3> =
+      self.terrain_cells.keys().filter(|pt| !cells.contains_key(pt)).cloned().collect();
+    for pt in removed {
+      self.set_terrain_cell(pt, None);
+    }
+    for (pt, cell) in cells {
+      self.set_terrain_cell(*pt, Some(cell.clone()));
+    }
+  }
+
+  /// Get rid of the terrain cells that no longer agree with whether their tiles are open, after
+  /// the terrain has been replaced wholesale.
+  pub fn sync_terrain_cells(&mut self) {
+    let terrain = &self.terrain;
+    self.terrain_cells.retain(|pt, cell| cell.is_open() == terrain.contains(pt));
+  }
+
+  /// Open up some tiles, getting rid of any terrain cells (like walls or doors) on them.
+  pub fn open_terrain(&mut self, pts: &Terrain) {
+    for pt in pts {
+      self.terrain_cells.remove(pt);
+      self.terrain.insert(*pt);
+    }
+  }
+
+  /// Close off some tiles, getting rid of any terrain cells on them.
+  pub fn close_terrain(&mut self, pts: &Terrain) {
+    for pt in pts {
+      self.terrain_cells.remove(pt);
+      self.terrain.remove(pt);
+    }
+  }
+
+  /// Get everything about this scene's terrain that affects movement.
+  pub fn terrain_map(&self) -> TerrainMap {
+    TerrainMap { open: &self.terrain, stairs: Some(&self.stairs), cells: Some(&self.terrain_cells) }
+  }
+
   pub fn add_volume_condition(
     &self, condition_id: ConditionID, point: Point3, volume: Volume, condition: Condition,
     duration: Duration,
@@ -75,21 +137,96 @@ impl Scene {
         .expect("Mapping over IDs that should only appear in this collection");
       results.push((cond_id, val));
     }
+    // Their effects are rolled in this order, so it mustn't depend on hashing for replays to work.
+    results.sort_by_key(|&(cond_id, _)| cond_id);
     Ok(results)
   }
 
   /// Return a set of points of open terrain which  intersect a volume.
   /// Largely used for previewing the area that will be affected by a volume-affecting ability.
   pub fn open_terrain_in_volume(
+    &self, game: &Game, pt: Point3, volume: Volume, propagation: VolumePropagation
+  ) -> Result<Vec<Point3>, GameError> {
+    let all_open = self.terrain.iter().map(|pt| (*pt, *pt)).collect();
+    Ok(game.tile_system.items_affected_by_volume(volume, pt, propagation, &self.terrain, &all_open))
+  }
+
+  /// Find the closed tiles within a volume, on the same level as the point it's placed at.
+  pub fn closed_terrain_in_volume(&self, game: &Game, pt: Point3, volume: Volume) -> Terrain {
+    let tiles = game.tile_system.points_in_volume(volume, pt).into_iter();
+    tiles.filter(|tile| tile.z == pt.z && !self.terrain.contains(tile)).collect()
+  }
+
+  /// Find the open tiles within a volume, on the same level as the point it's placed at, that no
+  /// creature is standing on.
+  pub fn unoccupied_terrain_in_volume(
     &self, game: &Game, pt: Point3, volume: Volume
   ) -> Result<Terrain, GameError> {
-    let all_open = self.terrain.iter().map(|pt| (*pt, *pt)).collect();
-    Ok(game.tile_system.items_within_volume(volume, pt, &all_open))
+    let occupied = self.occupied_tiles(game)?;
+    let tiles = game.tile_system.points_in_volume(volume, pt).into_iter();
+    Ok(
+      tiles
+        .filter(|tile| tile.z == pt.z && self.terrain.contains(tile) && !occupied.contains(tile))
+        .collect(),
+    )
+  }
+
+  /// Find the creatures which are affected by a volume. A creature is affected if any of the tiles
+  /// it takes up are. They're sorted by ID, so that dice rolled for each of them come out the same
+  /// way when a command is replayed.
+  pub fn creatures_in_volume(
+    &self, game: &Game, pt: Point3, volume: Volume, propagation: VolumePropagation
+  ) -> Result<Vec<CreatureID>, GameError> {
+    let mut creature_tiles = HashMap::new();
+    for creature_id in self.creatures.keys() {
+      for tile in self.creature_tiles(game, *creature_id)? {
+        creature_tiles.insert((*creature_id, tile), tile);
+      }
+    }
+    let affected = game.tile_system.items_affected_by_volume(
+      volume,
+      pt,
+      propagation,
+      &self.terrain,
+      &creature_tiles,
+    );
+    let creature_ids: HashSet<CreatureID> = affected.into_iter().map(|(cid, _)| cid).collect();
+    let mut creature_ids: Vec<CreatureID> = creature_ids.into_iter().collect();
+    creature_ids.sort();
+    Ok(creature_ids)
+  }
+
+  /// Get the tiles that a creature in this scene takes up, based on its size.
+  /// Find the effects of the hazards that a creature is standing in. A creature that takes up more
+  /// than one tile of the same hazard is only affected by it once.
+  pub fn creature_hazards(
+    &self, game: &Game, creature_id: CreatureID
+  ) -> Result<Vec<CreatureEffect>, GameError> {
+    let mut effects = vec![];
+    for tile in self.creature_tiles(game, creature_id)? {
+      if let Some(TerrainCell::Hazard(effect)) = self.terrain_cells.get(&tile) {
+        if !effects.contains(effect) {
+          effects.push(effect.clone());
+        }
+      }
+    }
+    Ok(effects)
+  }
+
+  /// Find every tile that some creature in the scene takes up.
+  pub fn occupied_tiles(&self, game: &Game) -> Result<HashSet<Point3>, GameError> {
+    let mut occupied = HashSet::new();
+    for creature_id in self.creatures.keys() {
+      occupied.extend(self.creature_tiles(game, *creature_id)?);
+    }
+    Ok(occupied)
   }
 
-  pub fn creatures_in_volume(&self, ts: TileSystem, pt: Point3, volume: Volume) -> Vec<CreatureID> {
-    let creature_locations = self.creatures.iter().map(|(cid, &(pt, _))| (*cid, pt)).collect();
-    ts.items_within_volume(volume, pt, &creature_locations)
+  pub fn creature_tiles(
+    &self, game: &Game, creature_id: CreatureID
+  ) -> Result<Vec<Point3>, GameError> {
+    let creature = game.get_creature(creature_id)?;
+    Ok(game.tile_system.footprint(self.get_pos(creature_id)?, creature.creature.size))
   }
 
   pub fn get_world(&self, game: &Game) -> Result<CollisionWorld, GameError> {
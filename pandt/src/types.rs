@@ -7,9 +7,6 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use num::Saturating;
-use rand;
-use rand::distributions as dist;
-use rand::distributions::IndependentSample;
 use serde::ser;
 use serde::ser::{Error as SerError, SerializeStruct};
 use serde::de;
@@ -41,7 +38,9 @@ pub fn up_length(v: u32units::Length) -> i64units::Length { i64cm(v.get(centimet
 
 pub type Color = String;
 pub type Inventory = HashMap<ItemID, u64>;
-pub type Terrain = Vec<Point3>;
+/// The open tiles of a scene. This is hashed so that checking whether a tile is open (which
+/// pathfinding and line of sight do constantly) is cheap; it's serialized as a plain list of points.
+pub type Terrain = HashSet<Point3>;
 
 /// Point3 holds a position in 3d space in meters (FOR NOW --radix)
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
@@ -119,12 +118,37 @@ impl AABB {
   }
 }
 
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize, Deserialize)]
+/// A dice expression. These can be written with a string syntax like `4d6kh3+2` or
+/// `BestOf(2, 1d20)`; see `Dice::from_str` for the details. Deserialization accepts either that
+/// string syntax or the structured representation.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize)]
 pub enum Dice {
   Expr { num: u8, size: u8 },
   Plus(Box<Dice>, Box<Dice>),
   Flat(i8),
   BestOf(u8, Box<Dice>),
+  Minus(Box<Dice>, Box<Dice>),
+  /// A number of dice with modifiers applied to each die.
+  Roll {
+    num: u8,
+    size: u8,
+    /// Roll another die (and add it) whenever a die shows its maximum value.
+    #[serde(default)]
+    explode: bool,
+    /// Reroll (once) any die that shows a value lower than this.
+    #[serde(default)]
+    reroll_below: Option<u8>,
+    /// Only count some of the dice.
+    #[serde(default)]
+    keep: Option<Keep>,
+  },
+}
+
+/// Which dice of a `Dice::Roll` to count.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize, Deserialize)]
+pub enum Keep {
+  Highest(u8),
+  Lowest(u8),
 }
 
 impl Dice {
@@ -134,45 +158,7 @@ impl Dice {
 
   pub fn plus(&self, d: Dice) -> Dice { Dice::Plus(Box::new(self.clone()), Box::new(d)) }
 
-  /// Roll the dice, returning a vector containing all of the individual die rolls, and then the
-  /// final result.
-  pub fn roll(&self) -> (Vec<i16>, i32) {
-    match *self {
-      Dice::Expr { num, size } => {
-        let mut intermediate = vec![];
-        let mut result = 0i32;
-        let range: dist::Range<i32> = dist::Range::new(1, i32::from(size) + 1);
-        let mut rng = rand::thread_rng();
-        for _ in 0..num {
-          let val = range.ind_sample(&mut rng);
-          result += val;
-          intermediate.push(val as i16);
-        }
-        (intermediate, result)
-      }
-      Dice::Flat(val) => (vec![i16::from(val)], i32::from(val)),
-      Dice::Plus(ref l, ref r) => {
-        let (mut intermediate, left_result) = l.roll();
-        let (right_intermediate, right_result) = r.roll();
-        intermediate.extend(right_intermediate);
-        (intermediate, left_result + right_result)
-      }
-      Dice::BestOf(count, ref dice) => {
-        if count == 0 {
-          panic!("Sorry, can't roll best of 0.")
-        }
-        let (mut best_rolls, mut best_result) = dice.roll();
-        for _ in 1..count {
-          let (rolls, result) = dice.roll();
-          if result > best_result {
-            best_rolls = rolls;
-            best_result = result;
-          }
-        }
-        (best_rolls, best_result)
-      }
-    }
-  }
+  pub fn minus(&self, d: Dice) -> Dice { Dice::Minus(Box::new(self.clone()), Box::new(d)) }
 }
 
 #[derive(Add, Sub, Mul, Div, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize,
@@ -183,8 +169,8 @@ impl Saturating for HP {
   fn saturating_sub(self, other: Self) -> Self { HP(self.0.saturating_sub(other.0)) }
 }
 
-#[derive(Add, Sub, Mul, Div, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize,
-         Deserialize)]
+#[derive(Add, Sub, Mul, Div, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Default,
+         Serialize, Deserialize)]
 pub struct Energy(pub u8);
 impl Saturating for Energy {
   fn saturating_add(self, other: Self) -> Self { Energy(self.0.saturating_add(other.0)) }
@@ -218,6 +204,7 @@ uuid_id!(ItemID);
 uuid_id!(SceneID);
 uuid_id!(AbilityID);
 uuid_id!(ClassID);
+uuid_id!(FactionID);
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum FolderItemID {
@@ -383,21 +370,44 @@ pub enum GameCommand {
     scene_id: SceneID,
     creatures: Vec<CreatureID>,
   },
-  // AddSceneVolumeCondition {
-  //   scene_id: SceneID,
-  //   point: Point3,
-  //   volume: Volume,
-  //   condition: Condition,
-  //   duration: Duration,
-  // },
+  AddSceneVolumeCondition {
+    scene_id: SceneID,
+    point: Point3,
+    volume: Volume,
+    condition: Condition,
+    duration: Duration,
+  },
   RemoveSceneVolumeCondition {
     scene_id: SceneID,
     condition_id: ConditionID,
   },
   EditSceneTerrain {
     scene_id: SceneID,
-    terrain: Vec<Point3>,
+    terrain: Terrain,
+  },
+  /// Replace the terrain on a single level of a scene (the tiles at the given `z`), leaving the
+  /// other levels alone.
+  EditSceneTerrainLevel {
+    scene_id: SceneID,
+    level: i64units::Length,
+    terrain: Terrain,
+  },
+  EditSceneStairs {
+    scene_id: SceneID,
+    stairs: HashSet<Point3>,
   },
+  /// Replace all of the special terrain cells in a scene. The tiles of any cells that are removed
+  /// are left open.
+  EditSceneTerrainCells {
+    scene_id: SceneID,
+    terrain_cells: HashMap<Point3, TerrainCell>,
+  },
+  /// Open the door at a point, letting creatures through it.
+  OpenDoor(SceneID, Point3),
+  /// Close the door at a point. Closed doors block movement and line of sight.
+  CloseDoor(SceneID, Point3),
+  /// Destroy the destructible wall at a point, leaving open terrain behind.
+  DestroyWall(SceneID, Point3),
   EditSceneHighlights {
     scene_id: SceneID,
     highlights: HashMap<Point3, (Color, Visibility)>,
@@ -416,8 +426,8 @@ pub enum GameCommand {
   },
 
   // ** Combat management **
-  /// Start a combat with the specified creatures.
-  StartCombat(SceneID, Vec<CreatureID>),
+  /// Start a combat with the specified creatures, rolling initiative according to the mode.
+  StartCombat(SceneID, Vec<CreatureID>, InitiativeMode),
   /// Stop the current combat.
   StopCombat,
   /// Add a creature to combat.
@@ -429,11 +439,16 @@ pub enum GameCommand {
   /// Reroll initiative for all creatures in combat, and sort the combat list
   RerollCombatInitiative,
   /// Move to the next creature in the initiative list. This does *not* run any end-of-turn or
-  /// start-turn events.
+  /// start-turn events, though it does keep track of the round number, and the creatures whose
+  /// turn it becomes get their reactions back and lose their readied actions as usual.
   ForceNextTurn,
   /// Move to the previous creature in the initiative list. This does *not* run any end-of-turn or
-  /// start-turn events.
+  /// start-turn events, though it does keep track of the round number, and the creatures whose
+  /// turn it becomes get their reactions back and lose their readied actions as usual.
   ForcePrevTurn,
+  /// Change how many actions of each kind creatures get per turn in the current combat. `None`
+  /// lifts the limit, which is how combats start out.
+  SetActionBudget(Option<ActionBudget>),
 
   // ** Combat **
   /// Use an Ability out of combat.
@@ -443,8 +458,30 @@ pub enum GameCommand {
   /// Move the current creature in combat to a point.
   /// There must be a clear path according to the current loaded map.
   PathCurrentCombatCreature(Point3),
-  /// End the current creature's turn.
+  /// End the current creature's turn, along with everyone sharing it in a group turn. This is
+  /// rejected while there are pending interrupts.
   Done,
+  /// Take the current creature out of the initiative order to put off its turn until later. It
+  /// can't have moved or acted yet, or be sharing a group turn.
+  DelayTurn,
+  /// Bring a creature that delayed its turn back into the initiative order, right after the
+  /// current turn. It takes the rest of its turn when the current turn is done.
+  ResumeTurn(CreatureID),
+  /// Hand the current turn to another creature that shares it in a group turn. Each of them keeps
+  /// track of its own movement and actions.
+  SwitchTurnMember(CreatureID),
+  /// Prepare the current creature to use an ability as a reaction when something happens.
+  ReadyAction {
+    ability_id: AbilityID,
+    target: DecidedTarget,
+    trigger: ReadyTrigger,
+  },
+  /// Resolve the first pending interrupt by having its creature use its reaction.
+  ReactToInterrupt,
+  /// Resolve the first pending interrupt without its creature doing anything.
+  DeclineInterrupt,
+  /// Have some creatures take a rest to recover their resources. None of them can be in combat.
+  Rest(RestKind, Vec<CreatureID>),
 
   // ** Creature Manipulation **
   /// Create a new creature.
@@ -460,6 +497,19 @@ pub enum GameCommand {
   /// There must be a clear path according to the current loaded map. It doesn't matter whether
   /// the creature is in combat.
   PathCreature(SceneID, CreatureID, Point3),
+  /// Put a creature in a faction, or take it out of any faction with None.
+  SetCreatureFaction(CreatureID, Option<FactionID>),
+
+  // ** Faction Management **
+  /// Create a new faction with the given name.
+  CreateFaction(String),
+  /// Set how the creatures of one faction regard the creatures of another. This only goes one
+  /// way; set the other direction separately.
+  SetFactionRelationship {
+    faction_id: FactionID,
+    other: FactionID,
+    relationship: Relationship,
+  },
 
   // ** Player Manipulation **
   /// Register a player as available for controlling a creature.
@@ -476,6 +526,8 @@ pub enum GameCommand {
   SetPlayerScene(PlayerID, Option<SceneID>),
 
   SetActiveScene(Option<SceneID>),
+  /// Choose whether abilities used outside of combat cost energy and start cooldowns.
+  SetOutOfCombatCost(OutOfCombatCost),
 
   /// Roll back to a specific snapshot + log index
   Rollback(usize, usize),
@@ -491,6 +543,8 @@ pub enum CreatureLog {
   ApplyCondition(ConditionID, Duration, Condition),
   DecrementConditionRemaining(ConditionID),
   RemoveCondition(ConditionID),
+  StartCooldown(AbilityID, u8),
+  DecrementCooldown(AbilityID),
 }
 
 // TODO: get rid of CombatLog, it's dumb... unless we ever support multiple Combats?
@@ -503,6 +557,27 @@ pub enum CombatLog {
   ForceNextTurn,
   ForcePrevTurn,
   RerollInitiative(Vec<(CreatureID, i16)>),
+  QueueInterrupt(Interrupt),
+  /// Remove the first pending interrupt.
+  ResolveInterrupt,
+  UseReaction(CreatureID),
+  ReadyAction(CreatureID, ReadiedAction),
+  /// Queue an interrupt for a creature's readied action, which is then no longer readied.
+  TriggerReadiedAction(CreatureID),
+  /// Record that the recurring effects of some volume conditions have hit a creature this turn.
+  VolumeConditionsApplied(CreatureID, Vec<ConditionID>),
+  /// The current creature takes up one of its actions for the turn.
+  UseAction(ActionSlot),
+  SetActionBudget(Option<ActionBudget>),
+  /// Take the current creature out of the order, and move on to the next one.
+  DelayTurn(CreatureID),
+  /// Put a delayed creature back into the order after the current turn.
+  ResumeTurn(CreatureID),
+  /// Make another creature sharing the current group turn the current creature.
+  SwitchTurnMember(CreatureID),
+  /// Change how initiative is rolled, rearranging the order to match. It's still the same
+  /// creature's turn afterwards.
+  SetInitiativeMode(InitiativeMode),
 }
 
 pub fn creature_logs_into_game_logs(cid: CreatureID, ls: Vec<CreatureLog>) -> Vec<GameLog> {
@@ -523,6 +598,7 @@ pub enum GameLog {
   },
 
   SetActiveScene(Option<SceneID>),
+  SetOutOfCombatCost(OutOfCombatCost),
 
   // ** Player Manipulation **
   RegisterPlayer(PlayerID),
@@ -609,10 +685,41 @@ pub enum GameLog {
     scene_id: SceneID,
     condition_id: ConditionID,
   },
+  /// Count a volume condition down by one round.
+  DecrementVolumeConditionRemaining {
+    scene_id: SceneID,
+    condition_id: ConditionID,
+  },
 
   EditSceneTerrain {
     scene_id: SceneID,
-    terrain: Vec<Point3>,
+    terrain: Terrain,
+  },
+  EditSceneTerrainLevel {
+    scene_id: SceneID,
+    level: i64units::Length,
+    terrain: Terrain,
+  },
+  EditSceneStairs {
+    scene_id: SceneID,
+    stairs: HashSet<Point3>,
+  },
+  EditSceneTerrainCells {
+    scene_id: SceneID,
+    terrain_cells: HashMap<Point3, TerrainCell>,
+  },
+  OpenDoor(SceneID, Point3),
+  CloseDoor(SceneID, Point3),
+  DestroyWall(SceneID, Point3),
+  /// Open up some tiles, leaving the rest of the terrain alone.
+  AddSceneTerrain {
+    scene_id: SceneID,
+    terrain: Terrain,
+  },
+  /// Close off some tiles, leaving the rest of the terrain alone.
+  RemoveSceneTerrain {
+    scene_id: SceneID,
+    terrain: Terrain,
   },
   EditSceneHighlights {
     scene_id: SceneID,
@@ -655,8 +762,20 @@ pub enum GameLog {
   },
   AddCreatureToCombat(CreatureID, i16),
   RemoveCreatureFromCombat(CreatureID),
+  SetCreatureFaction(CreatureID, Option<FactionID>),
+  CreateFaction(Faction),
+  SetFactionRelationship {
+    faction_id: FactionID,
+    other: FactionID,
+    relationship: Relationship,
+  },
   /// Indexes into snapshots and logs.
   Rollback(usize, usize),
+
+  /// The seed of the `SeededRoller` that was used to process a command. This is purely
+  /// informational; it has no effect on the game, but allows the command to be re-run with the
+  /// same rolls.
+  DiceSeed(u64),
 }
 
 pub fn combat_logs_into_game_logs(ls: Vec<CombatLog>) -> Vec<GameLog> {
@@ -677,6 +796,10 @@ pub enum GameError {
   ItemAlreadyExists(ItemID),
   #[fail(display = "The Item {:?} couldn't be found", _0)]
   ItemNotFound(ItemID),
+  #[fail(display = "The faction {:?} already exists", _0)]
+  FactionAlreadyExists(FactionID),
+  #[fail(display = "The faction {:?} couldn't be found", _0)]
+  FactionNotFound(FactionID),
   #[fail(display = "The scene {:?} already exists", _0)]
   SceneAlreadyExists(SceneID),
   #[fail(display = "The Scene '{:?}' wasn't found", _0)]
@@ -701,6 +824,8 @@ pub enum GameError {
   MustRerollAtStartOfRound,
   #[fail(display = "The creature with ID {:?} does not have the ability {:?}", _0, _1)]
   CreatureLacksAbility(CreatureID, AbilityID),
+  #[fail(display = "Creature {:?} can't use ability {:?} for another {} rounds.", _0, _1, _2)]
+  AbilityOnCooldown(CreatureID, AbilityID, u8),
   #[fail(display = "The creature with ID {} could not be found.", _0)]
   CreatureNotFound(String),
   #[fail(display = "Creature with ID {:?} is not a valid target.", _0)]
@@ -713,8 +838,36 @@ pub enum GameError {
   CreatureOutOfRange(CreatureID),
   #[fail(display = "Point {:?} is out of range.", _0)]
   PointOutOfRange(Point3),
+  #[fail(display = "Creature {:?} can't be seen.", _0)]
+  CreatureNotVisible(CreatureID),
+  #[fail(display = "Point {:?} can't be seen.", _0)]
+  PointNotVisible(Point3),
+  #[fail(display = "Point {:?} isn't on the level being edited.", _0)]
+  PointNotOnLevel(Point3),
+  #[fail(display = "There's no door at {:?}.", _0)]
+  NoDoor(Point3),
+  #[fail(display = "There's no destructible wall at {:?}.", _0)]
+  NoDestructibleWall(Point3),
+  #[fail(display = "Point {:?} can't be closed off while a creature is there.", _0)]
+  TileOccupied(Point3),
   #[fail(display = "There's a bug in the program: {}", _0)]
   BuggyProgram(String),
+  #[fail(display = "There are interrupts which must be resolved first.")]
+  InterruptsPending,
+  #[fail(display = "There are no interrupts to resolve.")]
+  NoInterruptPending,
+  #[fail(display = "Creature {:?} has no reactions left this round.", _0)]
+  NoReactionLeft(CreatureID),
+  #[fail(display = "Creature {:?} has no {:?} actions left this turn.", _0, _1)]
+  NoActionLeft(CreatureID, ActionSlot),
+  #[fail(display = "Creature {:?} can't delay its turn after moving or acting.", _0)]
+  CannotDelayAfterActing(CreatureID),
+  #[fail(display = "Creature {:?} hasn't delayed its turn.", _0)]
+  CreatureNotDelayed(CreatureID),
+  #[fail(display = "Creature {:?} can't delay a turn it shares with its group.", _0)]
+  CannotDelayGroupTurn(CreatureID),
+  #[fail(display = "Creature {:?} isn't taking part in the current turn.", _0)]
+  CreatureNotInTurn(CreatureID),
   #[fail(display = "There is currently no combat.")]
   NotInCombat,
   #[fail(display = "Creature {:?} is already in combat.", _0)]
@@ -756,6 +909,8 @@ pub enum GameError {
 
   #[fail(display = "No module source found")]
   NoModuleSource,
+  #[fail(display = "Couldn't parse dice expression '{}': {}", _0, _1)]
+  InvalidDice(String, String),
 
   // Wrappers for other errors:
   #[fail(display = "FolderTree error: {}", _0)]
@@ -777,6 +932,10 @@ impl<'a> From<&'a str> for GameError {
 pub enum PotentialTargets {
   CreatureIDs(Vec<CreatureID>),
   Points(Vec<Point3>),
+  /// The potential targets for each part of an `Action::Multi`, labeled with the part's name.
+  /// These are all based on the scene as it is before the action, so they're only exact for the
+  /// first part.
+  Multi(Vec<(String, PotentialTargets)>),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -786,6 +945,17 @@ pub struct Ability {
   pub cost: Energy,
   pub action: Action,
   pub usable_ooc: bool,
+  /// How any volume this ability targets interacts with terrain.
+  #[serde(default)]
+  pub propagation: VolumePropagation,
+  /// How many rounds must pass before this ability can be used again. The round it's used in
+  /// counts, so a creature that uses an ability with a cooldown of 2 on its turn can use it again
+  /// on its turn after next.
+  #[serde(default)]
+  pub cooldown: u8,
+  /// Which of the creature's actions for the turn using this ability takes up in combat.
+  #[serde(default)]
+  pub slot: ActionSlot,
 }
 
 impl DeriveKey for Ability {
@@ -793,13 +963,38 @@ impl DeriveKey for Ability {
   fn derive_key(&self) -> AbilityID { self.id }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ActionSlot {
+  Standard,
+  Minor,
+  /// Abilities that don't take up any of the creature's actions, and can be used as often as the
+  /// creature can pay for them.
+  Free,
+}
+
+impl Default for ActionSlot {
+  fn default() -> ActionSlot { ActionSlot::Standard }
+}
+
+/// How many actions of each kind a creature gets on each of its turns in combat.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ActionBudget {
+  pub standard: u8,
+  pub minor: u8,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Action {
   Creature { effect: CreatureEffect, target: CreatureTarget },
   SceneVolume { effect: SceneEffect, target: SceneTarget },
-  // Multi will require DecidedTarget::Multi
-  // also PotentialTargets::Multi(Vec<(String, PotentialTarget)>)
-  // Multi(Vec<(String, Action)>),
+  /// Walk the actor to the targeted point, along a path no longer than `distance`. This doesn't use
+  /// up any of the actor's movement for the turn, so it's useful for dashing in before a strike.
+  /// Like any other movement in combat, it can provoke opportunity attacks.
+  MoveActor { distance: u32units::Length },
+  /// Several named actions that happen one after another, each with its own target. Each part
+  /// sees the results of the parts before it, and if any part fails, none of them happen. If a part
+  /// sets off an interrupt in combat, the parts after it are forfeited.
+  Multi(Vec<(String, Action)>),
 }
 
 /// A target specifier for actions that ultimately affect creatures.
@@ -807,16 +1002,23 @@ pub enum Action {
 /// ultimately the target is resolved into one or more creatures which `CreatureEffect`s will be
 /// applied to. For example, `LineFromActor` is specified by the client as a Point, but will
 /// affect the creatures in that line.
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
 pub enum CreatureTarget {
-  Melee,
-  Range(u32units::Length),
+  Melee {
+    filter: TargetFilter,
+  },
+  Range {
+    distance: u32units::Length,
+    filter: TargetFilter,
+  },
   Actor,
   /// A *piercing* line, from an actor, which is always a fixed length.
   /// When targeted at a point, it will continue through any creatures up to *and past* that point,
   /// up to the maximum distance.
   LineFromActor {
     distance: u32units::Length,
+    #[serde(default)]
+    filter: TargetFilter,
   },
   // LineFromActorToCreature{ distance: u32units::Length },
   SomeCreaturesInVolumeInRange {
@@ -824,11 +1026,117 @@ pub enum CreatureTarget {
     /// maximum number of creatures that can be hit
     maximum: u8,
     range: u32units::Length,
+    #[serde(default)]
+    filter: TargetFilter,
   },
   AllCreaturesInVolumeInRange {
     volume: Volume,
     range: u32units::Length,
+    #[serde(default)]
+    filter: TargetFilter,
+  },
+}
+
+impl CreatureTarget {
+  /// Which creatures the target can affect, from the point of view of the creature using it.
+  /// An `Actor` target only ever hits the actor, so it doesn't filter.
+  pub fn filter(&self) -> TargetFilter {
+    match *self {
+      CreatureTarget::Melee { filter }
+      | CreatureTarget::Range { filter, .. }
+      | CreatureTarget::LineFromActor { filter, .. }
+      | CreatureTarget::SomeCreaturesInVolumeInRange { filter, .. }
+      | CreatureTarget::AllCreaturesInVolumeInRange { filter, .. } => filter,
+      CreatureTarget::Actor => TargetFilter::Any,
+    }
+  }
+}
+
+/// How `CreatureTarget`s are written now. Its `Deserialize` tries this before the older forms.
+#[derive(Deserialize)]
+#[serde(remote = "CreatureTarget")]
+#[allow(dead_code)]
+enum CreatureTargetDef {
+  Melee {
+    #[serde(default)]
+    filter: TargetFilter,
+  },
+  Range {
+    distance: u32units::Length,
+    #[serde(default)]
+    filter: TargetFilter,
   },
+  Actor,
+  LineFromActor {
+    distance: u32units::Length,
+    #[serde(default)]
+    filter: TargetFilter,
+  },
+  SomeCreaturesInVolumeInRange {
+    volume: Volume,
+    maximum: u8,
+    range: u32units::Length,
+    #[serde(default)]
+    filter: TargetFilter,
+  },
+  AllCreaturesInVolumeInRange {
+    volume: Volume,
+    range: u32units::Length,
+    #[serde(default)]
+    filter: TargetFilter,
+  },
+}
+
+/// Before they could be filtered, `Melee` and `Range` targets were written as just `Melee` and
+/// `Range: 500`. Modules and saved games that use those forms still load, without a filter.
+impl<'de> de::Deserialize<'de> for CreatureTarget {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: de::Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    enum Unfiltered {
+      Melee,
+      Range(u32units::Length),
+    }
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Format {
+      Current(#[serde(with = "CreatureTargetDef")] CreatureTarget),
+      Unfiltered(Unfiltered),
+    }
+    Ok(match Format::deserialize(deserializer)? {
+      Format::Current(target) => target,
+      Format::Unfiltered(Unfiltered::Melee) => CreatureTarget::Melee { filter: TargetFilter::Any },
+      Format::Unfiltered(Unfiltered::Range(distance)) => {
+        CreatureTarget::Range { distance, filter: TargetFilter::Any }
+      }
+    })
+  }
+}
+
+/// Limits the creatures that an ability affects, based on how the actor regards them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TargetFilter {
+  Any,
+  /// Only the actor and its allies.
+  Allies,
+  /// Only creatures the actor is hostile to.
+  Enemies,
+}
+
+impl Default for TargetFilter {
+  fn default() -> TargetFilter { TargetFilter::Any }
+}
+
+impl TargetFilter {
+  pub fn allows(self, relationship: Relationship) -> bool {
+    match self {
+      TargetFilter::Any => true,
+      TargetFilter::Allies => relationship == Relationship::Ally,
+      TargetFilter::Enemies => relationship == Relationship::Enemy,
+    }
+  }
 }
 
 /// A target specifier for actions that ultimately affect the scene by way of `SceneEffect`.
@@ -842,7 +1150,11 @@ pub enum SceneTarget {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SceneEffect {
   CreateVolumeCondition { duration: Duration, condition: Condition },
-  // Another example of a SceneEffect would be DestroyTerrain or BuildTerrain
+  /// Open up every closed tile in the volume on the targeted level, like blasting a hole in a wall.
+  CarveTerrain,
+  /// Fill in every open tile in the volume on the targeted level that no creature is standing on,
+  /// like raising a wall of stone.
+  RaiseTerrain,
 }
 
 /// The target of an ability, as chosen at play-time by a player. Generally this falls into
@@ -855,6 +1167,8 @@ pub enum DecidedTarget {
   Creatures(Vec<CreatureID>),
   Actor,
   Point(Point3),
+  /// One decision for each part of an `Action::Multi`, in the same order as the parts.
+  Multi(Vec<DecidedTarget>),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -866,11 +1180,29 @@ pub enum CreatureEffect {
   Damage(Dice),
   MultiEffect(Vec<CreatureEffect>),
   GenerateEnergy(Energy),
+  // Forced movement moves the target relative to the creature using the ability, so it only has
+  // an effect when it's part of an ability.
+  /// Shove the target directly away from the actor. It stops early if it runs into something.
+  Push(u32units::Length),
+  /// Drag the target directly toward the actor, stopping when it's next to the actor.
+  Pull(u32units::Length),
+  /// Like `Push`, but the target takes `impact` damage if it runs into something before it's gone
+  /// the whole distance.
+  Knockback {
+    distance: u32units::Length,
+    impact: Dice,
+  },
+  /// Move the target directly away from the actor without passing through anything in between.
+  /// It lands as far along that line as it fits.
+  Teleport(u32units::Length),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Condition {
+  /// An effect that happens to the creature at the end of each of its turns.
   RecurringEffect(Box<CreatureEffect>),
+  /// An effect that happens to the creature at some other point in the turn order.
+  RecurringEffectAt(TickTiming, Box<CreatureEffect>),
   Dead,
   Incapacitated,
   AddDamageBuff(HP),
@@ -882,6 +1214,25 @@ impl Condition {
   pub fn apply(&self, duration: Duration) -> AppliedCondition {
     AppliedCondition { remaining: duration, condition: self.clone() }
   }
+
+  /// The effect this condition has over time, if any, and when it happens.
+  pub fn recurring_effect(&self) -> Option<(TickTiming, &CreatureEffect)> {
+    match *self {
+      Condition::RecurringEffect(ref effect) => Some((TickTiming::EndOfTurn, effect)),
+      Condition::RecurringEffectAt(timing, ref effect) => Some((timing, effect)),
+      _ => None,
+    }
+  }
+}
+
+/// The points in combat at which conditions can tick.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TickTiming {
+  StartOfTurn,
+  EndOfTurn,
+  /// This happens for every combatant as each round starts, including when combat starts. So
+  /// conditions and cooldowns have already counted down once by the time round 1 gets going.
+  StartOfRound,
 }
 
 /// Serializes as either "Interminate" or {"Duration": 0}
@@ -913,6 +1264,24 @@ pub enum Volume {
   AABB(AABB),
 }
 
+/// How the effect of a volume-targeting ability makes its way out from the point it's centered on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum VolumePropagation {
+  /// The volume ignores terrain entirely.
+  Unobstructed,
+  /// The effect is cast outward from the center, and is blocked by any solid terrain, like a
+  /// grenade. Only things in line of sight of the center are affected.
+  Burst,
+  /// The effect crawls out from the center along open terrain, so it can wrap around corners but
+  /// can't pass through walls, like a fireball. Only things with a path from the center no longer
+  /// than the volume's reach are affected.
+  Spreading,
+}
+
+impl Default for VolumePropagation {
+  fn default() -> VolumePropagation { VolumePropagation::Unobstructed }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct AbilityStatus {
   pub ability_id: AbilityID,
@@ -935,6 +1304,14 @@ pub struct Class {
   pub conditions: Vec<Condition>,
   /// An SVG-compatible color specifier
   pub color: Color,
+  /// The ability that creatures of this class use against hostile creatures which leave their
+  /// melee reach during combat. Classes without one don't have a zone of control.
+  #[serde(default)]
+  pub opportunity_attack: Option<AbilityID>,
+  /// How much energy creatures of this class regain at the start of every round of combat,
+  /// including the first one.
+  #[serde(default)]
+  pub energy_regen: Energy,
 }
 
 impl DeriveKey for Class {
@@ -942,6 +1319,15 @@ impl DeriveKey for Class {
   fn derive_key(&self) -> ClassID { self.id }
 }
 
+/// How thoroughly a rest restores a creature.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RestKind {
+  /// Restores energy and resets ability cooldowns.
+  Short,
+  /// Everything a short rest does, and also restores health.
+  Long,
+}
+
 /// A specification for creating a new creature.
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct CreatureCreation {
@@ -986,6 +1372,36 @@ pub struct Creature {
   pub size: AABB,
   #[serde(default)]
   pub inventory: Inventory,
+  /// The faction the creature belongs to, which decides who it's friends and enemies with.
+  #[serde(default)]
+  pub faction: Option<FactionID>,
+  /// How many more rounds each ability is cooling down for. Abilities that aren't cooling down
+  /// aren't listed.
+  #[serde(default)]
+  pub cooldowns: HashMap<AbilityID, u8>,
+}
+
+/// A group of creatures that are on the same side, along with how it regards other factions.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Faction {
+  pub id: FactionID,
+  pub name: String,
+  /// How members of this faction regard members of other factions. Factions that aren't listed
+  /// here are enemies.
+  #[serde(default)]
+  pub relationships: HashMap<FactionID, Relationship>,
+}
+
+impl DeriveKey for Faction {
+  type KeyType = FactionID;
+  fn derive_key(&self) -> FactionID { self.id }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Relationship {
+  Ally,
+  Neutral,
+  Enemy,
 }
 
 /// A definition of an Item, which can be referenced by creatures' inventories.
@@ -1007,7 +1423,95 @@ pub struct AttrID(pub String);
 pub struct Combat {
   pub scene: SceneID,
   pub creatures: nonempty::NonEmptyWithCursor<(CreatureID, i16)>,
+  /// The current round, counting from 1.
+  #[serde(default = "first_round")]
+  pub round: u32,
   pub movement_used: u32units::Length,
+  /// Reactions which have been triggered during the current turn, in the order they happened.
+  #[serde(default)]
+  pub pending_interrupts: Vec<Interrupt>,
+  /// How many reactions each creature has used since the start of its last turn.
+  #[serde(default)]
+  pub reactions_used: HashMap<CreatureID, u8>,
+  /// Abilities that creatures are waiting to use until something happens. These last until the
+  /// start of the creature's next turn.
+  #[serde(default)]
+  pub readied_actions: HashMap<CreatureID, ReadiedAction>,
+  /// Volume conditions whose recurring effects have already hit each creature taking the current
+  /// turn, so that standing in one for a whole turn doesn't set it off at both ends.
+  #[serde(default)]
+  pub volume_conditions_applied: HashMap<CreatureID, HashSet<ConditionID>>,
+  /// How many actions of each kind every creature gets per turn, if that's limited at all.
+  #[serde(default)]
+  pub action_budget: Option<ActionBudget>,
+  /// How many actions of each kind the current creature has used this turn.
+  #[serde(default)]
+  pub actions_used: HashMap<ActionSlot, u8>,
+  /// How far the other creatures sharing the current group turn have moved so far. The current
+  /// creature's movement is in `movement_used`.
+  #[serde(default)]
+  pub group_movement_used: HashMap<CreatureID, u32units::Length>,
+  /// How many actions the other creatures sharing the current group turn have used so far. The
+  /// current creature's actions are in `actions_used`.
+  #[serde(default)]
+  pub group_actions_used: HashMap<CreatureID, HashMap<ActionSlot, u8>>,
+  /// Creatures that have put off their turns, and are out of the order until they resume them.
+  #[serde(default)]
+  pub delayed: Vec<CreatureID>,
+  /// Creatures that have come back into the order after delaying. They already went through the
+  /// start of their turns, so that doesn't happen again when their turns come up.
+  #[serde(default)]
+  pub resuming: Vec<CreatureID>,
+  /// How initiative is rolled and the turn order is arranged.
+  #[serde(default)]
+  pub initiative_mode: InitiativeMode,
+}
+
+fn first_round() -> u32 { 1 }
+
+/// How creatures' initiative is rolled at the start of combat (and when it's rerolled).
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum InitiativeMode {
+  /// Every creature rolls its own initiative.
+  Individual,
+  /// Creatures of the same class share one initiative roll and take a single turn together.
+  GroupByClass,
+  /// Each group of creatures shares one initiative roll and takes a single turn together.
+  /// Creatures that aren't in any group roll on their own.
+  Groups(Vec<Vec<CreatureID>>),
+  /// Turns alternate between the sides, starting with the side that has the best initiative.
+  /// Each side's creatures still go in the order of their own rolls, and creatures that aren't on
+  /// any side make up one more side.
+  Sides(Vec<Vec<CreatureID>>),
+}
+
+impl Default for InitiativeMode {
+  fn default() -> InitiativeMode { InitiativeMode::Individual }
+}
+
+/// An ability that a creature gets to use in the middle of someone else's turn, in reaction to
+/// something they did.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Interrupt {
+  pub creature_id: CreatureID,
+  pub ability_id: AbilityID,
+  pub target: DecidedTarget,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ReadiedAction {
+  pub ability_id: AbilityID,
+  pub target: DecidedTarget,
+  pub trigger: ReadyTrigger,
+}
+
+/// Something which sets off a readied action.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ReadyTrigger {
+  /// The creature moves, for any reason.
+  CreatureMoves(CreatureID),
+  /// The creature uses an ability during its turn.
+  CreatureActs(CreatureID),
 }
 
 impl DeriveKey for Creature {
@@ -1032,6 +1536,25 @@ pub struct Game {
   // goes.
   #[serde(default)]
   pub active_scene: Option<SceneID>,
+  #[serde(default)]
+  pub ooc_ability_cost: OutOfCombatCost,
+  #[serde(default)]
+  pub factions: IndexedHashMap<Faction>,
+}
+
+/// Whether abilities used outside of combat are paid for like they are in combat.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OutOfCombatCost {
+  /// Abilities are free to use outside of combat, and don't start their cooldowns.
+  Free,
+  /// Abilities cost energy and start their cooldowns whether or not the creature is in combat.
+  /// Cooldowns only count down as combat rounds start, though, so one started outside of combat
+  /// lasts until the creature rests or fights through enough rounds.
+  Charged,
+}
+
+impl Default for OutOfCombatCost {
+  fn default() -> OutOfCombatCost { OutOfCombatCost::Free }
 }
 
 pub struct Runtime {
@@ -1073,11 +1596,54 @@ pub struct SceneCreation {
   pub background_image_scale: (i32, i32),
 }
 
+/// A special kind of terrain at a tile.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TerrainCell {
+  /// Terrain which can be moved through, but multiplies the cost of moving into it.
+  Difficult(u8),
+  /// Water must be swum through, which costs twice as much as walking.
+  Water,
+  /// A door only lets creatures (and line of sight) through when it's open.
+  Door { open: bool },
+  /// A wall which blocks the tile until it's destroyed.
+  DestructibleWall,
+  /// Open terrain, like lava or a patch of poison gas, which has an effect on each creature
+  /// standing in it at the start of that creature's turns in combat.
+  Hazard(CreatureEffect),
+}
+
+impl TerrainCell {
+  /// How many times more it costs to move into this cell than into plain open terrain.
+  pub fn cost_multiplier(&self) -> u32 {
+    match *self {
+      TerrainCell::Difficult(multiplier) => u32::from(multiplier).max(1),
+      TerrainCell::Water => 2,
+      TerrainCell::Door { .. } | TerrainCell::DestructibleWall | TerrainCell::Hazard(_) => 1,
+    }
+  }
+
+  /// Whether the tile with this cell is open terrain.
+  pub fn is_open(&self) -> bool {
+    match *self {
+      TerrainCell::Door { open } => open,
+      TerrainCell::DestructibleWall => false,
+      TerrainCell::Difficult(_) | TerrainCell::Water | TerrainCell::Hazard(_) => true,
+    }
+  }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Scene {
   pub id: SceneID,
   pub name: String,
-  pub terrain: Vec<Point3>,
+  pub terrain: Terrain,
+  /// Tiles which lead up to the tile directly above them: stairs, ramps, ladders, and so on.
+  #[serde(default)]
+  pub stairs: HashSet<Point3>,
+  /// Tiles which are more than just open space or walls. `terrain` is kept in sync with these, so
+  /// closed doors and intact walls aren't open terrain.
+  #[serde(default)]
+  pub terrain_cells: HashMap<Point3, TerrainCell>,
   pub highlights: HashMap<Point3, (Color, Visibility)>,
   pub annotations: HashMap<Point3, (String, Visibility)>,
 
@@ -1173,10 +1739,21 @@ impl<'a> ser::Serialize for RPIApp<'a> {
 
 impl<'a> ser::Serialize for RPIGame<'a> {
   fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-    let mut str = serializer.serialize_struct("Game", 10)?;
+    let mut str = serializer.serialize_struct("Game", 13)?;
     let game = self.0;
 
     str.serialize_field("current_combat", &game.current_combat)?;
+    str.serialize_field(
+      "actions_left",
+      &game.current_combat.as_ref().and_then(|combat| combat.actions_left()),
+    )?;
+    let turn_members = match game.current_combat {
+      Some(ref combat) => combat
+        .turn_members(game)
+        .map_err(|e| S::Error::custom(&format!("Couldn't find the turn's members: {:?}", e)))?,
+      None => vec![],
+    };
+    str.serialize_field("turn_members", &turn_members)?;
     str.serialize_field("abilities", &game.abilities)?;
     str.serialize_field(
       "creatures",
@@ -1190,13 +1767,15 @@ impl<'a> ser::Serialize for RPIGame<'a> {
     str.serialize_field("campaign", &game.campaign)?;
     str.serialize_field("items", &game.items)?;
     str.serialize_field("players", &game.players)?;
+    str.serialize_field("ooc_ability_cost", &game.ooc_ability_cost)?;
+    str.serialize_field("factions", &game.factions)?;
     str.end()
   }
 }
 
 impl<'creature, 'game: 'creature> ser::Serialize for DynamicCreature<'creature, 'game> {
   fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-    let mut str = serializer.serialize_struct("Creature", 21)?;
+    let mut str = serializer.serialize_struct("Creature", 22)?;
     let creat = &self.creature;
     str.serialize_field("id", &creat.id)?;
     str.serialize_field("name", &creat.name)?;
@@ -1219,6 +1798,7 @@ impl<'creature, 'game: 'creature> ser::Serialize for DynamicCreature<'creature,
     str.serialize_field("initiative", &creat.initiative)?;
     str.serialize_field("size", &creat.size)?;
     str.serialize_field("inventory", &creat.inventory)?;
+    str.serialize_field("faction", &creat.faction)?;
     str.end()
   }
 }
@@ -1331,6 +1911,8 @@ pub mod test {
       inventory: HashMap::new(),
       volume_conditions: HashMap::new(),
       focused_creatures: vec![],
+      stairs: HashSet::new(),
+      terrain_cells: HashMap::new(),
     }
   }
 
@@ -1355,8 +1937,11 @@ pub mod test {
       name: "Punch".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      propagation: VolumePropagation::Unobstructed,
+      cooldown: 0,
+      slot: ActionSlot::Standard,
       action: Action::Creature {
-        target: CreatureTarget::Melee,
+        target: CreatureTarget::Melee { filter: TargetFilter::Any },
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
     }
@@ -1368,8 +1953,11 @@ pub mod test {
       name: "Shoot".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      propagation: VolumePropagation::Unobstructed,
+      cooldown: 0,
+      slot: ActionSlot::Standard,
       action: Action::Creature {
-        target: CreatureTarget::Range(u32cm(500)),
+        target: CreatureTarget::Range { distance: u32cm(500), filter: TargetFilter::Any },
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
     }
@@ -1381,8 +1969,11 @@ pub mod test {
       name: "Heal".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      propagation: VolumePropagation::Unobstructed,
+      cooldown: 0,
+      slot: ActionSlot::Standard,
       action: Action::Creature {
-        target: CreatureTarget::Range(u32cm(500)),
+        target: CreatureTarget::Range { distance: u32cm(500), filter: TargetFilter::Any },
         effect: CreatureEffect::Heal(Dice::flat(3)),
       },
     }
@@ -1394,10 +1985,14 @@ pub mod test {
       name: "Fireball".to_string(),
       cost: Energy(8),
       usable_ooc: true,
+      propagation: VolumePropagation::Unobstructed,
+      cooldown: 0,
+      slot: ActionSlot::Standard,
       action: Action::Creature {
         target: CreatureTarget::AllCreaturesInVolumeInRange {
           volume: Volume::Sphere(u32cm(1000)),
           range: u32cm(2000),
+          filter: TargetFilter::Any,
         },
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
@@ -1410,8 +2005,11 @@ pub mod test {
       name: "Piercing Shot".to_string(),
       cost: Energy(8),
       usable_ooc: true,
+      propagation: VolumePropagation::Unobstructed,
+      cooldown: 0,
+      slot: ActionSlot::Standard,
       action: Action::Creature {
-        target: CreatureTarget::LineFromActor { distance: u32cm(1000) },
+        target: CreatureTarget::LineFromActor { distance: u32cm(1000), filter: TargetFilter::Any },
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
     }
@@ -1423,6 +2021,9 @@ pub mod test {
       name: "Thorn Patch".to_string(),
       cost: Energy(8),
       usable_ooc: true,
+      propagation: VolumePropagation::Unobstructed,
+      cooldown: 0,
+      slot: ActionSlot::Standard,
       action: Action::SceneVolume {
         target: SceneTarget::RangedVolume {
           volume: Volume::Sphere(u32cm(200)),
@@ -1465,15 +2066,26 @@ pub mod test {
   }
 
   #[test]
-  fn dice_plus() {
-    let d = Dice::flat(1).plus(Dice::flat(1));
-    assert_eq!(d.roll(), (vec![1, 1], 2));
+  fn deserialize_terrain_from_list() {
+    let terrain: Terrain = serde_yaml::from_str("- 0/0/0\n- 100/0/0\n- 0/0/0").unwrap();
+    assert_eq!(terrain, hashset!{Point3::new(0, 0, 0), Point3::new(100, 0, 0)});
   }
 
   #[test]
-  fn dice_negative() {
-    let d = Dice::flat(1).plus(Dice::flat(-5));
-    assert_eq!(d.roll(), (vec![1, -5], -4));
+  fn deserialize_single_creature_targets() {
+    let target: CreatureTarget = serde_yaml::from_str("Melee").unwrap();
+    assert_eq!(target, CreatureTarget::Melee { filter: TargetFilter::Any });
+    let target: CreatureTarget = serde_yaml::from_str("Range: 500").unwrap();
+    assert_eq!(target, CreatureTarget::Range { distance: u32cm(500), filter: TargetFilter::Any });
+    let target: CreatureTarget =
+      serde_yaml::from_str("Range: {distance: 500, filter: Enemies}").unwrap();
+    let enemies = TargetFilter::Enemies;
+    assert_eq!(target, CreatureTarget::Range { distance: u32cm(500), filter: enemies });
+    let target: CreatureTarget = serde_yaml::from_str("Melee: {}").unwrap();
+    assert_eq!(target, CreatureTarget::Melee { filter: TargetFilter::Any });
+    let target = CreatureTarget::Melee { filter: TargetFilter::Allies };
+    let json = serde_json::to_string(&target).unwrap();
+    assert_eq!(serde_json::from_str::<CreatureTarget>(&json).unwrap(), target);
   }
 
   #[test]
@@ -144,6 +144,18 @@ handle_actor! {
   }
 }
 
+pub struct VisibleTiles {
+  pub creature_id: types::CreatureID,
+  pub scene_id: types::SceneID,
+}
+handle_actor! {
+  VisibleTiles => String, Error;
+  fn handle(&mut self, cmd: VisibleTiles, _: &mut Context<Self>) -> Self::Result {
+    let tiles = &self.app.get_visible_tiles(cmd.scene_id, cmd.creature_id)?;
+    serde_json::to_string(tiles).map_err(From::from)
+  }
+}
+
 pub struct CombatMovementOptions;
 handle_actor! {
   CombatMovementOptions => String, Error;
@@ -168,6 +180,24 @@ handle_actor! {
   }
 }
 
+pub struct MultiTargetOptions {
+  pub creature_id: types::CreatureID,
+  pub scene_id: types::SceneID,
+  pub ability_id: types::AbilityID,
+  pub decided: Vec<types::DecidedTarget>,
+}
+handle_actor! {
+  MultiTargetOptions => String, Error;
+  fn handle(&mut self, cmd: MultiTargetOptions, _: &mut Context<AppActor>) -> Self::Result {
+    Ok(serde_json::to_string(&self.app.get_multi_part_target_options(
+      cmd.scene_id,
+      cmd.creature_id,
+      cmd.ability_id,
+      &cmd.decided,
+    )?)?)
+  }
+}
+
 pub struct PreviewVolumeTargets {
   pub scene_id: types::SceneID,
   pub actor_id: types::CreatureID,
@@ -9,7 +9,7 @@ use failure::Error;
 use futures::Future;
 use http::{header, Method};
 
-use pandt::types::{CreatureID, GameCommand, ModuleSource, Point3, SceneID};
+use pandt::types::{CreatureID, DecidedTarget, GameCommand, ModuleSource, Point3, SceneID};
 
 use super::PT;
 use actor;
@@ -30,7 +30,11 @@ pub fn router(pt: PT) -> Application<PT> {
     .resource("/poll/{snapshot_len}/{log_len}", |r| r.route().f(poll_app))
     .resource("/movement_options/{scene_id}/{cid}", |r| r.route().f(movement_options))
     .resource("/combat_movement_options", |r| r.route().f(combat_movement_options))
+    .resource("/visible_tiles/{scene_id}/{cid}", |r| r.route().f(visible_tiles))
     .resource("/target_options/{scene_id}/{cid}/{abid}", |r| r.route().f(target_options))
+    .resource("/multi_target_options/{scene_id}/{cid}/{abid}", |r| {
+      r.method(Method::POST).f(multi_target_options)
+    })
     .resource("/preview_volume_targets/{scene_id}/{actor_id}/{ability_id}/{x}/{y}/{z}", |r| {
       r.f(preview_volume_targets)
     })
@@ -83,6 +87,15 @@ fn movement_options(req: HttpRequest<PT>) -> AsyncRPIResponse {
   )
 }
 
+fn visible_tiles(req: HttpRequest<PT>) -> AsyncRPIResponse {
+  let creature_id: CreatureID = try_fut!(parse_arg(&req, "cid"));
+  let scene_id: SceneID = try_fut!(parse_arg(&req, "scene_id"));
+  invoke_actor_string_result(
+    &req.state().app_address,
+    actor::VisibleTiles { creature_id, scene_id },
+  )
+}
+
 fn combat_movement_options(req: HttpRequest<PT>) -> AsyncRPIResponse {
   invoke_actor_string_result(&req.state().app_address, actor::CombatMovementOptions)
 }
@@ -97,6 +110,22 @@ fn target_options(req: HttpRequest<PT>) -> AsyncRPIResponse {
   )
 }
 
+/// Get the target options for the next part of a multi-part ability. The body is the list of
+/// targets already decided for the parts before it.
+fn multi_target_options(req: HttpRequest<PT>) -> AsyncRPIResponse {
+  let scene_id = try_fut!(parse_arg(&req, "scene_id"));
+  let creature_id = try_fut!(parse_arg(&req, "cid"));
+  let ability_id = try_fut!(parse_arg(&req, "abid"));
+  let app_address = req.state().app_address.clone();
+  let f = req.json().from_err().and_then(move |decided: Vec<DecidedTarget>| -> AsyncRPIResponse {
+    invoke_actor_string_result(
+      &app_address,
+      actor::MultiTargetOptions { scene_id, creature_id, ability_id, decided },
+    )
+  });
+  Box::new(f)
+}
+
 fn preview_volume_targets(req: HttpRequest<PT>) -> AsyncRPIResponse {
   let scene_id = try_fut!(parse_arg(&req, "scene_id"));
   let actor_id = try_fut!(parse_arg(&req, "actor_id"));
@@ -353,6 +353,7 @@ export class PositiveIntegerInput
 export function conditionIcon(cond: T.Condition): string {
   switch (cond.t) {
     case "RecurringEffect": return "Recurring effect of some sort";
+    case "RecurringEffectAt": return "Recurring effect of some sort";
     case "Dead": return "💀";
     case "Incapacitated": return "😞";
     case "AddDamageBuff": return "😈";
@@ -436,7 +437,8 @@ interface CombatProps {
   initiative?: (creature: T.Creature, init: number) => JSX.Element;
 }
 export const Combat = M.connectRedux(
-  function Combat({ combat, card, ptui, initiative }: CombatProps & M.ReduxProps): JSX.Element {
+  function Combat(
+    { combat, card, ptui, dispatch, initiative }: CombatProps & M.ReduxProps): JSX.Element {
     const creatures_with_init = M.filterMap(combat.creatures.data,
       ([cid, init]) => {
         const creature = ptui.getCreature(cid);
@@ -445,6 +447,7 @@ export const Combat = M.connectRedux(
 
     const Card = card ? card : CreatureCard;
     return <Segment.Group>
+      <Segment>Round {combat.round}</Segment>
       {creatures_with_init.map(([creature, init], index) => {
         const show_init = initiative ? initiative(creature, init) : null;
         return <Segment.Group key={creature.id} horizontal={true}>
@@ -460,6 +463,19 @@ export const Combat = M.connectRedux(
         </Segment.Group>;
       })
       }
+      {combat.delayed.length > 0 ? <Segment>Delayed</Segment> : null}
+      {M.filterMap(combat.delayed, cid => ptui.getCreature(cid)).map(creature =>
+        <Segment.Group key={creature.id} horizontal={true}>
+          <Segment compact={true}>
+            <Button size="mini"
+              onClick={() =>
+                ptui.sendCommand(dispatch, { t: "ResumeTurn", creature_id: creature.id })}>
+              Resume
+            </Button>
+          </Segment>
+          <Card creature={creature} />
+        </Segment.Group>)
+      }
     </Segment.Group>;
   });
 
@@ -475,8 +491,12 @@ export const ActionBar = M.connectRedux(function ActionBar(
   abilities = LD.sortBy(abilities, abo => abo.ability.name);
 
   let abilityButtons;
+  let memberButtons;
   if (props.combat) {
     const combat = props.combat;
+    const others = props.ptui.app.current_game.turn_members.filter(cid => cid !== props.creature.id);
+    memberButtons = others.map(cid =>
+      <SwitchTurnMemberButton key={cid} creature_id={cid} />);
     abilityButtons = abilities.map(abinfo =>
       <AbilityButton key={abinfo.ability_id} dispatch={props.dispatch}
         creature={props.creature} abinfo={abinfo}
@@ -487,11 +507,24 @@ export const ActionBar = M.connectRedux(function ActionBar(
   return <div style={{ display: "flex" }}>
     <CreatureIcon app={props.ptui.app} creature={props.creature} />
     {props.combat ? <DoneButton dispatch={props.dispatch} /> : <noscript />}
+    {props.combat ? <DelayButton dispatch={props.dispatch} /> : <noscript />}
     <MoveButton creature={props.creature} combat={props.combat} />
     {abilityButtons}
+    {memberButtons}
   </div>;
 });
 
+/** A button to hand the current group turn over to another creature taking it. */
+export const SwitchTurnMemberButton = M.connectRedux(function SwitchTurnMemberButton(
+  props: { creature_id: T.CreatureID } & M.ReduxProps): JSX.Element {
+  const creature = props.ptui.getCreature(props.creature_id);
+  const command: T.GameCommand = { t: "SwitchTurnMember", creature_id: props.creature_id };
+  return <Button style={{ height: "50px", flex: "1" }}
+    onClick={() => props.dispatch(M.sendCommand(command))}>
+    Act with {creature ? creature.name : props.creature_id}
+  </Button>;
+});
+
 function DoneButton({ dispatch }: { dispatch: M.Dispatch }): JSX.Element {
   const command: T.GameCommand = { t: "Done" };
   return <Button style={{ height: "50px", flex: "1" }}
@@ -500,6 +533,14 @@ function DoneButton({ dispatch }: { dispatch: M.Dispatch }): JSX.Element {
   </Button>;
 }
 
+function DelayButton({ dispatch }: { dispatch: M.Dispatch }): JSX.Element {
+  const command: T.GameCommand = { t: "DelayTurn" };
+  return <Button style={{ height: "50px", flex: "1" }}
+    onClick={() => dispatch(M.sendCommand(command))}>
+    Delay
+  </Button>;
+}
+
 interface AbilityButtonProps {
   creature: T.Creature;
   abinfo: { ability_id: T.AbilityID; ability: T.Ability };
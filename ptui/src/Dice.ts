@@ -1,54 +1,106 @@
 import * as P from 'parsimmon';
 import * as T from './PTTypes';
 
+// This grammar mirrors `DiceParser` in pandt/src/dice.rs, so anything the server accepts can be
+// typed in here too. The only difference is that whitespace is allowed between more tokens.
+
 function spaced<X>(parser: P.Parser<X>): P.Parser<X> {
   return P.optWhitespace.then(parser).skip(P.optWhitespace);
 }
 
-const dicep = P.lazy(() => dicep_);
-
-const digits =
-  spaced(
-    P.digits
-      .map(Number)
-      .desc('number'));
-const flat =
-  spaced(
-    digits
-      .map(n => ({ t: 'Flat', val: n }))
-      .desc('Flat'));
-const expr =
-  spaced(
-    P.seq(spaced(digits).skip(spaced(P.string('d'))), spaced(digits))
-      .map(([num, size]): T.Dice => ({ t: "Expr", num, size }))
-      .desc('Expr'));
-
-const plus: P.Parser<T.Dice> =
-  spaced(
-    P.seq(spaced(expr).skip(spaced(P.string('+'))), spaced(dicep))
-      .map(([left, right]): T.Dice => ({ t: "Plus", left, right }))
-      .desc('Plus'));
-
-const minus: P.Parser<T.Dice> =
-  // TODO: the data model doesn't support subtraction *in general* --
-  // i.e. 1d20-1d8 is not representable, but we can support 1d20-2 at least.
-  spaced(
-    P.seq(spaced(expr).skip(spaced(P.string('-'))), spaced(digits))
-      .map(([left, num]): T.Dice => ({ t: "Plus", left, right: { t: "Flat", val: -num } }))
-      .desc('Minus'));
-
-const sum = P.alt(plus, minus);
+function token(s: string): P.Parser<string> {
+  return spaced(P.string(s));
+}
+
+const dicep: P.Parser<T.Dice> = P.lazy(() => dicep_);
+
+const number = P.regexp(/[0-9]+/).map(Number).desc('number');
+const digits = spaced(number);
+
+function flatValue(val: number): P.Parser<T.Dice> {
+  return val < -128 || val > 127
+    ? P.fail("number is out of range")
+    : P.succeed<T.Dice>({ t: "Flat", val });
+}
+
+const flat = digits.chain(flatValue).desc('Flat');
+const negativeFlat = token('-').then(digits).chain(n => flatValue(-n)).desc('Flat');
+
+type Modifier =
+  | { t: "Explode" }
+  | { t: "Reroll"; below: number }
+  | { t: "Keep"; keep: T.Keep };
+
+const modifier: P.Parser<Modifier> = P.alt(
+  P.string('!').map((): Modifier => ({ t: "Explode" })),
+  P.string('r<').then(number).map((below): Modifier => ({ t: "Reroll", below })),
+  P.seq(P.regexp(/k[hl]/), number).map(([k, num]): Modifier =>
+    ({ t: "Keep", keep: { t: k === "kh" ? "Highest" : "Lowest", num } })),
+);
+
+function makeRoll(num: number, size: number, modifiers: Array<Modifier>): P.Parser<T.Dice> {
+  if (num > 255) { return P.fail("too many dice"); }
+  if (size < 1 || size > 255) { return P.fail("dice must have between 1 and 255 sides"); }
+  let explode = false;
+  let reroll_below: number | undefined;
+  let keep: T.Keep | undefined;
+  for (const mod of modifiers) {
+    switch (mod.t) {
+      case "Explode":
+        if (explode) { return P.fail("dice can only explode once"); }
+        if (size < 2) { return P.fail("only dice with at least two sides can explode"); }
+        explode = true;
+        break;
+      case "Reroll":
+        if (reroll_below !== undefined) { return P.fail("dice can only be rerolled once"); }
+        if (mod.below < 2 || mod.below > size) {
+          return P.fail("reroll threshold must be between 2 and the size of the die");
+        }
+        reroll_below = mod.below;
+        break;
+      case "Keep":
+        if (keep !== undefined) { return P.fail("only one 'kh' or 'kl' is allowed"); }
+        if (mod.keep.num > num) { return P.fail("can't keep more dice than are rolled"); }
+        keep = mod.keep;
+        break;
+    }
+  }
+  return !explode && reroll_below === undefined && keep === undefined
+    ? P.succeed<T.Dice>({ t: "Expr", num, size })
+    : P.succeed<T.Dice>({ t: "Roll", num, size, explode, reroll_below, keep });
+}
+
+const roll: P.Parser<T.Dice> =
+  P.seq(digits.atMost(1), token('d').then(number), modifier.many())
+    .chain(([num, size, modifiers]) => makeRoll(num.length ? num[0] : 1, size, modifiers))
+    .desc('Roll');
 
 const bestof: P.Parser<T.Dice> =
-  spaced(
-    P.seq(
-      spaced(P.string("BestOf")), spaced(P.string("(")),
-      spaced(digits),
-      spaced(P.string(",")), spaced(dicep), spaced(P.string(")")))
-      .map(([_, _2, num, _3, dice]): T.Dice => ({ t: "BestOf", num, dice }))
-      .desc("BestOf"));
+  P.seq(token("BestOf").then(token("(")).then(digits), token(",").then(dicep).skip(token(")")))
+    .chain(([num, dice]) =>
+      num === 0 ? P.fail("can't take the best of 0")
+        : num > 255 ? P.fail("number is too large")
+          : P.succeed<T.Dice>({ t: "BestOf", num, dice }))
+    .desc("BestOf");
+
+const parens: P.Parser<T.Dice> = token("(").then(dicep).skip(token(")"));
+
+const term = spaced(P.alt(parens, bestof, negativeFlat, roll, flat));
 
-const dicep_ = P.alt(bestof, sum, expr, flat);
+const plus: P.Parser<(left: T.Dice) => T.Dice> =
+  token('+').then(term).map(right => (left: T.Dice): T.Dice => ({ t: "Plus", left, right }));
+
+const minus: P.Parser<(left: T.Dice) => T.Dice> =
+  token('-').then(P.alt(
+    // `X-3` is represented as `X+(-3)`, but `X-1d4` needs a real subtraction.
+    digits.skip(P.notFollowedBy(token('d'))).chain(n => flatValue(-n))
+      .map(right => (left: T.Dice): T.Dice => ({ t: "Plus", left, right })),
+    term.map(right => (left: T.Dice): T.Dice => ({ t: "Minus", left, right })),
+  ));
+
+const dicep_: P.Parser<T.Dice> =
+  P.seq(term, P.alt(plus, minus).many())
+    .map(([first, rest]) => rest.reduce((left, op) => op(left), first));
 
 export function parse(input: string): T.Dice {
   return dicep.tryParse(input);
@@ -58,22 +110,38 @@ export function maybeParse(input: string): P.Result<T.Dice> {
   return dicep.parse(input);
 }
 
+// This must produce exactly what `Display for Dice` does on the Rust side.
 export function format(d: T.Dice): string {
   switch (d.t) {
     case "Flat": return d.val.toString();
     case "Expr": return (d.num.toString() + "d" + d.size.toString());
     case "Plus":
-      const def = format(d.left) + "+" + format(d.right);
-      switch (d.right.t) {
-        case "Flat":
-          if (d.right.val < 0) {
-            return (format(d.left) + "-" + (-d.right.val));
-          } else {
-            return def;
-          }
-        default: return def;
+      if (d.right.t === "Flat" && d.right.val < 0) {
+        return format(d.left) + "-" + (-d.right.val).toString();
+      }
+      return format(d.left) + "+" + formatOperand(d.right);
+    case "Minus":
+      if (d.right.t === "Flat" && d.right.val < 0) {
+        return format(d.left) + "-(" + d.right.val.toString() + ")";
       }
+      return format(d.left) + "-" + formatOperand(d.right);
     case "BestOf":
       return "BestOf(" + d.num.toString() + ", " + format(d.dice) + ")";
+    case "Roll":
+      let result = d.num.toString() + "d" + d.size.toString();
+      if (d.explode) { result += "!"; }
+      if (d.reroll_below !== undefined) { result += "r<" + d.reroll_below.toString(); }
+      if (d.keep !== undefined) {
+        result += (d.keep.t === "Highest" ? "kh" : "kl") + d.keep.num.toString();
+      }
+      return result;
+  }
+}
+
+// Format the right-hand side of a sum or difference, parenthesizing it if necessary.
+function formatOperand(d: T.Dice): string {
+  switch (d.t) {
+    case "Plus": case "Minus": return "(" + format(d) + ")";
+    default: return format(d);
   }
 }
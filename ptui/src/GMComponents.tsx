@@ -821,12 +821,12 @@ export const GMCombat = M.connectRedux(
     }
   });
 
-class StartCombatComp
-  extends React.Component<{ scene: T.Scene } & M.ReduxProps, { selected: I.Set<T.CreatureID> }> {
+class StartCombatComp extends React.Component<
+  { scene: T.Scene } & M.ReduxProps, { selected: I.Set<T.CreatureID>; group_by_class: boolean }> {
   constructor(props: { scene: T.Scene } & M.ReduxProps) {
     super(props);
     const selected = I.Set(props.ptui.getSceneCreatures(props.scene).map(c => c.id));
-    this.state = { selected };
+    this.state = { selected, group_by_class: false };
   }
 
   componentWillReceiveProps(nextProps: { scene: T.Scene } & M.ReduxProps) {
@@ -837,11 +837,18 @@ class StartCombatComp
 
   render(): JSX.Element {
     const { scene, ptui, dispatch } = this.props;
+    const mode: T.InitiativeMode =
+      this.state.group_by_class ? { t: "GroupByClass" } : { t: "Individual" };
     return <div>
       <Button
         onClick={() => ptui.sendCommand(dispatch,
-          { t: "StartCombat", scene_id: scene.id, creature_ids: this.state.selected.toArray() })}
+          {
+            t: "StartCombat", scene_id: scene.id, creature_ids: this.state.selected.toArray(),
+            mode,
+          })}
       >Start combat</Button>
+      <Checkbox label="Group initiative by class" checked={this.state.group_by_class}
+        onChange={(_, d) => this.setState({ group_by_class: d.checked as boolean })} />
       <SelectSceneCreatures scene={scene}
         selections={this.state.selected}
         add={cid => this.setState({ selected: this.state.selected.add(cid) })}
@@ -307,10 +307,8 @@ export const SceneGrid = M.connectRedux(class SceneGrid
     if (!options) { return; }
     if (!this.state.targeting_point) { return; }
     const target = this.state.targeting_point;
-    const ability_id = options.ability_id;
-    const ability = ptui.getAbility(ability_id);
-    if (!ability) { return; }
-    const action = ability.action;
+    const action = ptui.getTargetedAction();
+    if (!action) { return; }
 
     switch (action.t) {
       case "Creature":
@@ -331,17 +329,21 @@ export const SceneGrid = M.connectRedux(class SceneGrid
           case "RangedVolume":
             return svgVolume("target-volume", action.target.volume, target);
         }
+        return;
+      case "MoveActor":
+      case "Multi":
+        return;
     }
   }
 
   targetClicked(point: T.Point3) {
     const { ptui, dispatch } = this.props;
     const options = ptui.state.grid.target_options!;
-    const ability = ptui.getAbility(options.ability_id);
-    if (!ability) { return; }
-    switch (ability.action.t) {
+    const action = ptui.getTargetedAction();
+    if (!action) { return; }
+    switch (action.t) {
       case "Creature":
-        switch (ability.action.target.t) {
+        switch (action.target.t) {
           case "SomeCreaturesInVolumeInRange":
           case "AllCreaturesInVolumeInRange":
           case "LineFromActor":
@@ -350,12 +352,18 @@ export const SceneGrid = M.connectRedux(class SceneGrid
         }
         break;
       case "SceneVolume":
-        switch (ability.action.target.t) {
+        switch (action.target.t) {
           case "RangedVolume": break;
           default: return;
         }
+        break;
+      case "MoveActor": break;
+      // Parts of multi-part actions are handled one at a time, so they never get here.
+      case "Multi": return;
     }
     this.setState({ targeting_point: point });
+    // The server can only preview the targets of abilities that target a single volume.
+    if (options.multi || action.t === "MoveActor") { return; }
     M.fetchAbilityTargets(dispatch, ptui.rpi_url, this.props.scene.id, options.cid,
       options.ability_id, point).then(
         ({ points, creatures }) =>
@@ -472,6 +480,7 @@ export const SceneGrid = M.connectRedux(class SceneGrid
     onClick: (pt: T.Point3) => void): JSX.Element[] | undefined {
     switch (options.t) {
       case "CreatureIDs": return undefined;
+      case "Multi": return undefined;
       case "Points":
         return options.points.map(pt => {
           const rprops = tile_props("pink", pt, { x: 1, y: 1 }, 0.3);
@@ -509,7 +518,14 @@ export const SceneGrid = M.connectRedux(class SceneGrid
        <Button onClick={() => dispatch({ type: 'ClearMovementOptions' })}>Cancel</Button>
       </div>;
     }
-    if (ptui.state.grid.target_options) {
+    const target_options = ptui.state.grid.target_options;
+    if (target_options && target_options.multi) {
+      const part_name = target_options.multi.part_names[target_options.multi.decided.length];
+      return <div>Select a target for {part_name} or
+      <Button onClick={() => dispatch({ type: 'ClearPotentialTargets' })}>Cancel</Button>
+      </div>;
+    }
+    if (target_options) {
       return <div>Select a target or
       <Button onClick={() => dispatch({ type: 'ClearPotentialTargets' })}>Cancel</Button>
       </div>;
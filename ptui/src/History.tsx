@@ -41,6 +41,8 @@ export function GameLog(props: { log: T.GameLog; creatures: I.Map<T.CreatureID,
   switch (log.t) {
     case "SetActiveScene":
       return <div>Set the active scene</div>;
+    case "SetOutOfCombatCost":
+      return <div>Abilities out of combat are now {log.cost === "Free" ? "free" : "charged"}</div>;
     case "RegisterPlayer":
       return <div>Registered player {log.player_id}</div>;
     case "UnregisterPlayer":
@@ -60,6 +62,8 @@ export function GameLog(props: { log: T.GameLog; creatures: I.Map<T.CreatureID,
         <div>Creature ID: {log.cid}</div>
         <div>Success? {log.success.toString()}</div>
       </div>;
+    case "DiceSeed":
+      return null;
     case "CreateFolder":
       return <div><div>Created Folder</div><div>{M.folderPathToString(log.path)}</div></div>;
     case "RenameFolder":
@@ -104,8 +108,26 @@ export function GameLog(props: { log: T.GameLog; creatures: I.Map<T.CreatureID,
       return <div>Changed focused creatures in a scene</div>;
     case "RemoveSceneVolumeCondition":
       return <div>Removed a volume condition from a scene</div>;
+    case "DecrementVolumeConditionRemaining":
+      return null;
     case "EditSceneTerrain":
       return <div>Edited a scene's terrain</div>;
+    case "EditSceneTerrainLevel":
+      return <div>Edited a scene's terrain on level {log.level / 100}</div>;
+    case "EditSceneStairs":
+      return <div>Edited a scene's stairs</div>;
+    case "EditSceneTerrainCells":
+      return <div>Edited a scene's terrain cells</div>;
+    case "OpenDoor":
+      return <div>Opened a door</div>;
+    case "CloseDoor":
+      return <div>Closed a door</div>;
+    case "DestroyWall":
+      return <div>Destroyed a wall</div>;
+    case "AddSceneTerrain":
+      return <div>Opened up {log.terrain.size} tiles of terrain</div>;
+    case "RemoveSceneTerrain":
+      return <div>Filled in {log.terrain.size} tiles of terrain</div>;
     case "EditSceneHighlights":
       return <div>Edited a scene's highlights</div>;
     case "EditSceneAnnotations":
@@ -131,6 +153,12 @@ export function GameLog(props: { log: T.GameLog; creatures: I.Map<T.CreatureID,
       return <div>Added a creature to combat</div>;
     case "RemoveCreatureFromCombat":
       return <div>Removed a creature from combat</div>;
+    case "SetCreatureFaction":
+      return <div>Changed a creature's faction</div>;
+    case "CreateFaction":
+      return <div>Created faction {log.faction.name}</div>;
+    case "SetFactionRelationship":
+      return <div>Changed how a faction regards another</div>;
     case "CombatLog":
       return combat_log(log.log);
     case "StopCombat":
@@ -159,6 +187,30 @@ function combat_log(log: T.CombatLog): JSX.Element | null {
       return <div>Forced move to previous creature in combat</div>;
     case "RerollInitiative":
       return <div>Rerolled initiative for all creatures</div>;
+    case "QueueInterrupt":
+      return <div>Movement provoked a reaction</div>;
+    case "ResolveInterrupt":
+      return <div>Resolved a reaction</div>;
+    case "UseReaction":
+      return null;
+    case "ReadyAction":
+      return <div>Readied an action</div>;
+    case "TriggerReadiedAction":
+      return <div>A readied action was triggered</div>;
+    case "VolumeConditionsApplied":
+      return null;
+    case "UseAction":
+      return null;
+    case "SetActionBudget":
+      return <div>Changed the number of actions creatures get each turn</div>;
+    case "DelayTurn":
+      return <div>Delayed a turn</div>;
+    case "ResumeTurn":
+      return <div>Resumed a delayed turn</div>;
+    case "SwitchTurnMember":
+      return null;
+    case "SetInitiativeMode":
+      return null;
   }
 }
 
@@ -185,5 +237,9 @@ export function creature_log(
       return <div>{creature_name} ticked a condition.</div>;
     case "RemoveCondition":
       return <div>{creature_name} lost a condition.</div>;
+    case "StartCooldown":
+      return <div>{creature_name} started a {log.cooldown}-round cooldown.</div>;
+    case "DecrementCooldown":
+      return <div>{creature_name} ticked a cooldown.</div>;
   }
 }
@@ -39,6 +39,7 @@ export type Action =
   | {
     type: "DisplayPotentialTargets";
     cid: T.CreatureID; ability_id: T.AbilityID; options: T.PotentialTargets;
+    multi?: MultiTargeting;
   }
   | { type: "ClearPotentialTargets" }
   | { type: "@@redux/INIT" };
@@ -134,9 +135,9 @@ export function update(ptui: PTUI, action: Action): PTUI {
           },
         }));
     case "DisplayPotentialTargets":
-      const { cid, ability_id, options } = action;
+      const { cid, ability_id, options, multi } = action;
       return ptui.updateGridState(
-        grid => ({ ...grid, target_options: { cid, ability_id, options } }));
+        grid => ({ ...grid, target_options: { cid, ability_id, options, multi } }));
     case "ClearPotentialTargets":
       return ptui.updateGridState(grid => ({ ...grid, target_options: undefined }));
     case "ClearMovementOptions":
@@ -162,11 +163,22 @@ export interface GridModel {
     options: Array<T.Point3>;
     teleport: boolean;
   };
-  target_options?: { cid: T.CreatureID; ability_id: T.AbilityID; options: T.PotentialTargets };
+  target_options?: {
+    cid: T.CreatureID; ability_id: T.AbilityID; options: T.PotentialTargets;
+    // Only for multi-part actions, in which case `options` are for the part being targeted now.
+    multi?: MultiTargeting;
+  };
   highlight_color: T.Color;
   object_visibility: T.Visibility;
 }
 
+// The progress of choosing a target for each part of a multi-part action.
+export interface MultiTargeting {
+  scene_id: T.SceneID;
+  part_names: Array<string>;
+  decided: Array<T.DecidedTarget>;
+}
+
 export type GridObject =
   | { t: "VolumeCondition"; id: T.ConditionID }
   | { t: "Creature"; id: T.CreatureID }
@@ -410,8 +422,7 @@ export class PTUI {
     const { ability_id, options } = opts;
     if (options.t !== "CreatureIDs") { throw new Error(`Only support CreatureIDs for now`); }
     const target: T.DecidedTarget = { t: "Creature", creature_id: target_id };
-    this.sendCommand(dispatch, { t: "CombatAct", ability_id, target });
-    dispatch({ type: "ClearPotentialTargets" });
+    this.decideTarget(dispatch, ability_id, target);
   }
 
   executeCombatPointTargetedAbility(dispatch: Dispatch, point: T.Point3) {
@@ -422,8 +433,34 @@ export class PTUI {
       throw new Error(`This function only works for abilities that use Points`);
     }
     const target: T.DecidedTarget = { t: "Point", point };
-    this.sendCommand(dispatch, { t: "CombatAct", ability_id, target });
-    dispatch({ type: "ClearPotentialTargets" });
+    this.decideTarget(dispatch, ability_id, target);
+  }
+
+  /* The action whose target is being chosen: either the selected ability's action, or the current
+   * part of it if it's a multi-part action.
+   */
+  getTargetedAction(): T.Action | undefined {
+    const opts = this.state.grid.target_options;
+    if (!opts) { return; }
+    const ability = this.getAbility(opts.ability_id);
+    if (!ability) { return; }
+    if (opts.multi && ability.action.t === "Multi") {
+      return ability.action.parts[opts.multi.decided.length][1];
+    }
+    return ability.action;
+  }
+
+  /* Use the ability with the chosen target, or for a multi-part action, move on to the next part.
+   */
+  decideTarget(dispatch: Dispatch, ability_id: T.AbilityID, target: T.DecidedTarget) {
+    const { cid, multi } = this.state.grid.target_options!;
+    if (multi) {
+      const decided = [...multi.decided, target];
+      dispatch(targetParts(multi.scene_id, cid, ability_id, multi.part_names, decided));
+    } else {
+      this.sendCommand(dispatch, { t: "CombatAct", ability_id, target });
+      dispatch({ type: "ClearPotentialTargets" });
+    }
   }
 
   // Utility functions for interacting with the model
@@ -636,7 +673,59 @@ function selectAbility(
     const rpi_url = getState().rpi_url;
     const url = `${rpi_url}/target_options/${scene_id}/${cid}/${ability_id}`;
     ptfetch(dispatch, url, undefined, T.decodePotentialTargets,
-      options => dispatch({ type: "DisplayPotentialTargets", cid, ability_id, options }));
+      options => {
+        if (options.t === "Multi") {
+          const part_names = options.parts.map(([name, _]) => name);
+          const first = options.parts.length ? options.parts[0][1] : undefined;
+          dispatch(targetParts(scene_id, cid, ability_id, part_names, [], first));
+        } else {
+          dispatch({ type: "DisplayPotentialTargets", cid, ability_id, options });
+        }
+      });
+  };
+}
+
+/* Display the options for the first part of a multi-part action that hasn't been targeted yet.
+ * Parts that can only target the actor are decided without asking, and once every part has a
+ * target, the ability is used. The options for a part depend on what the parts before it do (like
+ * where a dash ends up), so unless they're already known, they're fetched given the targets
+ * decided so far.
+ */
+function targetParts(
+  scene_id: T.SceneID, cid: T.CreatureID, ability_id: T.AbilityID, part_names: Array<string>,
+  decided: Array<T.DecidedTarget>, options?: T.PotentialTargets,
+): ThunkAction<void> {
+  return (dispatch, getState) => {
+    const ptui = getState();
+    const ability = ptui.getAbility(ability_id);
+    if (!ability || ability.action.t !== "Multi") { return; }
+    const actions = ability.action.parts;
+    decided = [...decided];
+    while (decided.length < part_names.length) {
+      const action = actions[decided.length][1];
+      if (action.t !== "Creature" || action.target.t !== "Actor") { break; }
+      decided.push({ t: "Actor" });
+      options = undefined;
+    }
+    if (decided.length === part_names.length) {
+      dispatch({ type: "ClearPotentialTargets" });
+      const target: T.DecidedTarget = { t: "Multi", targets: decided };
+      dispatch(sendCommand({ t: "CombatAct", ability_id, target }));
+      return;
+    }
+    const multi = { scene_id, part_names, decided };
+    if (options) {
+      dispatch({ type: "DisplayPotentialTargets", cid, ability_id, options, multi });
+      return;
+    }
+    const url = `${ptui.rpi_url}/multi_target_options/${scene_id}/${cid}/${ability_id}`;
+    const init = {
+      method: "POST",
+      body: JSON.stringify(decided.map(T.encodeDecidedTarget)),
+      headers: { "content-type": "application/json" },
+    };
+    ptfetch(dispatch, url, init, T.decodePotentialTargets,
+      next => dispatch({ type: "DisplayPotentialTargets", cid, ability_id, options: next, multi }));
   };
 }
 
@@ -674,13 +763,16 @@ export function connectRedux<BaseProps extends {} & object>(
 export function fetchAbilityTargets(
   dispatch: Dispatch, rpi_url: string, scene_id: T.SceneID, actor_id: T.CreatureID,
   ability_id: T.AbilityID, point: T.Point3)
-  : Promise<{ points: Array<T.Point3>; creatures: Array<T.CreatureID> }> {
+  : Promise<{
+    points: Array<T.Point3>; creatures: Array<T.CreatureID>; damage: T.Distribution | undefined;
+  }> {
   const uri =
     `${rpi_url}/preview_volume_targets/${scene_id}/${actor_id}/`
     + `${ability_id}/${point.x}/${point.y}/${point.z}`;
   return ptfetch(dispatch, uri, { method: 'POST' },
-    JD.map(([creatures, points]) => ({ points, creatures }),
-      JD.tuple(JD.array(JD.string()), JD.array(T.decodePoint3))),
+    JD.map(([creatures, points, damage]) => ({ points, creatures, damage }),
+      JD.tuple(
+        JD.array(JD.string()), JD.array(T.decodePoint3), T.maybe(T.decodeDistribution))),
     x => x
   );
 }
@@ -9,6 +9,7 @@ export type CreatureID = string;
 export type PlayerID = string;
 export type SceneID = string;
 export type ItemID = string;
+export type FactionID = string;
 export type AttrID = string;
 export type Color = string;
 export type Distance = number;
@@ -19,6 +20,7 @@ export type FolderPath = Array<string>;
 export type Terrain = I.Set<Point3>;
 export type Highlights = I.Map<Point3, [Color, Visibility]>;
 export type Annotations = I.Map<Point3, [string, Visibility]>;
+export type TerrainCells = I.Map<Point3, TerrainCell>;
 
 
 export class Point3 implements I.ValueObject {
@@ -59,35 +61,97 @@ export interface Game {
   abilities: { [index: string]: Ability };
   campaign: Folder;
   players: I.Map<PlayerID, Player>;
+  ooc_ability_cost: OutOfCombatCost;
+  actions_left: ActionBudget | undefined;
+  turn_members: Array<CreatureID>;
+  factions: I.Map<FactionID, Faction>;
 }
 
+export interface Faction {
+  id: FactionID;
+  name: string;
+  relationships: I.Map<FactionID, Relationship>;
+}
+
+export type Relationship = "Ally" | "Neutral" | "Enemy";
+
+export type TargetFilter = "Any" | "Allies" | "Enemies";
+
 export interface Combat {
   scene: SceneID;
   creatures: { cursor: number; data: Array<[CreatureID, number]> };
+  round: number;
   movement_used: number;
+  pending_interrupts: Array<Interrupt>;
+  reactions_used: { [index: string]: number };
+  readied_actions: { [index: string]: ReadiedAction };
+  volume_conditions_applied: { [index: string]: Array<ConditionID> };
+  action_budget: ActionBudget | undefined;
+  actions_used: { [index: string]: number };
+  group_movement_used: { [index: string]: number };
+  group_actions_used: { [index: string]: { [index: string]: number } };
+  delayed: Array<CreatureID>;
+  resuming: Array<CreatureID>;
+  initiative_mode: InitiativeMode;
+}
+
+export type InitiativeMode =
+  | { t: "Individual" }
+  | { t: "GroupByClass" }
+  | { t: "Groups"; groups: Array<Array<CreatureID>> }
+  | { t: "Sides"; sides: Array<Array<CreatureID>> };
+
+export interface Interrupt {
+  creature_id: CreatureID;
+  ability_id: AbilityID;
+  target: DecidedTarget;
 }
 
+export interface ReadiedAction {
+  ability_id: AbilityID;
+  target: DecidedTarget;
+  trigger: ReadyTrigger;
+}
+
+export type ReadyTrigger =
+  | { t: "CreatureMoves"; creature_id: CreatureID }
+  | { t: "CreatureActs"; creature_id: CreatureID };
+
 export interface Ability {
   name: string;
   id: AbilityID;
   action: Action;
   cost: Energy;
   usable_ooc: boolean;
+  cooldown: number;
+  slot: ActionSlot;
+}
+
+export type ActionSlot = 'Standard' | 'Minor' | 'Free';
+
+export interface ActionBudget {
+  standard: number;
+  minor: number;
 }
 
 export type Action =
   // these variants also have an `effect` field but we don't use it in the client
   | { t: "Creature"; target: CreatureTarget }
   | { t: "SceneVolume"; target: SceneTarget }
+  | { t: "MoveActor"; distance: Distance }
+  | { t: "Multi"; parts: Array<[string, Action]> }
   ;
 
 export type CreatureTarget =
-  | { t: "Melee" }
-  | { t: "Range"; distance: Distance }
+  | { t: "Melee"; filter: TargetFilter }
+  | { t: "Range"; distance: Distance; filter: TargetFilter }
   | { t: "Actor" }
-  | { t: "SomeCreaturesInVolumeInRange"; volume: Volume; maximum: number; range: Distance }
-  | { t: "AllCreaturesInVolumeInRange"; volume: Volume; range: Distance }
-  | { t: "LineFromActor"; distance: Distance }
+  | {
+    t: "SomeCreaturesInVolumeInRange"; volume: Volume; maximum: number; range: Distance;
+    filter: TargetFilter;
+  }
+  | { t: "AllCreaturesInVolumeInRange"; volume: Volume; range: Distance; filter: TargetFilter }
+  | { t: "LineFromActor"; distance: Distance; filter: TargetFilter }
   ;
 
 export interface SceneTarget { t: "RangedVolume"; volume: Volume; range: Distance; }
@@ -96,7 +160,15 @@ export type DecidedTarget =
   | { t: "Creature"; creature_id: CreatureID }
   | { t: "Creatures"; creature_ids: Array<CreatureID> }
   | { t: "Actor" }
-  | { t: "Point"; point: Point3 };
+  | { t: "Point"; point: Point3 }
+  | { t: "Multi"; targets: Array<DecidedTarget> };
+
+export type TerrainCell =
+  | { t: "Difficult"; multiplier: number }
+  | { t: "Water" }
+  | { t: "Door"; open: boolean }
+  | { t: "DestructibleWall" }
+  | { t: "Hazard"; effect: CreatureEffect };
 
 export type Volume =
   | { t: "Sphere"; radius: Distance }
@@ -125,6 +197,7 @@ export type InventoryOwner =
 
 export type GameCommand =
   | { t: "SetActiveScene"; scene_id: SceneID | undefined }
+  | { t: "SetOutOfCombatCost"; cost: OutOfCombatCost }
   | { t: "ChatFromGM"; message: string }
   | { t: "ChatFromPlayer"; player_id: PlayerID; message: string }
   | { t: "RegisterPlayer"; player_id: PlayerID }
@@ -153,20 +226,44 @@ export type GameCommand =
   | { t: "AddSceneChallenge"; scene_id: SceneID; description: string; challenge: AttributeCheck }
   | { t: "RemoveSceneChallenge"; scene_id: SceneID; description: string }
   | { t: "SetFocusedSceneCreatures"; scene_id: SceneID; creatures: I.List<CreatureID> }
+  | {
+    t: "AddSceneVolumeCondition"; scene_id: SceneID; point: Point3; volume: Volume;
+    condition: Condition; duration: Duration;
+  }
   | { t: "RemoveSceneVolumeCondition"; scene_id: SceneID; condition_id: ConditionID }
   | { t: "EditSceneTerrain"; scene_id: SceneID; terrain: Terrain }
+  | { t: "EditSceneTerrainLevel"; scene_id: SceneID; level: number; terrain: Terrain }
+  | { t: "EditSceneStairs"; scene_id: SceneID; stairs: Terrain }
+  | { t: "EditSceneTerrainCells"; scene_id: SceneID; terrain_cells: TerrainCells }
+  | { t: "OpenDoor"; scene_id: SceneID; point: Point3 }
+  | { t: "CloseDoor"; scene_id: SceneID; point: Point3 }
+  | { t: "DestroyWall"; scene_id: SceneID; point: Point3 }
   | { t: "EditSceneHighlights"; scene_id: SceneID; highlights: Highlights }
   | { t: "EditSceneAnnotations"; scene_id: SceneID; annotations: Annotations }
   | { t: "EditSceneRelatedScenes"; scene_id: SceneID; related_scenes: I.Set<SceneID> }
   | { t: "EditSceneSceneHotspots"; scene_id: SceneID; scene_hotspots: I.Map<Point3, SceneID> }
   | { t: "RemoveCreatureFromCombat"; creature_id: CreatureID }
+  | { t: "SetCreatureFaction"; creature_id: CreatureID; faction_id: FactionID | undefined }
+  | { t: "CreateFaction"; name: string }
+  | {
+    t: "SetFactionRelationship"; faction_id: FactionID; other: FactionID;
+    relationship: Relationship;
+  }
   | { t: "CombatAct"; ability_id: AbilityID; target: DecidedTarget }
   | { t: "PathCreature"; scene_id: SceneID; creature_id: CreatureID; dest: Point3 }
   | { t: "SetCreaturePos"; scene_id: SceneID; creature_id: CreatureID; dest: Point3 }
   | { t: "PathCurrentCombatCreature"; dest: Point3 }
   | { t: "Done" }
+  | { t: "DelayTurn" }
+  | { t: "ResumeTurn"; creature_id: CreatureID }
+  | { t: "SwitchTurnMember"; creature_id: CreatureID }
+  | { t: "ReadyAction"; ability_id: AbilityID; target: DecidedTarget; trigger: ReadyTrigger }
+  | { t: "ReactToInterrupt" }
+  | { t: "DeclineInterrupt" }
+  | { t: "Rest"; kind: RestKind; creature_ids: Array<CreatureID> }
   | { t: "ChangeCreatureInitiative"; creature_id: CreatureID; init: number }
-  | { t: "StartCombat"; scene_id: SceneID; creature_ids: Array<CreatureID> }
+  | { t: "SetActionBudget"; budget: ActionBudget | undefined }
+  | { t: "StartCombat"; scene_id: SceneID; creature_ids: Array<CreatureID>; mode: InitiativeMode }
   | { t: "StopCombat" }
   | { t: "AddCreatureToCombat"; creature_id: CreatureID }
   | { t: "AttributeCheck"; creature_id: CreatureID; check: AttributeCheck }
@@ -177,6 +274,10 @@ export type GameCommand =
 
 export type ModuleSource = 'Module' | 'SavedGame';
 
+export type RestKind = 'Short' | 'Long';
+
+export type OutOfCombatCost = 'Free' | 'Charged';
+
 export interface CreatureCreation {
   name: string;
   class_: string;
@@ -203,6 +304,7 @@ export interface Player {
 
 export type GameLog =
   | { t: "SetActiveScene"; scene_id: SceneID | undefined }
+  | { t: "SetOutOfCombatCost"; cost: OutOfCombatCost }
   | { t: "RegisterPlayer"; player_id: string }
   | { t: "UnregisterPlayer"; player_id: string }
   | { t: "GiveCreaturesToPlayer"; player_id: string; creature_ids: Array<CreatureID> }
@@ -218,6 +320,7 @@ export type GameLog =
     actual: number;
     success: boolean;
   }
+  | { t: "DiceSeed"; seed: number }
   | { t: "CreateFolder"; path: FolderPath }
   | { t: "RenameFolder"; path: FolderPath; newName: string }
   | { t: "DeleteFolderItem"; path: FolderPath; item: FolderItemID }
@@ -242,7 +345,16 @@ export type GameLog =
   | { t: "RemoveSceneChallenge"; scene_id: SceneID; description: string }
   | { t: "SetFocusedSceneCreatures"; scene_id: SceneID; creatures: I.List<CreatureID> }
   | { t: "RemoveSceneVolumeCondition"; scene_id: SceneID; condition_id: ConditionID }
+  | { t: "DecrementVolumeConditionRemaining"; scene_id: SceneID; condition_id: ConditionID }
   | { t: "EditSceneTerrain"; scene_id: SceneID; terrain: Terrain }
+  | { t: "EditSceneTerrainLevel"; scene_id: SceneID; level: number; terrain: Terrain }
+  | { t: "EditSceneStairs"; scene_id: SceneID; stairs: Terrain }
+  | { t: "EditSceneTerrainCells"; scene_id: SceneID; terrain_cells: TerrainCells }
+  | { t: "OpenDoor"; scene_id: SceneID; point: Point3 }
+  | { t: "CloseDoor"; scene_id: SceneID; point: Point3 }
+  | { t: "DestroyWall"; scene_id: SceneID; point: Point3 }
+  | { t: "AddSceneTerrain"; scene_id: SceneID; terrain: Terrain }
+  | { t: "RemoveSceneTerrain"; scene_id: SceneID; terrain: Terrain }
   | { t: "EditSceneHighlights"; scene_id: SceneID; highlights: Highlights }
   | { t: "EditSceneAnnotations"; scene_id: SceneID; annotations: Annotations }
   | { t: "EditSceneRelatedScenes"; scene_id: SceneID; related_scenes: I.Set<SceneID> }
@@ -254,6 +366,12 @@ export type GameLog =
   | { t: "StartCombat"; scene: SceneID; creatures: Array<{ cid: CreatureID; init: number }> }
   | { t: "AddCreatureToCombat"; creature_id: CreatureID; init: number }
   | { t: "RemoveCreatureFromCombat"; creature_id: CreatureID }
+  | { t: "SetCreatureFaction"; creature_id: CreatureID; faction_id: FactionID | undefined }
+  | { t: "CreateFaction"; faction: Faction }
+  | {
+    t: "SetFactionRelationship"; faction_id: FactionID; other: FactionID;
+    relationship: Relationship;
+  }
   | { t: "CombatLog"; log: CombatLog }
   | { t: "CreatureLog"; creature_id: CreatureID; log: CreatureLog }
   | { t: "StopCombat" }
@@ -267,7 +385,19 @@ export type CombatLog =
   | { t: "EndTurn"; creature_id: CreatureID }
   | { t: "ForceNextTurn" }
   | { t: "ForcePrevTurn" }
-  | { t: "RerollInitiative"; combatants: Array<[CreatureID, number]> };
+  | { t: "RerollInitiative"; combatants: Array<[CreatureID, number]> }
+  | { t: "QueueInterrupt"; interrupt: Interrupt }
+  | { t: "ResolveInterrupt" }
+  | { t: "UseReaction"; creature_id: CreatureID }
+  | { t: "ReadyAction"; creature_id: CreatureID; readied: ReadiedAction }
+  | { t: "TriggerReadiedAction"; creature_id: CreatureID }
+  | { t: "VolumeConditionsApplied"; creature_id: CreatureID; condition_ids: Array<ConditionID> }
+  | { t: "UseAction"; slot: ActionSlot }
+  | { t: "SetActionBudget"; budget: ActionBudget | undefined }
+  | { t: "DelayTurn"; creature_id: CreatureID }
+  | { t: "ResumeTurn"; creature_id: CreatureID }
+  | { t: "SwitchTurnMember"; creature_id: CreatureID }
+  | { t: "SetInitiativeMode"; mode: InitiativeMode };
 
 export type CreatureLog =
   | { t: "Damage"; hp: HP; rolls: Array<number> }
@@ -276,7 +406,9 @@ export type CreatureLog =
   | { t: "ReduceEnergy"; energy: Energy }
   | { t: "ApplyCondition"; condition_id: ConditionID; duration: Duration } // TODO Condition
   | { t: "DecrementConditionRemaining"; condition_id: ConditionID }
-  | { t: "RemoveCondition"; condition_id: ConditionID };
+  | { t: "RemoveCondition"; condition_id: ConditionID }
+  | { t: "StartCooldown"; ability_id: AbilityID; cooldown: number }
+  | { t: "DecrementCooldown"; ability_id: AbilityID };
 
 export interface Item {
   id: ItemID;
@@ -288,7 +420,11 @@ export type CreatureEffect =
   | { t: "Heal"; dice: Dice }
   | { t: "Damage"; dice: Dice }
   | { t: "MultiEffect"; effects: Array<CreatureEffect> }
-  | { t: "GenerateEnergy"; energy: Energy };
+  | { t: "GenerateEnergy"; energy: Energy }
+  | { t: "Push"; distance: Distance }
+  | { t: "Pull"; distance: Distance }
+  | { t: "Knockback"; distance: Distance; impact: Dice }
+  | { t: "Teleport"; distance: Distance };
 
 export type Duration =
   | { t: "Interminate" }
@@ -296,12 +432,15 @@ export type Duration =
 
 export type Condition =
   | { t: "RecurringEffect"; effect: CreatureEffect }
+  | { t: "RecurringEffectAt"; timing: TickTiming; effect: CreatureEffect }
   | { t: "Dead" }
   | { t: "Incapacitated" }
   | { t: "AddDamageBuff"; hp: HP }
   | { t: "DoubleMaxMovement" }
   | { t: "ActivateAbility"; ability_id: AbilityID };
 
+export type TickTiming = 'StartOfTurn' | 'EndOfTurn' | 'StartOfRound';
+
 export interface AppliedCondition {
   remaining: Duration;
   condition: Condition;
@@ -333,6 +472,7 @@ export class Creature {
     public initiative: Dice,
     public inventory: I.Map<ItemID, number>,
     public size: AABB,
+    public faction: FactionID | undefined,
   ) { }
 
   dynamic_conditions(): I.Map<ConditionID, AppliedCondition> {
@@ -411,11 +551,30 @@ export type Dice =
   | { t: "Flat"; val: number }
   | { t: "Expr"; num: number; size: number }
   | { t: "Plus"; left: Dice; right: Dice }
-  | { t: "BestOf"; num: number; dice: Dice };
+  | { t: "BestOf"; num: number; dice: Dice }
+  | { t: "Minus"; left: Dice; right: Dice }
+  | {
+    t: "Roll"; num: number; size: number; explode: boolean; reroll_below: number | undefined;
+    keep: Keep | undefined;
+  };
+
+export interface Distribution {
+  min: number;
+  max: number;
+  mean: number;
+  variance: number;
+  probabilities: Array<number>;
+  at_least: Array<number>;
+}
+
+export type Keep =
+  | { t: "Highest"; num: number }
+  | { t: "Lowest"; num: number };
 
 export type PotentialTargets =
   | { t: "CreatureIDs"; cids: Array<CreatureID> }
   | { t: "Points"; points: Array<Point3> }
+  | { t: "Multi"; parts: Array<[string, PotentialTargets]> }
   ;
 
 export type RustResult<T, E> =
@@ -436,11 +595,14 @@ export function parsePoint3(str: string): Point3 {
 
 export const decodePoint3: Decoder<Point3> = JD.map(parsePoint3, JD.string());
 
-export const decodePotentialTargets = sum<PotentialTargets>("PotentialTargets", {}, {
-  CreatureIDs: JD.map((cids): PotentialTargets =>
-    ({ t: "CreatureIDs", cids }), JD.array(JD.string())),
-  Points: JD.map((points): PotentialTargets => ({ t: "Points", points }), JD.array(decodePoint3)),
-});
+export const decodePotentialTargets: Decoder<PotentialTargets> = sum<PotentialTargets>(
+  "PotentialTargets", {}, {
+    CreatureIDs: JD.map((cids): PotentialTargets =>
+      ({ t: "CreatureIDs", cids }), JD.array(JD.string())),
+    Points: JD.map((points): PotentialTargets => ({ t: "Points", points }), JD.array(decodePoint3)),
+    Multi: JD.map((parts): PotentialTargets => ({ t: "Multi", parts }),
+      JD.array(JD.tuple(JD.string(), JD.lazy(() => decodePotentialTargets)))),
+  });
 
 const decodeDiceLazy = JD.lazy(() => decodeDice);
 const decodeConditionLazy = JD.lazy(() => decodeCondition);
@@ -457,8 +619,57 @@ const decodeDice: Decoder<Dice> = sum<Dice>("Dice", {}, {
   Plus: JD.map(
     ([left, right]): Dice => ({ t: "Plus", left, right }),
     JD.tuple(decodeDiceLazy, decodeDiceLazy)),
+  Minus: JD.map(
+    ([left, right]): Dice => ({ t: "Minus", left, right }),
+    JD.tuple(decodeDiceLazy, decodeDiceLazy)),
+  Roll: JD.object(
+    ["num", JD.number()],
+    ["size", JD.number()],
+    ["explode", JD.boolean()],
+    ["reroll_below", maybe(JD.number())],
+    ["keep", maybe(sum<Keep>("Keep", {}, {
+      Highest: JD.map((num): Keep => ({ t: "Highest", num }), JD.number()),
+      Lowest: JD.map((num): Keep => ({ t: "Lowest", num }), JD.number()),
+    }))],
+    (num, size, explode, reroll_below, keep): Dice =>
+      ({ t: "Roll", num, size, explode, reroll_below, keep })),
 });
 
+export const decodeDistribution: Decoder<Distribution> = JD.object(
+  ["min", JD.number()],
+  ["max", JD.number()],
+  ["mean", JD.number()],
+  ["variance", JD.number()],
+  ["probabilities", JD.array(JD.number())],
+  ["at_least", JD.array(JD.number())],
+  (min, max, mean, variance, probabilities, at_least) =>
+    ({ min, max, mean, variance, probabilities, at_least })
+);
+
+const decodeTickTiming: Decoder<TickTiming> = JD.oneOf(
+  JD.equal('StartOfTurn' as TickTiming), JD.equal('EndOfTurn' as TickTiming),
+  JD.equal('StartOfRound' as TickTiming));
+
+const decodeRelationship: Decoder<Relationship> = JD.oneOf(
+  JD.equal('Ally' as Relationship), JD.equal('Neutral' as Relationship),
+  JD.equal('Enemy' as Relationship));
+
+const decodeFaction: Decoder<Faction> = JD.object(
+  ["id", JD.string()],
+  ["name", JD.string()],
+  ["relationships", JD.map(I.Map, JD.dict(decodeRelationship))],
+  (id, name, relationships) => ({ id, name, relationships }));
+
+const decodeInitiativeMode: Decoder<InitiativeMode> =
+  sum<InitiativeMode>("InitiativeMode",
+    { Individual: { t: "Individual" }, GroupByClass: { t: "GroupByClass" } },
+    {
+      Groups: JD.map((groups): InitiativeMode => ({ t: "Groups", groups }),
+        JD.array(JD.array(JD.string()))),
+      Sides: JD.map((sides): InitiativeMode => ({ t: "Sides", sides }),
+        JD.array(JD.array(JD.string()))),
+    });
+
 const decodeDuration: Decoder<Duration> =
   sum<Duration>("Duration", { Interminate: { t: "Interminate" } },
     {
@@ -480,6 +691,13 @@ const decodeEffect: Decoder<CreatureEffect> = sum<CreatureEffect>("CreatureEffec
     MultiEffect: JD.map(
       (effects): CreatureEffect => ({ t: "MultiEffect", effects }),
       JD.array(decodeEffectLazy)),
+    Push: JD.map((distance): CreatureEffect => ({ t: "Push", distance }), JD.number()),
+    Pull: JD.map((distance): CreatureEffect => ({ t: "Pull", distance }), JD.number()),
+    Knockback: JD.object(
+      ["distance", JD.number()],
+      ["impact", decodeDice],
+      (distance, impact): CreatureEffect => ({ t: "Knockback", distance, impact })),
+    Teleport: JD.map((distance): CreatureEffect => ({ t: "Teleport", distance }), JD.number()),
   });
 
 const decodeCondition: Decoder<Condition> = sum<Condition>("Condition",
@@ -494,6 +712,9 @@ const decodeCondition: Decoder<Condition> = sum<Condition>("Condition",
     RecurringEffect: JD.map(
       (effect): Condition => ({ t: "RecurringEffect", effect }),
       decodeEffect),
+    RecurringEffectAt: JD.map(
+      ([timing, effect]): Condition => ({ t: "RecurringEffectAt", timing, effect }),
+      JD.tuple(decodeTickTiming, decodeEffect)),
   }
 );
 
@@ -550,6 +771,15 @@ function objectBig<T, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S>(
   _cons: (
     a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K, l: L, m: M, n: N, o: O,
     p: P, q: Q, r: R, s: S) => T): Decoder<T>;
+function objectBig<T, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, U>(
+  _ad: JD.EntryDecoder<A>, _bd: JD.EntryDecoder<B>, _cd: JD.EntryDecoder<C>, _dd: JD.EntryDecoder<D>,
+  _ed: JD.EntryDecoder<E>, _fd: JD.EntryDecoder<F>, _gd: JD.EntryDecoder<G>, _hd: JD.EntryDecoder<H>,
+  _id: JD.EntryDecoder<I>, _jd: JD.EntryDecoder<J>, _kd: JD.EntryDecoder<K>, _ld: JD.EntryDecoder<L>,
+  _md: JD.EntryDecoder<M>, _nd: JD.EntryDecoder<N>, _od: JD.EntryDecoder<O>, _pd: JD.EntryDecoder<P>,
+  _qd: JD.EntryDecoder<Q>, _rd: JD.EntryDecoder<R>, _sd: JD.EntryDecoder<S>, _ud: JD.EntryDecoder<U>,
+  _cons: (
+    a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K, l: L, m: M, n: N, o: O,
+    p: P, q: Q, r: R, s: S, u: U) => T): Decoder<T>;
 function objectBig<T>(...args: Array<any>): Decoder<T> {
   return JD.object.apply(undefined, args);
 }
@@ -574,14 +804,15 @@ export const decodeCreature: Decoder<Creature> = objectBig(
   ["initiative", decodeDice],
   ["inventory", JD.map(I.Map, JD.dict(JD.number()))],
   ["size", decodeAABB],
+  ["faction", maybe(JD.string())],
   (
     id, name, speed, max_energy, cur_energy, abilities, class_, max_health, cur_health,
     own_conditions, volume_conditions, note, bio, portrait_url, icon_url, attributes, initiative,
-    inventory, size) =>
+    inventory, size, faction) =>
     new Creature(
       id, name, speed, max_energy, cur_energy, abilities, class_, max_health, cur_health,
       own_conditions, volume_conditions, note, bio, portrait_url, icon_url, attributes, initiative,
-      inventory, size
+      inventory, size, faction
     )
 );
 
@@ -643,6 +874,14 @@ const decodeTerrain: Decoder<Terrain> = decodeSet(decodePoint3);
 const decodeHighlights: Decoder<Highlights> =
   decodeIMap(decodePoint3, JD.tuple(JD.string(), decodeVisibility));
 const decodeAnnotations: Decoder<Annotations> = decodeHighlights;
+const decodeTerrainCell: Decoder<TerrainCell> = sum<TerrainCell>("TerrainCell",
+  { Water: { t: "Water" }, DestructibleWall: { t: "DestructibleWall" } },
+  {
+    Difficult: JD.map((multiplier): TerrainCell => ({ t: "Difficult", multiplier }), JD.number()),
+    Door: JD.object(["open", JD.boolean()], (open): TerrainCell => ({ t: "Door", open })),
+    Hazard: JD.map((effect): TerrainCell => ({ t: "Hazard", effect }), decodeEffect),
+  });
+const decodeTerrainCells: Decoder<TerrainCells> = decodeIMap(decodePoint3, decodeTerrainCell);
 
 function decodeSet<T>(d: Decoder<T>): Decoder<I.Set<T>> {
   // In the upgrade to a latest typescript (and many other dependencies), for some reason
@@ -728,6 +967,9 @@ export const decodeInventoryOwner: Decoder<InventoryOwner> = sum<InventoryOwner>
 const decodeModuleSource: Decoder<ModuleSource> =
   JD.oneOf(JD.equal('Module' as ModuleSource), JD.equal('SavedGame' as ModuleSource));
 
+const decodeOutOfCombatCost: Decoder<OutOfCombatCost> =
+  JD.oneOf(JD.equal('Free' as OutOfCombatCost), JD.equal('Charged' as OutOfCombatCost));
+
 const decodeCreatureLog: Decoder<CreatureLog> =
   sum<CreatureLog>("CreatureLog", {}, {
     Damage: JD.map(
@@ -747,13 +989,53 @@ const decodeCreatureLog: Decoder<CreatureLog> =
       JD.string()),
     RemoveCondition: JD.map((condition_id): CreatureLog => ({ t: "RemoveCondition", condition_id }),
       JD.string()),
+    StartCooldown: JD.map(
+      ([ability_id, cooldown]): CreatureLog => ({ t: "StartCooldown", ability_id, cooldown }),
+      JD.tuple(JD.string(), JD.number())),
+    DecrementCooldown: JD.map(
+      (ability_id): CreatureLog => ({ t: "DecrementCooldown", ability_id }),
+      JD.string()),
   });
 
+const decodeDecidedTarget: Decoder<DecidedTarget> = sum<DecidedTarget>("DecidedTarget",
+  { Actor: { t: "Actor" } },
+  {
+    Creature: JD.map((creature_id): DecidedTarget => ({ t: "Creature", creature_id }), JD.string()),
+    Creatures: JD.map((creature_ids): DecidedTarget => ({ t: "Creatures", creature_ids }),
+      JD.array(JD.string())),
+    Point: JD.map((point): DecidedTarget => ({ t: "Point", point }), decodePoint3),
+    Multi: JD.map((targets): DecidedTarget => ({ t: "Multi", targets }),
+      JD.array(JD.lazy(() => decodeDecidedTarget))),
+  });
+
+const decodeInterrupt: Decoder<Interrupt> = JD.object(
+  ["creature_id", JD.string()],
+  ["ability_id", JD.string()],
+  ["target", decodeDecidedTarget],
+  (creature_id, ability_id, target) => ({ creature_id, ability_id, target })
+);
+
+const decodeReadyTrigger: Decoder<ReadyTrigger> = sum<ReadyTrigger>("ReadyTrigger", {},
+  {
+    CreatureMoves: JD.map((creature_id): ReadyTrigger => ({ t: "CreatureMoves", creature_id }),
+      JD.string()),
+    CreatureActs: JD.map((creature_id): ReadyTrigger => ({ t: "CreatureActs", creature_id }),
+      JD.string()),
+  });
+
+const decodeReadiedAction: Decoder<ReadiedAction> = JD.object(
+  ["ability_id", JD.string()],
+  ["target", decodeDecidedTarget],
+  ["trigger", decodeReadyTrigger],
+  (ability_id, target, trigger) => ({ ability_id, target, trigger })
+);
+
 const decodeCombatLog: Decoder<CombatLog> =
   sum<CombatLog>("CombatLog",
     {
       ForceNextTurn: { t: "ForceNextTurn" },
       ForcePrevTurn: { t: "ForcePrevTurn" },
+      ResolveInterrupt: { t: "ResolveInterrupt" },
     },
     {
       ConsumeMovement: JD.map(
@@ -765,11 +1047,39 @@ const decodeCombatLog: Decoder<CombatLog> =
       EndTurn: JD.map((creature_id): CombatLog => ({ t: "EndTurn", creature_id }), JD.string()),
       RerollInitiative: JD.map((combatants): CombatLog => ({ t: "RerollInitiative", combatants }),
         JD.array(JD.tuple(JD.string(), JD.number()))),
+      QueueInterrupt: JD.map((interrupt): CombatLog => ({ t: "QueueInterrupt", interrupt }),
+        decodeInterrupt),
+      UseReaction: JD.map((creature_id): CombatLog => ({ t: "UseReaction", creature_id }),
+        JD.string()),
+      ReadyAction: JD.map(
+        ([creature_id, readied]): CombatLog => ({ t: "ReadyAction", creature_id, readied }),
+        JD.tuple(JD.string(), decodeReadiedAction)),
+      TriggerReadiedAction: JD.map(
+        (creature_id): CombatLog => ({ t: "TriggerReadiedAction", creature_id }),
+        JD.string()),
+      VolumeConditionsApplied: JD.map(
+        ([creature_id, condition_ids]): CombatLog =>
+          ({ t: "VolumeConditionsApplied", creature_id, condition_ids }),
+        JD.tuple(JD.string(), JD.array(JD.string()))),
+      UseAction: JD.map((slot): CombatLog => ({ t: "UseAction", slot }), decodeActionSlot),
+      SetActionBudget: JD.map(
+        (budget): CombatLog => ({ t: "SetActionBudget", budget }),
+        maybe(decodeActionBudget)),
+      DelayTurn: JD.map((creature_id): CombatLog => ({ t: "DelayTurn", creature_id }), JD.string()),
+      ResumeTurn: JD.map((creature_id): CombatLog => ({ t: "ResumeTurn", creature_id }),
+        JD.string()),
+      SwitchTurnMember: JD.map((creature_id): CombatLog => ({ t: "SwitchTurnMember", creature_id }),
+        JD.string()),
+      SetInitiativeMode: JD.map((mode): CombatLog => ({ t: "SetInitiativeMode", mode }),
+        decodeInitiativeMode),
     });
 
 export const decodeGameLog: Decoder<GameLog> =
   sum<GameLog>("GameLog", { StopCombat: { t: "StopCombat" } }, {
     SetActiveScene: JD.map((scene_id): GameLog => ({ t: "SetActiveScene", scene_id }), JD.string()),
+    SetOutOfCombatCost: JD.map(
+      (cost): GameLog => ({ t: "SetOutOfCombatCost", cost }),
+      decodeOutOfCombatCost),
     RegisterPlayer: JD.map(
       (player_id): GameLog => ({ t: "RegisterPlayer", player_id }),
       JD.string()),
@@ -883,10 +1193,48 @@ export const decodeGameLog: Decoder<GameLog> =
       (scene_id, condition_id): GameLog =>
         ({ t: "RemoveSceneVolumeCondition", scene_id, condition_id })
     ),
+    DecrementVolumeConditionRemaining: JD.object(
+      ["scene_id", JD.string()],
+      ["condition_id", JD.string()],
+      (scene_id, condition_id): GameLog =>
+        ({ t: "DecrementVolumeConditionRemaining", scene_id, condition_id })
+    ),
     EditSceneTerrain: JD.object(
       ["scene_id", JD.string()],
       ["terrain", decodeTerrain],
       (scene_id, terrain): GameLog => ({ t: "EditSceneTerrain", scene_id, terrain })),
+    EditSceneTerrainLevel: JD.object(
+      ["scene_id", JD.string()],
+      ["level", JD.number()],
+      ["terrain", decodeTerrain],
+      (scene_id, level, terrain): GameLog =>
+        ({ t: "EditSceneTerrainLevel", scene_id, level, terrain })),
+    EditSceneStairs: JD.object(
+      ["scene_id", JD.string()],
+      ["stairs", decodeTerrain],
+      (scene_id, stairs): GameLog => ({ t: "EditSceneStairs", scene_id, stairs })),
+    EditSceneTerrainCells: JD.object(
+      ["scene_id", JD.string()],
+      ["terrain_cells", decodeTerrainCells],
+      (scene_id, terrain_cells): GameLog =>
+        ({ t: "EditSceneTerrainCells", scene_id, terrain_cells })),
+    OpenDoor: JD.map(
+      ([scene_id, point]): GameLog => ({ t: "OpenDoor", scene_id, point }),
+      JD.tuple(JD.string(), decodePoint3)),
+    CloseDoor: JD.map(
+      ([scene_id, point]): GameLog => ({ t: "CloseDoor", scene_id, point }),
+      JD.tuple(JD.string(), decodePoint3)),
+    DestroyWall: JD.map(
+      ([scene_id, point]): GameLog => ({ t: "DestroyWall", scene_id, point }),
+      JD.tuple(JD.string(), decodePoint3)),
+    AddSceneTerrain: JD.object(
+      ["scene_id", JD.string()],
+      ["terrain", decodeTerrain],
+      (scene_id, terrain): GameLog => ({ t: "AddSceneTerrain", scene_id, terrain })),
+    RemoveSceneTerrain: JD.object(
+      ["scene_id", JD.string()],
+      ["terrain", decodeTerrain],
+      (scene_id, terrain): GameLog => ({ t: "RemoveSceneTerrain", scene_id, terrain })),
     EditSceneHighlights: JD.object(
       ["scene_id", JD.string()],
       ["highlights", decodeHighlights],
@@ -924,6 +1272,16 @@ export const decodeGameLog: Decoder<GameLog> =
     RemoveCreatureFromCombat: JD.map(
       (creature_id): GameLog => ({ t: "RemoveCreatureFromCombat", creature_id }),
       JD.string()),
+    SetCreatureFaction: JD.map(
+      ([creature_id, faction_id]): GameLog => ({ t: "SetCreatureFaction", creature_id, faction_id }),
+      JD.tuple(JD.string(), maybe(JD.string()))),
+    CreateFaction: JD.map((faction): GameLog => ({ t: "CreateFaction", faction }), decodeFaction),
+    SetFactionRelationship: JD.object(
+      ["faction_id", JD.string()],
+      ["other", JD.string()],
+      ["relationship", decodeRelationship],
+      (faction_id, other, relationship): GameLog =>
+        ({ t: "SetFactionRelationship", faction_id, other, relationship })),
     CombatLog: JD.map((log): GameLog => ({ t: "CombatLog", log }), decodeCombatLog),
     CreatureLog: JD.map(([creature_id, log]): GameLog => ({ t: "CreatureLog", creature_id, log }),
       JD.tuple(JD.string(), decodeCreatureLog)),
@@ -932,6 +1290,7 @@ export const decodeGameLog: Decoder<GameLog> =
         ({ t: "AttributeCheckResult", cid, check, actual, success }),
       JD.tuple(JD.string(), decodeAttributeCheck, JD.number(), JD.boolean())
     ),
+    DiceSeed: JD.map((seed): GameLog => ({ t: "DiceSeed", seed }), JD.number()),
     Rollback: JD.map(
       ([snapshot_index, log_index]): GameLog => ({ t: "Rollback", snapshot_index, log_index }),
       JD.tuple(JD.number(), JD.number())),
@@ -961,11 +1320,30 @@ function decodeNonEmpty<T>(valueDecoder: Decoder<T>): Decoder<{ cursor: number;
     (cursor, data) => ({ cursor, data }));
 }
 
-const decodeCombat: Decoder<Combat> = JD.object(
+const decodeCombat: Decoder<Combat> = objectBig(
   ["scene", JD.string()],
   ["creatures", decodeNonEmpty(JD.tuple(JD.string(), JD.number()))],
+  ["round", JD.number()],
   ["movement_used", JD.number()],
-  (scene, creatures, movement_used) => ({ scene, creatures, movement_used })
+  ["pending_interrupts", JD.array(decodeInterrupt)],
+  ["reactions_used", JD.dict(JD.number())],
+  ["readied_actions", JD.dict(decodeReadiedAction)],
+  ["volume_conditions_applied", JD.dict(JD.array(JD.string()))],
+  ["action_budget", maybe(decodeActionBudget)],
+  ["actions_used", JD.dict(JD.number())],
+  ["group_movement_used", JD.dict(JD.number())],
+  ["group_actions_used", JD.dict(JD.dict(JD.number()))],
+  ["delayed", JD.array(JD.string())],
+  ["resuming", JD.array(JD.string())],
+  ["initiative_mode", decodeInitiativeMode],
+  (scene, creatures, round, movement_used, pending_interrupts, reactions_used, readied_actions,
+    volume_conditions_applied, action_budget, actions_used, group_movement_used,
+    group_actions_used, delayed, resuming, initiative_mode): Combat =>
+    ({
+      scene, creatures, round, movement_used, pending_interrupts, reactions_used,
+      readied_actions, volume_conditions_applied, action_budget, actions_used,
+      group_movement_used, group_actions_used, delayed, resuming, initiative_mode,
+    })
 );
 
 
@@ -988,26 +1366,39 @@ const decodeFolder: Decoder<Folder> = JD.object(
   (data, children) => ({ data, children })
 );
 
+const decodeTargetFilter: Decoder<TargetFilter> = JD.oneOf(
+  JD.equal('Any' as TargetFilter), JD.equal('Allies' as TargetFilter),
+  JD.equal('Enemies' as TargetFilter));
+
 const decodeCreatureTarget: Decoder<CreatureTarget> = sum<CreatureTarget>("TargetSpec",
   {
     Actor: { t: "Actor" },
-    Melee: { t: "Melee" },
   },
   // | { t: "Volume"; volume: Volume; range: Distance }
 
   {
-    Range: JD.map((distance): CreatureTarget => ({ t: "Range", distance }), JD.number()),
+    Melee: JD.object(
+      ["filter", decodeTargetFilter],
+      (filter): CreatureTarget => ({ t: "Melee", filter })),
+    Range: JD.object(
+      ["distance", JD.number()],
+      ["filter", decodeTargetFilter],
+      (distance, filter): CreatureTarget => ({ t: "Range", distance, filter })),
     SomeCreaturesInVolumeInRange: JD.object(
       ["volume", decodeVolume], ["maximum", JD.number()], ["range", JD.number()],
-      (volume, maximum, range): CreatureTarget =>
-        ({ t: "SomeCreaturesInVolumeInRange", volume, maximum, range })),
+      ["filter", decodeTargetFilter],
+      (volume, maximum, range, filter): CreatureTarget =>
+        ({ t: "SomeCreaturesInVolumeInRange", volume, maximum, range, filter })),
     AllCreaturesInVolumeInRange: JD.object(
       ["volume", decodeVolume],
       ["range", JD.number()],
-      (volume, range): CreatureTarget => ({ t: "AllCreaturesInVolumeInRange", volume, range })),
+      ["filter", decodeTargetFilter],
+      (volume, range, filter): CreatureTarget =>
+        ({ t: "AllCreaturesInVolumeInRange", volume, range, filter })),
     LineFromActor: JD.object(
       ["distance", JD.number()],
-      (distance): CreatureTarget => ({ t: "LineFromActor", distance })),
+      ["filter", decodeTargetFilter],
+      (distance, filter): CreatureTarget => ({ t: "LineFromActor", distance, filter })),
   });
 
 export const decodeSceneTarget: Decoder<SceneTarget> = sum<SceneTarget>("SceneTarget", {}, {
@@ -1023,16 +1414,32 @@ export const decodeAction: Decoder<Action> = sum<Action>("Action", {},
       (target): Action => ({ t: "Creature", target })),
     SceneVolume: JD.object(["target", decodeSceneTarget],
       (target): Action => ({ t: "SceneVolume", target })),
+    MoveActor: JD.object(["distance", JD.number()],
+      (distance): Action => ({ t: "MoveActor", distance })),
+    Multi: JD.map((parts): Action => ({ t: "Multi", parts }),
+      JD.array(JD.tuple(JD.string(), JD.lazy(() => decodeAction)))),
   }
 );
 
+const decodeActionSlot: Decoder<ActionSlot> = JD.oneOf(
+  JD.equal('Standard' as ActionSlot), JD.equal('Minor' as ActionSlot),
+  JD.equal('Free' as ActionSlot));
+
+const decodeActionBudget: Decoder<ActionBudget> = JD.object(
+  ["standard", JD.number()],
+  ["minor", JD.number()],
+  (standard, minor) => ({ standard, minor }));
+
 const decodeAbility: Decoder<Ability> = JD.object(
   ["name", JD.string()],
   ["id", JD.string()],
   ["action", decodeAction],
   ["cost", JD.number()],
   ["usable_ooc", JD.boolean()],
-  (name, id, action, cost, usable_ooc) => ({ name, id, action, cost, usable_ooc })
+  ["cooldown", JD.number()],
+  ["slot", decodeActionSlot],
+  (name, id, action, cost, usable_ooc, cooldown, slot) =>
+    ({ name, id, action, cost, usable_ooc, cooldown, slot })
 );
 
 const decodeGame: Decoder<Game> = JD.object(
@@ -1044,8 +1451,16 @@ const decodeGame: Decoder<Game> = JD.object(
   ["abilities", JD.dict(decodeAbility)],
   ["campaign", decodeFolder],
   ["players", JD.map(I.Map, JD.dict(decodePlayer))],
-  (current_combat, creatures, classes, items, scenes, abilities, campaign, players) =>
-    ({ current_combat, creatures, classes, items, scenes, abilities, campaign, players })
+  ["ooc_ability_cost", decodeOutOfCombatCost],
+  ["actions_left", maybe(decodeActionBudget)],
+  ["turn_members", JD.array(JD.string())],
+  ["factions", JD.map(I.Map, JD.dict(decodeFaction))],
+  (current_combat, creatures, classes, items, scenes, abilities, campaign, players,
+    ooc_ability_cost, actions_left, turn_members, factions) =>
+    ({
+      current_combat, creatures, classes, items, scenes, abilities, campaign, players,
+      ooc_ability_cost, actions_left, turn_members, factions,
+    })
 );
 
 export const decodeApp: Decoder<App> = JD.object(
@@ -1073,6 +1488,7 @@ export function decodeRustResult<T, E>(decode_ok: Decoder<T>, decode_err: Decode
 export function encodeGameCommand(cmd: GameCommand): object | string {
   switch (cmd.t) {
     case "SetActiveScene": return { SetActiveScene: cmd.scene_id };
+    case "SetOutOfCombatCost": return { SetOutOfCombatCost: cmd.cost };
     case "ChatFromGM": return { ChatFromGM: cmd.message };
     case "ChatFromPlayer": return { ChatFromPlayer: [cmd.player_id, cmd.message] };
     case "RegisterPlayer": return { RegisterPlayer: cmd.player_id };
@@ -1161,6 +1577,13 @@ export function encodeGameCommand(cmd: GameCommand): object | string {
       return {
         SetFocusedSceneCreatures: { scene_id: cmd.scene_id, creatures: cmd.creatures.toArray() },
       };
+    case "AddSceneVolumeCondition":
+      return {
+        AddSceneVolumeCondition: {
+          scene_id: cmd.scene_id, point: encodePoint3(cmd.point), volume: encodeVolume(cmd.volume),
+          condition: encodeCondition(cmd.condition), duration: encodeDuration(cmd.duration),
+        },
+      };
     case "RemoveSceneVolumeCondition":
       return {
         RemoveSceneVolumeCondition: { scene_id: cmd.scene_id, condition_id: cmd.condition_id },
@@ -1169,6 +1592,30 @@ export function encodeGameCommand(cmd: GameCommand): object | string {
       return {
         EditSceneTerrain: { scene_id: cmd.scene_id, terrain: cmd.terrain.map(encodePoint3) },
       };
+    case "EditSceneTerrainLevel":
+      return {
+        EditSceneTerrainLevel: {
+          scene_id: cmd.scene_id, level: cmd.level, terrain: cmd.terrain.map(encodePoint3),
+        },
+      };
+    case "EditSceneStairs":
+      return {
+        EditSceneStairs: { scene_id: cmd.scene_id, stairs: cmd.stairs.map(encodePoint3) },
+      };
+    case "EditSceneTerrainCells":
+      return {
+        EditSceneTerrainCells: {
+          scene_id: cmd.scene_id,
+          terrain_cells: cmd.terrain_cells.mapEntries(
+            ([point, cell]) => [encodePoint3(point), encodeTerrainCell(cell)]).toJS(),
+        },
+      };
+    case "OpenDoor":
+      return { OpenDoor: [cmd.scene_id, encodePoint3(cmd.point)] };
+    case "CloseDoor":
+      return { CloseDoor: [cmd.scene_id, encodePoint3(cmd.point)] };
+    case "DestroyWall":
+      return { DestroyWall: [cmd.scene_id, encodePoint3(cmd.point)] };
     case "EditSceneHighlights":
       return {
         EditSceneHighlights: {
@@ -1202,6 +1649,15 @@ export function encodeGameCommand(cmd: GameCommand): object | string {
       };
     case "RemoveCreatureFromCombat":
       return { RemoveCreatureFromCombat: cmd.creature_id };
+    case "SetCreatureFaction":
+      return { SetCreatureFaction: [cmd.creature_id, cmd.faction_id] };
+    case "CreateFaction": return { CreateFaction: cmd.name };
+    case "SetFactionRelationship":
+      return {
+        SetFactionRelationship: {
+          faction_id: cmd.faction_id, other: cmd.other, relationship: cmd.relationship,
+        },
+      };
     case "CombatAct": return { CombatAct: [cmd.ability_id, encodeDecidedTarget(cmd.target)] };
     case "PathCreature":
       return { PathCreature: [cmd.scene_id, cmd.creature_id, encodePoint3(cmd.dest)] };
@@ -1210,10 +1666,26 @@ export function encodeGameCommand(cmd: GameCommand): object | string {
     case "PathCurrentCombatCreature":
       return { PathCurrentCombatCreature: encodePoint3(cmd.dest) };
     case "Done": return "Done";
+    case "DelayTurn": return "DelayTurn";
+    case "ResumeTurn": return { ResumeTurn: cmd.creature_id };
+    case "SwitchTurnMember": return { SwitchTurnMember: cmd.creature_id };
+    case "ReadyAction":
+      return {
+        ReadyAction: {
+          ability_id: cmd.ability_id,
+          target: encodeDecidedTarget(cmd.target),
+          trigger: encodeReadyTrigger(cmd.trigger),
+        },
+      };
+    case "ReactToInterrupt": return "ReactToInterrupt";
+    case "DeclineInterrupt": return "DeclineInterrupt";
+    case "Rest": return { Rest: [cmd.kind, cmd.creature_ids] };
     case "ChangeCreatureInitiative":
       return { ChangeCreatureInitiative: [cmd.creature_id, cmd.init] };
+    case "SetActionBudget":
+      return { SetActionBudget: cmd.budget === undefined ? null : cmd.budget };
     case "StartCombat":
-      return { StartCombat: [cmd.scene_id, cmd.creature_ids] };
+      return { StartCombat: [cmd.scene_id, cmd.creature_ids, encodeInitiativeMode(cmd.mode)] };
     case "StopCombat":
       return "StopCombat";
     case "AddCreatureToCombat":
@@ -1280,6 +1752,16 @@ function encodeVisibility(vis: Visibility): string {
   return vis.t;
 }
 
+function encodeTerrainCell(cell: TerrainCell): object | string {
+  switch (cell.t) {
+    case "Difficult": return { Difficult: cell.multiplier };
+    case "Water": return "Water";
+    case "Door": return { Door: { open: cell.open } };
+    case "DestructibleWall": return "DestructibleWall";
+    case "Hazard": return { Hazard: encodeEffect(cell.effect) };
+  }
+}
+
 function encodeAttributeCheck(check: AttributeCheck): object {
   return {
     reliable: check.reliable,
@@ -1311,15 +1793,90 @@ function encodeDice(d: Dice): object {
     case "Expr": return { Expr: { num: d.num, size: d.size } };
     case "Plus": return { Plus: [encodeDice(d.left), encodeDice(d.right)] };
     case "BestOf": return { BestOf: [d.num, encodeDice(d.dice)] };
+    case "Minus": return { Minus: [encodeDice(d.left), encodeDice(d.right)] };
+    case "Roll":
+      const keep = d.keep === undefined ? null
+        : d.keep.t === "Highest" ? { Highest: d.keep.num } : { Lowest: d.keep.num };
+      return {
+        Roll: {
+          num: d.num, size: d.size, explode: d.explode,
+          reroll_below: d.reroll_below === undefined ? null : d.reroll_below, keep,
+        },
+      };
   }
 }
 
-function encodeDecidedTarget(dt: DecidedTarget): object | string {
+function encodeVolume(volume: Volume): object {
+  switch (volume.t) {
+    case "Sphere": return { Sphere: volume.radius };
+    case "Line": return { Line: { vector: encodePoint3(volume.vector) } };
+    case "VerticalCylinder":
+      return { VerticalCylinder: { radius: volume.radius, height: volume.height } };
+    case "AABB": return { AABB: encodeAABB(volume.aabb) };
+  }
+}
+
+function encodeInitiativeMode(mode: InitiativeMode): object | string {
+  switch (mode.t) {
+    case "Individual": return "Individual";
+    case "GroupByClass": return "GroupByClass";
+    case "Groups": return { Groups: mode.groups };
+    case "Sides": return { Sides: mode.sides };
+  }
+}
+
+function encodeDuration(duration: Duration): object | string {
+  switch (duration.t) {
+    case "Interminate": return "Interminate";
+    case "Rounds": return { Rounds: duration.duration };
+  }
+}
+
+function encodeCondition(condition: Condition): object | string {
+  switch (condition.t) {
+    case "RecurringEffect": return { RecurringEffect: encodeEffect(condition.effect) };
+    case "RecurringEffectAt":
+      return { RecurringEffectAt: [condition.timing, encodeEffect(condition.effect)] };
+    case "Dead": return "Dead";
+    case "Incapacitated": return "Incapacitated";
+    case "AddDamageBuff": return { AddDamageBuff: condition.hp };
+    case "DoubleMaxMovement": return "DoubleMaxMovement";
+    case "ActivateAbility": return { ActivateAbility: condition.ability_id };
+  }
+}
+
+function encodeEffect(effect: CreatureEffect): object {
+  switch (effect.t) {
+    case "ApplyCondition":
+      return {
+        ApplyCondition: [encodeDuration(effect.duration), encodeCondition(effect.condition)],
+      };
+    case "Heal": return { Heal: encodeDice(effect.dice) };
+    case "Damage": return { Damage: encodeDice(effect.dice) };
+    case "MultiEffect": return { MultiEffect: effect.effects.map(encodeEffect) };
+    case "GenerateEnergy": return { GenerateEnergy: effect.energy };
+    case "Push": return { Push: effect.distance };
+    case "Pull": return { Pull: effect.distance };
+    case "Knockback":
+      return { Knockback: { distance: effect.distance, impact: encodeDice(effect.impact) } };
+    case "Teleport": return { Teleport: effect.distance };
+  }
+}
+
+export function encodeDecidedTarget(dt: DecidedTarget): object | string {
   switch (dt.t) {
     case "Actor": return "Actor";
     case "Creature": return { Creature: dt.creature_id };
     case "Creatures": return { Creatures: dt.creature_ids };
     case "Point": return { Point: encodePoint3(dt.point) };
+    case "Multi": return { Multi: dt.targets.map(encodeDecidedTarget) };
+  }
+}
+
+function encodeReadyTrigger(trigger: ReadyTrigger): object {
+  switch (trigger.t) {
+    case "CreatureMoves": return { CreatureMoves: trigger.creature_id };
+    case "CreatureActs": return { CreatureActs: trigger.creature_id };
   }
 }
 
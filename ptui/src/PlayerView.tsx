@@ -146,9 +146,14 @@ export const PlayerActionBar = M.connectRedux((
     if (creature) {
       if (LD.includes(props.player.creatures, cid)) {
         return <CV.ActionBar combat={props.combat} creature={creature} />;
-      } else {
-        return <div>{creature.name} is acting</div>;
       }
+      // The player may control another creature taking the same group turn.
+      const mine = props.ptui.app.current_game.turn_members.filter(member =>
+        LD.includes(props.player.creatures, member));
+      return <div style={{ display: "flex" }}>
+        <div>{creature.name} is acting</div>
+        {mine.map(member => <CV.SwitchTurnMemberButton key={member} creature_id={member} />)}
+      </div>;
     } else {
       return <div>Creature disappeared!</div>;
     }
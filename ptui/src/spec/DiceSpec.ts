@@ -1,4 +1,5 @@
 import * as D from '../Dice';
+import * as T from '../PTTypes';
 
 // let describe: any;
 // let it: any;
@@ -42,6 +43,52 @@ describe("dice parsing", () => {
     expect(D.parse(" BestOf ( 5 ,1 d 20 ) "))
       .toEqual({ t: "BestOf", num: 5, dice: { t: "Expr", num: 1, size: 20 } });
   });
+  it("parses a roll without a count", () => {
+    expect(D.parse("d6"))
+      .toEqual({ t: "Expr", num: 1, size: 6 });
+  });
+  it("parses roll modifiers", () => {
+    expect(D.parse("4d6!r<2kh3"))
+      .toEqual({
+        t: "Roll", num: 4, size: 6, explode: true, reroll_below: 2,
+        keep: { t: "Highest", num: 3 },
+      });
+    expect(D.parse("2d20kl1"))
+      .toEqual({
+        t: "Roll", num: 2, size: 20, explode: false, reroll_below: undefined,
+        keep: { t: "Lowest", num: 1 },
+      });
+  });
+  it("parses a subtraction of dice", () => {
+    expect(D.parse("3d6-1d4"))
+      .toEqual({
+        t: "Minus",
+        left: { t: "Expr", num: 3, size: 6 },
+        right: { t: "Expr", num: 1, size: 4 },
+      });
+  });
+  it("parses a negative number", () => {
+    expect(D.parse("-3"))
+      .toEqual({ t: "Flat", val: -3 });
+  });
+  it("rejects invalid dice", () => {
+    for (const input of ["1d0", "1d1!", "1d6r<7", "2d6kh3", "BestOf(0, 1d20)", "1d6!!"]) {
+      expect(D.maybeParse(input).status).toEqual(false);
+    }
+  });
+});
+
+describe("Dice round-tripping", () => {
+  it("formats parsed dice the way they were written", () => {
+    const inputs = [
+      "5", "-3", "1d20", "1d20+5", "1d20-2", "4d6kh3", "2d20kl1", "1d6!", "2d6r<3+5",
+      "3d6!r<2kl1", "3d6-1d4", "1d20+(1d4-1d6)", "1d20-(1d4+2)", "1d8-(-3)",
+      "BestOf(2, 1d20+5)",
+    ];
+    for (const input of inputs) {
+      expect(D.format(D.parse(input))).toEqual(input);
+    }
+  });
 });
 
 
@@ -66,4 +113,13 @@ describe("Dice formatting", () => {
     expect(D.format({ t: "BestOf", num: 20, dice: { t: "Expr", num: 50, size: 100 } }))
       .toEqual("BestOf(20, 50d100)");
   })
+  it("formats a Minus of a negative Flat", () => {
+    expect(D.format({ t: "Minus", left: { t: "Expr", num: 1, size: 8 }, right: { t: "Flat", val: -3 } }))
+      .toEqual("1d8-(-3)");
+  });
+  it("parenthesizes nested sums", () => {
+    const right: T.Dice = { t: "Plus", left: { t: "Expr", num: 1, size: 4 }, right: { t: "Flat", val: 2 } };
+    expect(D.format({ t: "Plus", left: { t: "Expr", num: 1, size: 20 }, right }))
+      .toEqual("1d20+(1d4+2)");
+  });
 });
@@ -134,6 +134,7 @@ describe("PTTypes", () => {
       size: { x: 1, y: 1, z: 1 },
       inventory: {},
       bio: "",
+      faction: null,
     };
     const creature = T.decodeCreature.decodeAny(sample);
     expect(creature.initiative).toEqual({